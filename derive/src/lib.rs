@@ -0,0 +1,120 @@
+//a Documentation
+/*!
+# Derive macros for mod3d-gl
+
+`#[derive(Std140)]` implements `mod3d_gl::Std140` for a structure
+with named fields, laying out its fields with the std140 rules
+!*/
+
+//a Imports
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields};
+
+//a Std140
+//fp derive_std140
+/// Derive `mod3d_gl::Std140` for a structure whose fields all
+/// implement it
+#[proc_macro_derive(Std140)]
+pub fn derive_std140(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_std140(&input).into()
+}
+
+//fi expand_std140
+/// Expand the derive of `mod3d_gl::Std140` for a structure, or a
+/// compile error if it is not a structure with named fields
+fn expand_std140(input: &DeriveInput) -> proc_macro2::TokenStream {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return syn::Error::new_spanned(name, "Std140 requires a struct with named fields")
+                    .to_compile_error();
+            }
+        },
+        _ => {
+            return syn::Error::new_spanned(name, "Std140 can only be derived for a struct")
+                .to_compile_error();
+        }
+    };
+    let idents: Vec<_> = fields.iter().map(|f| f.ident.as_ref().unwrap()).collect();
+    let names: Vec<_> = idents.iter().map(|i| i.to_string()).collect();
+    let tys: Vec<_> = fields.iter().map(|f| &f.ty).collect();
+
+    quote! {
+        impl #impl_generics ::mod3d_gl::Std140 for #name #ty_generics #where_clause {
+            const ALIGN: usize = ::mod3d_gl::std140::struct_align(&[
+                #( <#tys as ::mod3d_gl::Std140>::ALIGN ),*
+            ]);
+            const SIZE: usize = ::mod3d_gl::std140::struct_size(&[
+                #( (<#tys as ::mod3d_gl::Std140>::ALIGN, <#tys as ::mod3d_gl::Std140>::SIZE) ),*
+            ]);
+            fn write_std140(&self, dst: &mut [u8]) {
+                dst.fill(0);
+                let mut offset = 0;
+                #(
+                    offset = ::mod3d_gl::std140::round_up(offset, <#tys as ::mod3d_gl::Std140>::ALIGN);
+                    ::mod3d_gl::Std140::write_std140(
+                        &self.#idents,
+                        &mut dst[offset..offset + <#tys as ::mod3d_gl::Std140>::SIZE],
+                    );
+                    offset += <#tys as ::mod3d_gl::Std140>::SIZE;
+                )*
+                let _ = offset;
+            }
+            fn field_range(name: &str) -> Option<::std::ops::Range<usize>> {
+                let mut offset = 0;
+                #(
+                    offset = ::mod3d_gl::std140::round_up(offset, <#tys as ::mod3d_gl::Std140>::ALIGN);
+                    if name == #names {
+                        return Some(offset..offset + <#tys as ::mod3d_gl::Std140>::SIZE);
+                    }
+                    offset += <#tys as ::mod3d_gl::Std140>::SIZE;
+                )*
+                let _ = offset;
+                None
+            }
+        }
+    }
+}
+
+//a Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn named_fields() {
+        let input: DeriveInput = parse_quote! {
+            struct Light<T> {
+                position: [f32; 3],
+                intensity: T,
+            }
+        };
+        let expanded = expand_std140(&input).to_string();
+        assert!(expanded.contains("impl < T > :: mod3d_gl :: Std140 for Light < T >"));
+        assert!(expanded.contains("struct_size"));
+        assert!(expanded.contains("\"position\""));
+        assert!(expanded.contains("\"intensity\""));
+        assert!(!expanded.contains("compile_error"));
+    }
+
+    #[test]
+    fn not_named_fields() {
+        let tuple: DeriveInput = parse_quote! { struct Light([f32; 3], f32); };
+        assert!(expand_std140(&tuple)
+            .to_string()
+            .contains("Std140 requires a struct with named fields"));
+        let unit: DeriveInput = parse_quote! { struct Light; };
+        assert!(expand_std140(&unit).to_string().contains("compile_error"));
+        let enumeration: DeriveInput = parse_quote! { enum Light { On, Off } };
+        assert!(expand_std140(&enumeration)
+            .to_string()
+            .contains("Std140 can only be derived for a struct"));
+    }
+}
@@ -0,0 +1,61 @@
+//a Documentation
+
+/*!
+
+Check a shader program description (JSON) and its shaders without a
+GL context, for both desktop OpenGL and WebGL2
+
+Usage: mod3d_shader_check <program.json> <shader directory>
+
+Exits with a non-zero status if any description fails its checks
+
+!*/
+
+//a Imports
+use std::path::PathBuf;
+
+use mod3d_gl::{GlslTarget, ShaderProgramDesc};
+
+//a Main
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: {} <program.json> <shader directory>", args[0]);
+        std::process::exit(2);
+    }
+    let desc_path = &args[1];
+    let shader_dir = PathBuf::from(&args[2]);
+
+    let desc_json = match std::fs::read_to_string(desc_path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Failed to read {desc_path}: {e}");
+            std::process::exit(2);
+        }
+    };
+    let desc: ShaderProgramDesc = match serde_json::from_str(&desc_json) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("Failed to parse {desc_path}: {e}");
+            std::process::exit(2);
+        }
+    };
+
+    let read_src = |filename: &str| {
+        let path = shader_dir.join(filename);
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {path:?}: {e}"))
+    };
+
+    let mut failed = false;
+    for target in [GlslTarget::Desktop, GlslTarget::Es300] {
+        let validation = desc.check_offline(&read_src, target);
+        print!("{validation}");
+        if !validation.is_ok() {
+            failed = true;
+        }
+        println!("{desc_path} ({target}): {}", if validation.is_ok() { "ok" } else { "FAILED" });
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
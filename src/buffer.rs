@@ -3,7 +3,61 @@ use std::marker::PhantomData;
 
 use mod3d_base::{BufferAccessor, BufferElementType, VertexAttr};
 
-use crate::{Gl, GlProgram};
+use crate::{AttributeFormat, BufferUsage, Gl, GlBuffer, GlProgram, PendingRead, Std140};
+
+//a Functions
+//fp ele_byte_size
+/// Get the byte size of a buffer element type
+pub(crate) fn ele_byte_size(ele_type: BufferElementType) -> u32 {
+    use BufferElementType::*;
+    match ele_type {
+        Int8 => 1,
+        Int16 | Float16 => 2,
+        Int32 | Float32 => 4,
+    }
+}
+
+//fi read_buffer
+/// Read a byte range of a GPU buffer in to a new Vec
+fn read_buffer<G: Gl>(
+    context: &mut G,
+    gl_buffer: &<G as Gl>::Buffer,
+    byte_offset: usize,
+    byte_length: usize,
+) -> Result<Vec<u8>, String> {
+    let mut data = vec![0; byte_length];
+    context.buffer_read_data(gl_buffer, byte_offset, &mut data)?;
+    Ok(data)
+}
+
+//fi orphan_buffer
+/// Orphan a GPU buffer, unless it is an allocation from a pool
+fn orphan_buffer<G: Gl>(
+    context: &mut G,
+    gl_buffer: &<G as Gl>::Buffer,
+    byte_length: usize,
+    usage: BufferUsage,
+) -> Result<(), String> {
+    if gl_buffer.is_pool_allocation() {
+        return Err("Cannot orphan a buffer allocated from a buffer pool".to_string());
+    }
+    context.buffer_orphan(gl_buffer, byte_length, usage);
+    Ok(())
+}
+
+//fp attribute_columns
+/// Get the number of consecutive attribute locations, and the
+/// elements per location, for vertex data with a number of elements
+/// per vertex; mat3 (9) and mat4 (16) data occupies one location per
+/// column, while other data (including 4 elements, as vec4) occupies
+/// one location
+pub(crate) fn attribute_columns(elements_per_data: u32) -> (u32, u32) {
+    match elements_per_data {
+        9 => (3, 3),
+        16 => (4, 4),
+        n => (1, n),
+    }
+}
 
 //a VertexBuffer
 //tp VertexBuffer
@@ -32,6 +86,8 @@ where
     pub byte_offset: u32,
     /// Stride of data in the buffer - 0 for elements_per_data*sizeof(ele_type)
     pub stride: u32,
+    /// Signedness and interpretation of integer elements
+    pub format: AttributeFormat,
 }
 
 //ip VertexBuffer
@@ -46,19 +102,48 @@ where
         &self.gl_buffer
     }
 
+    //fp of_gl_buffer
+    /// Create a [VertexBuffer] for float (or converted to float)
+    /// attribute data in a GPU buffer, such as that written by
+    /// transform feedback
+    pub fn of_gl_buffer(
+        gl_buffer: <G as Gl>::Buffer,
+        elements_per_data: u32,
+        ele_type: BufferElementType,
+        byte_offset: u32,
+        stride: u32,
+    ) -> Self {
+        let byte_offset = byte_offset + gl_buffer.byte_offset();
+        Self {
+            gl_buffer,
+            elements_per_data,
+            ele_type,
+            byte_offset,
+            stride,
+            format: AttributeFormat::default(),
+        }
+    }
+
     //mp of_view
     /// Create the OpenGL ARRAY_BUFFER buffer using STATIC_DRAW - this copies the data in to OpenGL
-    fn of_view(&mut self, view: &BufferAccessor<G>, render_context: &mut G) {
+    ///
+    /// The format of integer data is that the context has for the
+    /// vertex attribute
+    fn of_view(&mut self, view: &BufferAccessor<G>, attr: VertexAttr, render_context: &mut G) {
         view.data.create_client(render_context);
         self.elements_per_data = view.elements_per_data;
         self.ele_type = view.ele_type;
-        self.byte_offset = view.byte_offset;
+        self.format = render_context.attribute_format(attr, view.ele_type);
         self.stride = view.stride;
         self.gl_buffer = view.data.borrow_client().clone();
+        self.byte_offset = view.byte_offset + self.gl_buffer.byte_offset();
     }
 
     //fp bind_to_vao_attr
     /// Bind the buffer as a vertex attribute to the current VAO
+    ///
+    /// Matrix (mat3 or mat4) data is bound to consecutive attribute
+    /// locations starting at the attribute's location, one per column
     pub fn bind_to_vao_attr(
         &self,
         context: &mut G,
@@ -69,11 +154,74 @@ where
             attr_id,
             self.elements_per_data,
             self.ele_type,
+            self.format,
             self.byte_offset,
             self.stride,
         );
     }
 
+    //mp read
+    /// Read a byte range of the vertex data (relative to its byte
+    /// offset), waiting for the GPU
+    pub fn read(
+        &self,
+        context: &mut G,
+        byte_offset: usize,
+        byte_length: usize,
+    ) -> Result<Vec<u8>, String> {
+        let byte_offset = self.data_byte_offset() + byte_offset;
+        read_buffer(context, &self.gl_buffer, byte_offset, byte_length)
+    }
+
+    //mp read_async
+    /// Start an asynchronous read of a byte range of the vertex data
+    /// (relative to its byte offset)
+    pub fn read_async(
+        &self,
+        context: &mut G,
+        byte_offset: usize,
+        byte_length: usize,
+    ) -> PendingRead<G> {
+        let byte_offset = self.data_byte_offset() + byte_offset;
+        PendingRead::of_buffer(context, &self.gl_buffer, byte_offset, byte_length)
+    }
+
+    //mp update_data
+    /// Update (a portion of) the vertex data, starting at a byte offset
+    /// relative to its byte offset
+    ///
+    /// The GPU buffer may be shared with other attributes (and the
+    /// vertex data of a [mod3d_base::BufferData] should be made dynamic
+    /// with [Gl::buffer_data_create] if it is updated often)
+    pub fn update_data(&self, context: &mut G, data: &[u8], byte_offset: usize) {
+        let byte_offset = self.data_byte_offset() + byte_offset;
+        context.buffer_update_data(&self.gl_buffer, data, byte_offset);
+    }
+
+    //mp orphan
+    /// Replace the whole GPU buffer of the vertex data with new
+    /// (uninitialized) storage of a byte length and usage, so that the
+    /// GPU need not wait for draws using the old contents
+    ///
+    /// This replaces the data of any other attributes in the same GPU
+    /// buffer too; it fails for a buffer allocated from a
+    /// [crate::BufferPool]
+    pub fn orphan(
+        &self,
+        context: &mut G,
+        byte_length: usize,
+        usage: BufferUsage,
+    ) -> Result<(), String> {
+        orphan_buffer(context, &self.gl_buffer, byte_length, usage)
+    }
+
+    //ai data_byte_offset
+    /// Byte offset of the data relative to the start of the data of
+    /// the gl_buffer (which may itself be offset within a pool block)
+    fn data_byte_offset(&self) -> usize {
+        (self.byte_offset - self.gl_buffer.byte_offset()) as usize
+    }
+
     //zz All done
 }
 
@@ -88,12 +236,14 @@ where
         let ele_type = BufferElementType::Float32;
         let byte_offset = 0;
         let stride = 0;
+        let format = AttributeFormat::default();
         Self {
             gl_buffer,
             elements_per_data,
             ele_type,
             byte_offset,
             stride,
+            format,
         }
     }
 }
@@ -109,12 +259,14 @@ where
         let ele_type = self.ele_type;
         let byte_offset = self.byte_offset;
         let stride = self.stride;
+        let format = self.format;
         Self {
             gl_buffer,
             elements_per_data,
             ele_type,
             byte_offset,
             stride,
+            format,
         }
     }
 }
@@ -141,8 +293,11 @@ impl<G> indent_display::DefaultIndentedDisplay for VertexBuffer<G> where G: Gl {
 ///
 /// A subset of a data buffer for use with OpenGL index data.
 ///
-/// An IndexBuffer directly owns the OpenGL buffer which is an
-/// ElementArray rather than vertex data
+/// An IndexBuffer usually directly owns the OpenGL buffer which is an
+/// ElementArray rather than vertex data; if the backend shares index
+/// buffers (see [Gl::share_index_buffers]) then it is the buffer of
+/// the [mod3d_base::BufferData] that the indices are in, and the
+/// indices start at a byte offset within it
 #[derive(Debug)]
 pub struct IndexBuffer<G>
 where
@@ -154,6 +309,8 @@ where
     pub count: u32,
     /// The type of each element
     pub ele_type: BufferElementType,
+    /// Byte offset of the first index in the buffer
+    pub byte_offset: u32,
 }
 
 //ip Default for IndexBuffer
@@ -169,6 +326,7 @@ where
             gl_buffer,
             count,
             ele_type,
+            byte_offset: 0,
         }
     }
 }
@@ -182,10 +340,12 @@ where
         let gl_buffer = self.gl_buffer.clone();
         let count = self.count;
         let ele_type = self.ele_type;
+        let byte_offset = self.byte_offset;
         Self {
             gl_buffer,
             count,
             ele_type,
+            byte_offset,
         }
     }
 }
@@ -196,20 +356,34 @@ where
     G: Gl,
 {
     //mp of_view
-    /// Create the OpenGL ARRAY_BUFFER buffer using STATIC_DRAW - this copies the data in to OpenGL
+    /// Create the OpenGL ELEMENT_ARRAY_BUFFER buffer using STATIC_DRAW - this copies the data in to OpenGL
+    ///
+    /// If the backend shares index buffers then the buffer of the
+    /// view's data is used instead, with the view's byte offset
     fn of_view(view: &BufferAccessor<G>, render_context: &mut G) -> Self {
-        let mut gl_buffer = <G as Gl>::Buffer::default();
-        render_context.init_buffer_of_indices(&mut gl_buffer, view);
+        let (gl_buffer, byte_offset) = {
+            if render_context.share_index_buffers() {
+                view.data.create_client(render_context);
+                let gl_buffer = view.data.borrow_client().clone();
+                let byte_offset = view.byte_offset + gl_buffer.byte_offset();
+                (gl_buffer, byte_offset)
+            } else {
+                let mut gl_buffer = <G as Gl>::Buffer::default();
+                render_context.init_buffer_of_indices(&mut gl_buffer, view);
+                (gl_buffer, 0)
+            }
+        };
         let count = view.elements_per_data;
         let ele_type = view.ele_type;
         println!(
-            "Create indices buffer {} of view {:?}#{}",
-            gl_buffer, view.ele_type, view.elements_per_data
+            "Create indices buffer {} of view {:?}#{} at {}",
+            gl_buffer, view.ele_type, view.elements_per_data, byte_offset
         );
         Self {
             gl_buffer,
             count,
             ele_type,
+            byte_offset,
         }
     }
     //ap gl_buffer
@@ -217,6 +391,53 @@ where
         &self.gl_buffer
     }
 
+    //mp byte_length
+    /// Get the byte length of the indices
+    pub fn byte_length(&self) -> usize {
+        self.count as usize * ele_byte_size(self.ele_type) as usize
+    }
+
+    //mp update_data
+    /// Update (a portion of) the indices, starting at a byte offset
+    /// relative to the first index; the number and type of indices are
+    /// unchanged
+    pub fn update_data(&self, context: &mut G, data: &[u8], byte_offset: usize) {
+        assert!(byte_offset + data.len() <= self.byte_length());
+        let byte_offset = (self.byte_offset - self.gl_buffer.byte_offset()) as usize + byte_offset;
+        context.buffer_update_data(&self.gl_buffer, data, byte_offset);
+    }
+
+    //mp orphan
+    /// Replace the whole GPU buffer of the indices with new
+    /// (uninitialized) storage of a byte length and usage, so that the
+    /// GPU need not wait for draws using the old indices
+    ///
+    /// If index buffers are shared this replaces the rest of the data
+    /// containing the indices too; it fails for a buffer allocated
+    /// from a [crate::BufferPool]
+    pub fn orphan(
+        &self,
+        context: &mut G,
+        byte_length: usize,
+        usage: BufferUsage,
+    ) -> Result<(), String> {
+        orphan_buffer(context, &self.gl_buffer, byte_length, usage)
+    }
+
+    //mp read
+    /// Read the indices as bytes, waiting for the GPU
+    pub fn read(&self, context: &mut G) -> Result<Vec<u8>, String> {
+        let byte_offset = (self.byte_offset - self.gl_buffer.byte_offset()) as usize;
+        read_buffer(context, &self.gl_buffer, byte_offset, self.byte_length())
+    }
+
+    //mp read_async
+    /// Start an asynchronous read of the indices
+    pub fn read_async(&self, context: &mut G) -> PendingRead<G> {
+        let byte_offset = (self.byte_offset - self.gl_buffer.byte_offset()) as usize;
+        PendingRead::of_buffer(context, &self.gl_buffer, byte_offset, self.byte_length())
+    }
+
     //zz All done
 }
 
@@ -228,8 +449,8 @@ where
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         write!(
             f,
-            "Ind({:?}#{} {:?})",
-            self.gl_buffer, self.count, self.ele_type,
+            "Ind({:?}#{} {:?} @{})",
+            self.gl_buffer, self.count, self.ele_type, self.byte_offset,
         )
     }
 }
@@ -291,6 +512,16 @@ where
         }
     }
 
+    //fp index_buffer
+    /// Return the [IndexBuffer] that this [BufferView] is of, if it
+    /// is a view of indices
+    pub fn index_buffer(&self) -> Option<&IndexBuffer<G>> {
+        match self {
+            Self::IndexBuffer(index_buffer) => Some(index_buffer),
+            _ => None,
+        }
+    }
+
     //fp as_vertex_buffer
     /// Return the [VertexBuffer] that this [BufferView] is of - if it
     /// is not a view of vertex attributess then panic
@@ -314,7 +545,7 @@ where
             match self {
                 BufferView::IndexBuffer(_) => panic!("Vertex buffer is already an index buffer"),
                 BufferView::VertexBuffer(vb) => {
-                    vb.of_view(view, renderer);
+                    vb.of_view(view, attr, renderer);
                 }
             }
         }
@@ -369,6 +600,12 @@ where
         G::uniform_buffer_create(context, data, is_dynamic)
     }
 
+    //fp of_std140
+    /// Create a uniform buffer containing the std140 image of a value
+    pub fn of_std140<T: Std140>(context: &mut G, value: &T, is_dynamic: bool) -> Result<Self, ()> {
+        G::uniform_buffer_create(context, &value.to_std140_bytes(), is_dynamic)
+    }
+
     //fp new
     pub fn new(gl_buffer: <G as Gl>::Buffer, byte_length: usize) -> Self {
         Self {
@@ -398,4 +635,169 @@ where
             (byte_offset, byte_length)
         }
     }
+
+    //mp read
+    /// Read a byte range of the buffer (a byte length of zero is the
+    /// whole buffer), waiting for the GPU
+    pub fn read(
+        &self,
+        context: &mut G,
+        byte_offset: usize,
+        byte_length: usize,
+    ) -> Result<Vec<u8>, String> {
+        let (byte_offset, byte_length) = self.offset_and_length(byte_offset, byte_length);
+        read_buffer(context, &self.gl_buffer, byte_offset, byte_length)
+    }
+
+    //mp read_async
+    /// Start an asynchronous read of a byte range of the buffer (a
+    /// byte length of zero is the whole buffer)
+    pub fn read_async(
+        &self,
+        context: &mut G,
+        byte_offset: usize,
+        byte_length: usize,
+    ) -> PendingRead<G> {
+        let (byte_offset, byte_length) = self.offset_and_length(byte_offset, byte_length);
+        PendingRead::of_buffer(context, &self.gl_buffer, byte_offset, byte_length)
+    }
+}
+
+//a TypedUniformBuffer
+//tp TypedUniformBuffer
+/// A [UniformBuffer] whose contents are the std140 image of a value
+/// of type T, so that the whole value or individual fields can be
+/// updated at the correct offsets
+#[derive(Debug)]
+pub struct TypedUniformBuffer<G, T>
+where
+    G: Gl,
+    T: Std140,
+{
+    uniform_buffer: UniformBuffer<G>,
+    phantom: PhantomData<T>,
+}
+
+//ip TypedUniformBuffer
+impl<G, T> TypedUniformBuffer<G, T>
+where
+    G: Gl,
+    T: Std140,
+{
+    //fp new
+    /// Create a uniform buffer initialized to the std140 image of a value
+    pub fn new(context: &mut G, value: &T, is_dynamic: bool) -> Result<Self, ()> {
+        let uniform_buffer = UniformBuffer::of_std140(context, value, is_dynamic)?;
+        Ok(Self {
+            uniform_buffer,
+            phantom: PhantomData,
+        })
+    }
+
+    //ap uniform_buffer
+    /// Borrow the underlying [UniformBuffer] (e.g. to bind it to a
+    /// uniform block binding point)
+    pub fn uniform_buffer(&self) -> &UniformBuffer<G> {
+        &self.uniform_buffer
+    }
+
+    //mp update
+    /// Update the whole buffer with the std140 image of a value
+    pub fn update(&self, context: &mut G, value: &T) {
+        context.uniform_buffer_update_data(&self.uniform_buffer, &value.to_std140_bytes(), 0);
+    }
+
+    //mp update_field
+    /// Update a single named field of the buffer; this fails if T has
+    /// no such field, or if its std140 size differs from that of F
+    pub fn update_field<F: Std140>(
+        &self,
+        context: &mut G,
+        name: &str,
+        value: &F,
+    ) -> Result<(), String> {
+        let range =
+            T::field_range(name).ok_or_else(|| format!("No std140 field '{name}' in buffer"))?;
+        if range.len() != F::SIZE {
+            return Err(format!(
+                "Field '{name}' is {} bytes in std140, but the value is {}",
+                range.len(),
+                F::SIZE
+            ));
+        }
+        context.uniform_buffer_update_data(
+            &self.uniform_buffer,
+            &value.to_std140_bytes(),
+            range.start as u32,
+        );
+        Ok(())
+    }
+}
+
+//a StorageBuffer
+//tp StorageBuffer
+/// A shader storage buffer (SSBO) that may contain the data for a
+/// number of program storage blocks
+///
+/// Unlike a [UniformBuffer] its size is limited only by GPU memory,
+/// and shaders may write to it. Storage buffers are only supported
+/// by OpenGL 4.3 or later; creating one fails for WebGL2
+///
+/// A program's storage block is bound to a *range* of one of these
+#[derive(Debug)]
+pub struct StorageBuffer<G>
+where
+    G: Gl,
+{
+    /// Ref-counted GPU gl buffer to use
+    gl_buffer: <G as Gl>::Buffer,
+    /// This is the size of the buffer (so that length=0 can map to the whole buffer)
+    byte_length: usize,
+}
+
+//ip StorageBuffer
+impl<G> StorageBuffer<G>
+where
+    G: Gl,
+{
+    //fp of_data
+    /// Create a storage buffer containing some data
+    pub fn of_data(context: &mut G, data: &[u8], usage: BufferUsage) -> Result<Self, String> {
+        G::storage_buffer_create(context, data, usage)
+    }
+
+    //fp new
+    pub fn new(gl_buffer: <G as Gl>::Buffer, byte_length: usize) -> Self {
+        Self {
+            gl_buffer,
+            byte_length,
+        }
+    }
+
+    //mp update_data
+    /// Update (a portion of) the buffer starting at a byte offset
+    pub fn update_data(&self, context: &mut G, data: &[u8], byte_offset: usize) {
+        assert!(byte_offset + data.len() <= self.byte_length);
+        context.buffer_update_data(&self.gl_buffer, data, byte_offset);
+    }
+
+    //ap gl_buffer
+    /// Get the gl_buffer of the storage buffer
+    pub fn gl_buffer(&self) -> &<G as Gl>::Buffer {
+        &self.gl_buffer
+    }
+
+    //ap byte_length
+    pub fn byte_length(&self) -> usize {
+        self.byte_length
+    }
+
+    //ap offset_and_length
+    pub fn offset_and_length(&self, byte_offset: usize, byte_length: usize) -> (usize, usize) {
+        if byte_length == 0 {
+            (0, self.byte_length)
+        } else {
+            (byte_offset, byte_length)
+        }
+    }
 }
@@ -0,0 +1,297 @@
+//a Documentation
+
+/*!
+
+Sub-allocation of many [mod3d_base::BufferData] in to a few large GPU buffers
+
+A [BufferPool] owns a number of GPU buffers ('blocks') of a fixed
+size; the data of each [mod3d_base::BufferData] that is pooled is
+copied in to a free range of a block (at an aligned offset), and its
+client buffer is a clone of the block's buffer with that byte offset
+(see [crate::GlBuffer::byte_offset]). Vertex and index buffers add the
+byte offset to their own offsets.
+
+The range is returned to the block's free list when the last clone
+of the client buffer is dropped; data larger than a block is not
+pooled.
+
+!*/
+
+//a Imports
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::{Rc, Weak};
+
+use crate::{BufferUsage, Gl, GlBuffer};
+
+//a FreeList
+//ti FreeList
+/// The free byte ranges of a pool block, in order of offset and
+/// with no two adjacent
+#[derive(Debug)]
+struct FreeList {
+    free: Vec<Range<usize>>,
+}
+
+//ii FreeList
+impl FreeList {
+    //fi new
+    fn new(byte_length: usize) -> Self {
+        Self {
+            free: vec![Range {
+                start: 0,
+                end: byte_length,
+            }],
+        }
+    }
+
+    //mi allocate
+    /// Allocate the first free range with space for an aligned byte length
+    fn allocate(&mut self, byte_length: usize, alignment: usize) -> Option<Range<usize>> {
+        for i in 0..self.free.len() {
+            let r = self.free[i].clone();
+            let start = r.start.div_ceil(alignment) * alignment;
+            let end = start + byte_length;
+            if end > r.end {
+                continue;
+            }
+            self.free.remove(i);
+            if end < r.end {
+                self.free.insert(i, end..r.end);
+            }
+            // The alignment padding stays free
+            if r.start < start {
+                self.free.insert(i, r.start..start);
+            }
+            return Some(start..end);
+        }
+        None
+    }
+
+    //mi release
+    /// Return a range to the free list, merging it with its neighbours
+    fn release(&mut self, range: Range<usize>) {
+        let i = self.free.partition_point(|r| r.start < range.start);
+        self.free.insert(i, range);
+        if i + 1 < self.free.len() && self.free[i].end == self.free[i + 1].start {
+            self.free[i].end = self.free.remove(i + 1).end;
+        }
+        if i > 0 && self.free[i - 1].end == self.free[i].start {
+            self.free[i - 1].end = self.free.remove(i).end;
+        }
+    }
+
+    //ai free_bytes
+    fn free_bytes(&self) -> usize {
+        self.free.iter().map(|r| r.len()).sum()
+    }
+}
+
+//a PoolAllocation
+//tp PoolAllocation
+/// A range of a [BufferPool] block; when dropped the range is
+/// returned to the block's free list (if the pool still exists)
+#[derive(Debug)]
+pub struct PoolAllocation {
+    /// The free list of the block
+    free_list: Weak<RefCell<FreeList>>,
+    /// The byte range of the allocation in the block
+    range: Range<usize>,
+}
+
+//ip PoolAllocation
+impl PoolAllocation {
+    //ap byte_offset
+    /// Get the byte offset of the allocation in its block
+    pub fn byte_offset(&self) -> usize {
+        self.range.start
+    }
+
+    //ap byte_length
+    /// Get the byte length of the allocation
+    pub fn byte_length(&self) -> usize {
+        self.range.len()
+    }
+}
+
+//ip Drop for PoolAllocation
+impl Drop for PoolAllocation {
+    fn drop(&mut self) {
+        if let Some(free_list) = self.free_list.upgrade() {
+            free_list.borrow_mut().release(self.range.clone());
+        }
+    }
+}
+
+//a BufferPool
+//tp BufferPool
+/// A pool of large GPU buffers from which the buffers of many
+/// [mod3d_base::BufferData] are allocated
+#[derive(Debug)]
+pub struct BufferPool<G>
+where
+    G: Gl,
+{
+    /// Byte size of each block
+    block_size: usize,
+    /// Alignment of the byte offset of each allocation
+    alignment: usize,
+    /// Usage of the block buffers
+    usage: BufferUsage,
+    /// The blocks and their free lists
+    blocks: Vec<(<G as Gl>::Buffer, Rc<RefCell<FreeList>>)>,
+}
+
+//ip BufferPool
+impl<G> BufferPool<G>
+where
+    G: Gl,
+{
+    //fp new
+    /// Create a pool of blocks of a byte size, with allocations at
+    /// byte offsets that are a multiple of an alignment
+    pub fn new(block_size: usize, alignment: usize, usage: BufferUsage) -> Self {
+        Self {
+            block_size,
+            alignment: alignment.max(1),
+            usage,
+            blocks: Vec::new(),
+        }
+    }
+
+    //mp allocate
+    /// Allocate a range for data from a block (creating a block if
+    /// required) and copy the data in to it, returning a buffer for
+    /// the range
+    ///
+    /// Returns None if the data is larger than a block
+    pub fn allocate(&mut self, context: &mut G, data: &[u8]) -> Option<<G as Gl>::Buffer> {
+        if data.is_empty() || data.len() > self.block_size {
+            return None;
+        }
+        let found = self
+            .blocks
+            .iter()
+            .enumerate()
+            .find_map(|(i, (_, free_list))| {
+                free_list
+                    .borrow_mut()
+                    .allocate(data.len(), self.alignment)
+                    .map(|range| (i, range))
+            });
+        let (block, range) = {
+            if let Some(found) = found {
+                found
+            } else {
+                let buffer = context.buffer_create(self.block_size, self.usage);
+                let mut free_list = FreeList::new(self.block_size);
+                let range = free_list.allocate(data.len(), self.alignment)?;
+                self.blocks.push((buffer, Rc::new(RefCell::new(free_list))));
+                (self.blocks.len() - 1, range)
+            }
+        };
+        let (buffer, free_list) = &self.blocks[block];
+        context.buffer_update_data(buffer, data, range.start);
+        let allocation = PoolAllocation {
+            free_list: Rc::downgrade(free_list),
+            range,
+        };
+        Some(buffer.of_pool_allocation(allocation))
+    }
+
+    //ap num_blocks
+    /// Get the number of blocks (GPU buffers) in the pool
+    pub fn num_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    //ap bytes_allocated
+    /// Get the number of bytes of the blocks that are allocated
+    pub fn bytes_allocated(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|(_, free_list)| self.block_size - free_list.borrow().free_bytes())
+            .sum()
+    }
+}
+
+//a Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocate() {
+        let mut free_list = FreeList::new(100);
+        assert_eq!(free_list.allocate(30, 1), Some(0..30));
+        assert_eq!(free_list.allocate(30, 1), Some(30..60));
+        assert_eq!(free_list.free_bytes(), 40);
+        assert_eq!(free_list.allocate(50, 1), None);
+        assert_eq!(free_list.allocate(40, 1), Some(60..100));
+        assert_eq!(free_list.free_bytes(), 0);
+        assert!(free_list.free.is_empty());
+        assert_eq!(free_list.allocate(1, 1), None);
+    }
+
+    #[test]
+    fn alignment() {
+        let mut free_list = FreeList::new(256);
+        assert_eq!(free_list.allocate(10, 16), Some(0..10));
+        // The padding before an aligned allocation stays free
+        assert_eq!(free_list.allocate(10, 16), Some(16..26));
+        assert_eq!(free_list.free, [10..16, 26..256]);
+        // and may be used by allocations that fit it
+        assert_eq!(free_list.allocate(6, 2), Some(10..16));
+        assert_eq!(free_list.allocate(8, 64), Some(64..72));
+        assert_eq!(free_list.free, [26..64, 72..256]);
+        // An allocation that does not fit a range once aligned skips it
+        assert_eq!(free_list.allocate(40, 32), Some(96..136));
+        assert_eq!(free_list.free, [26..64, 72..96, 136..256]);
+    }
+
+    #[test]
+    fn release_and_coalesce() {
+        let mut free_list = FreeList::new(100);
+        let a = free_list.allocate(20, 1).unwrap();
+        let b = free_list.allocate(20, 1).unwrap();
+        let c = free_list.allocate(20, 1).unwrap();
+        let d = free_list.allocate(20, 1).unwrap();
+        free_list.release(a);
+        free_list.release(c);
+        assert_eq!(free_list.free, [0..20, 40..60, 80..100]);
+        // Merged with the ranges before and after
+        free_list.release(b);
+        assert_eq!(free_list.free, [0..60, 80..100]);
+        free_list.release(d);
+        assert_eq!(free_list.free.len(), 1);
+        assert_eq!(free_list.free_bytes(), 100);
+        // The first range that fits is used
+        assert_eq!(free_list.allocate(100, 1), Some(0..100));
+    }
+
+    #[test]
+    fn release_first_fit() {
+        let mut free_list = FreeList::new(100);
+        let a = free_list.allocate(10, 1).unwrap();
+        let _b = free_list.allocate(30, 1).unwrap();
+        free_list.release(a);
+        assert_eq!(free_list.allocate(20, 1), Some(40..60));
+        assert_eq!(free_list.allocate(10, 1), Some(0..10));
+    }
+
+    #[test]
+    fn allocation_drop() {
+        let free_list = Rc::new(RefCell::new(FreeList::new(64)));
+        let range = free_list.borrow_mut().allocate(32, 16).unwrap();
+        let allocation = PoolAllocation {
+            free_list: Rc::downgrade(&free_list),
+            range,
+        };
+        assert_eq!(allocation.byte_offset(), 0);
+        assert_eq!(allocation.byte_length(), 32);
+        assert_eq!(free_list.borrow().free_bytes(), 32);
+        drop(allocation);
+        assert_eq!(free_list.borrow().free.len(), 1);
+        assert_eq!(free_list.borrow().free_bytes(), 64);
+    }
+}
@@ -0,0 +1,377 @@
+//a Documentation
+
+/*!
+
+Cube map textures, created from six faces or from an equirectangular
+(latitude-longitude) image
+
+The faces of a cube map are in the GL order +X, -X, +Y, -Y, +Z, -Z;
+each is a square image whose first row is the top of the face when
+viewed from the center of the cube, as the GL cube map convention
+requires.
+
+An equirectangular image covers 360 degrees of longitude across its
+width, with the -Z direction at its center (+X three quarters of the
+way across, and +Z at its left and right edges), and latitude from
++90 degrees (the +Y direction) at its first row to -90 at its last;
+it is resampled on the CPU (with bilinear filtering) to the six
+faces.
+
+A cube map is bound by [crate::Gl::program_use_texture] as a
+samplerCube, usually as [crate::TextureId::Environment].
+
+!*/
+
+//a Imports
+use mod3d_base::BufferElementType;
+
+use crate::Gl;
+
+//a Functions
+//fi face_direction
+/// Get the (unnormalized) direction from the center of the cube
+/// through a point on a face, with (s, t) from -1 to 1 (t from the
+/// top of the face)
+fn face_direction(face: usize, s: f32, t: f32) -> [f32; 3] {
+    match face {
+        0 => [1.0, -t, -s],
+        1 => [-1.0, -t, s],
+        2 => [s, 1.0, t],
+        3 => [s, -1.0, -t],
+        4 => [s, -t, 1.0],
+        _ => [-s, -t, -1.0],
+    }
+}
+
+//fi read_element
+/// Read the element at an index of data as a float
+fn read_element(data: &[u8], ele_type: BufferElementType, index: usize) -> f32 {
+    match ele_type {
+        BufferElementType::Float32 => {
+            let b = &data[index * 4..index * 4 + 4];
+            f32::from_ne_bytes([b[0], b[1], b[2], b[3]])
+        }
+        _ => data[index] as f32,
+    }
+}
+
+//fi write_element
+/// Write a float as the element at an index of data
+fn write_element(data: &mut [u8], ele_type: BufferElementType, index: usize, value: f32) {
+    match ele_type {
+        BufferElementType::Float32 => {
+            data[index * 4..index * 4 + 4].copy_from_slice(&value.to_ne_bytes());
+        }
+        _ => {
+            data[index] = value.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+//fp cube_faces_of_equirectangular
+/// Resample an equirectangular image, of a width and height with a
+/// number of channels of an element type, to the six faces of a cube
+/// map of a size
+///
+/// Only 8-bit unsigned (Int8) and Float32 elements are supported
+pub fn cube_faces_of_equirectangular(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    channels: usize,
+    ele_type: BufferElementType,
+    face_size: usize,
+) -> Result<[Vec<u8>; 6], String> {
+    let ele_size = match ele_type {
+        BufferElementType::Int8 => 1,
+        BufferElementType::Float32 => 4,
+        _ => {
+            return Err(format!(
+                "Cannot resample an equirectangular image of {ele_type:?}"
+            ));
+        }
+    };
+    if width == 0 || height == 0 || data.len() < width * height * channels * ele_size {
+        return Err(format!(
+            "Equirectangular image data too small for {width}x{height}x{channels}"
+        ));
+    }
+    let face_byte_size = face_size * face_size * channels * ele_size;
+    let mut faces: [Vec<u8>; 6] = Default::default();
+    for (face, face_data) in faces.iter_mut().enumerate() {
+        face_data.resize(face_byte_size, 0);
+        for y in 0..face_size {
+            let t = (2.0 * (y as f32 + 0.5) / face_size as f32) - 1.0;
+            for x in 0..face_size {
+                let s = (2.0 * (x as f32 + 0.5) / face_size as f32) - 1.0;
+                let [dx, dy, dz] = face_direction(face, s, t);
+                let len = (dx * dx + dy * dy + dz * dz).sqrt();
+                let longitude = dx.atan2(-dz);
+                let latitude = (dy / len).clamp(-1.0, 1.0).acos();
+                // Pixel coordinates in the image, with pixel centers at +0.5
+                let u = (0.5 + longitude / std::f32::consts::TAU) * width as f32 - 0.5;
+                let v = (latitude / std::f32::consts::PI) * height as f32 - 0.5;
+                let u0 = u.floor();
+                let v0 = v.floor();
+                let (fu, fv) = (u - u0, v - v0);
+                let wrap_x = |x: f32| (x as isize).rem_euclid(width as isize) as usize;
+                let clamp_y = |y: f32| (y.max(0.0) as usize).min(height - 1);
+                let (x0, x1) = (wrap_x(u0), wrap_x(u0 + 1.0));
+                let (y0, y1) = (clamp_y(v0), clamp_y(v0 + 1.0));
+                for c in 0..channels {
+                    let texel = |x: usize, y: usize| {
+                        read_element(data, ele_type, (y * width + x) * channels + c)
+                    };
+                    let top = texel(x0, y0) * (1.0 - fu) + texel(x1, y0) * fu;
+                    let bottom = texel(x0, y1) * (1.0 - fu) + texel(x1, y1) * fu;
+                    let value = top * (1.0 - fv) + bottom * fv;
+                    write_element(
+                        face_data,
+                        ele_type,
+                        (y * face_size + x) * channels + c,
+                        value,
+                    );
+                }
+            }
+        }
+    }
+    Ok(faces)
+}
+
+//a CubeMap
+//tp CubeMap
+/// A cube map texture
+pub struct CubeMap<G>
+where
+    G: Gl,
+{
+    /// The GL texture
+    texture: <G as Gl>::Texture,
+    /// The width and height of each face
+    face_size: usize,
+}
+
+//ip CubeMap
+impl<G> CubeMap<G>
+where
+    G: Gl,
+{
+    //fp of_faces
+    /// Create a cube map from six square textures (+X, -X, +Y, -Y,
+    /// +Z, -Z) of the same size and data type, optionally generating
+    /// mipmaps
+    pub fn of_faces(
+        context: &mut G,
+        faces: [&mod3d_base::Texture<G>; 6],
+        mipmaps: bool,
+    ) -> Result<Self, String> {
+        let (width, height, depth) = *faces[0].dims();
+        let data_type = faces[0].data_type();
+        if width != height || depth != 0 {
+            return Err("Cube map faces must be square 2D textures".into());
+        }
+        if faces
+            .iter()
+            .any(|f| *f.dims() != (width, height, depth) || f.data_type() != data_type)
+        {
+            return Err("Cube map faces must have the same size and data type".into());
+        }
+        let face_size = width;
+        let data = faces.map(|f| f.data());
+        let texture = context.texture_create_cube(
+            face_size,
+            data_type.0 as usize,
+            data_type.1,
+            data,
+            mipmaps,
+        )?;
+        Ok(Self { texture, face_size })
+    }
+
+    //fp of_equirectangular
+    /// Create a cube map with faces of a size from an equirectangular
+    /// texture, optionally generating mipmaps
+    pub fn of_equirectangular(
+        context: &mut G,
+        image: &mod3d_base::Texture<G>,
+        face_size: usize,
+        mipmaps: bool,
+    ) -> Result<Self, String> {
+        let (width, height, depth) = *image.dims();
+        if depth != 0 {
+            return Err("An equirectangular image must be a 2D texture".into());
+        }
+        let data_type = image.data_type();
+        let (channels, ele_type) = (data_type.0, data_type.1);
+        let faces = cube_faces_of_equirectangular(
+            image.data(),
+            width,
+            height,
+            channels as usize,
+            ele_type,
+            face_size,
+        )?;
+        let faces = [
+            &faces[0][..],
+            &faces[1][..],
+            &faces[2][..],
+            &faces[3][..],
+            &faces[4][..],
+            &faces[5][..],
+        ];
+        let texture =
+            context.texture_create_cube(face_size, channels as usize, ele_type, faces, mipmaps)?;
+        Ok(Self { texture, face_size })
+    }
+
+    //ap texture
+    /// Borrow the GL texture (e.g. for [crate::Gl::program_use_texture])
+    pub fn texture(&self) -> &<G as Gl>::Texture {
+        &self.texture
+    }
+
+    //ap face_size
+    /// Get the width and height of each face
+    pub fn face_size(&self) -> usize {
+        self.face_size
+    }
+}
+
+//ip Debug for CubeMap
+impl<G> std::fmt::Debug for CubeMap<G>
+where
+    G: Gl,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        f.debug_struct("CubeMap")
+            .field("face_size", &self.face_size)
+            .finish()
+    }
+}
+
+//a Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //fi face_of_direction
+    /// Get the face and (s, t) of a direction with the selection rules
+    /// of the GL specification (table 8.19)
+    fn face_of_direction([x, y, z]: [f32; 3]) -> (usize, f32, f32) {
+        let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+        let (face, sc, tc, ma) = {
+            if ax >= ay && ax >= az {
+                if x > 0.0 {
+                    (0, -z, -y, ax)
+                } else {
+                    (1, z, -y, ax)
+                }
+            } else if ay >= az {
+                if y > 0.0 {
+                    (2, x, z, ay)
+                } else {
+                    (3, x, -z, ay)
+                }
+            } else if z > 0.0 {
+                (4, x, -y, az)
+            } else {
+                (5, -x, -y, az)
+            }
+        };
+        (face, sc / ma, tc / ma)
+    }
+
+    //fi float_image
+    /// Create a Float32 image with two channels, the first a function
+    /// of the column and the second the row
+    fn float_image(width: usize, height: usize, column: fn(usize) -> f32) -> Vec<u8> {
+        let mut data = vec![];
+        for y in 0..height {
+            for x in 0..width {
+                data.extend_from_slice(&column(x).to_ne_bytes());
+                data.extend_from_slice(&(y as f32).to_ne_bytes());
+            }
+        }
+        data
+    }
+
+    //fi face_texel
+    /// Read the two Float32 channels of the first texel of a face
+    fn face_texel(face: &[u8]) -> (f32, f32) {
+        (
+            read_element(face, BufferElementType::Float32, 0),
+            read_element(face, BufferElementType::Float32, 1),
+        )
+    }
+
+    //fi assert_texel
+    /// Assert that the first texel of a face is close to a value
+    fn assert_texel(face: &[u8], value: (f32, f32)) {
+        let texel = face_texel(face);
+        assert!(
+            (texel.0 - value.0).abs() < 1e-3 && (texel.1 - value.1).abs() < 1e-3,
+            "texel {texel:?} is not {value:?}"
+        );
+    }
+
+    #[test]
+    fn face_directions() {
+        for face in 0..6 {
+            for (s, t) in [(0.0, 0.0), (0.5, -0.25), (-0.75, 0.5), (0.9, 0.9)] {
+                let (f, fs, ft) = face_of_direction(face_direction(face, s, t));
+                assert_eq!(f, face, "face of ({s}, {t})");
+                assert!((fs - s).abs() < 1e-6 && (ft - t).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn equirectangular_directions() {
+        // The texel at the center of faces of size 1 samples the
+        // direction of the face
+        let data = float_image(8, 4, |x| (x * x) as f32);
+        let faces =
+            cube_faces_of_equirectangular(&data, 8, 4, 2, BufferElementType::Float32, 1).unwrap();
+        // -Z is at the center, between columns 3 and 4, on the equator
+        assert_texel(&faces[5], (12.5, 1.5));
+        // +X is three quarters across, between columns 5 and 6
+        assert_texel(&faces[0], (30.5, 1.5));
+        // -X is one quarter across, between columns 1 and 2
+        assert_texel(&faces[1], (2.5, 1.5));
+        // +Y and -Y are the first and last rows
+        assert!(face_texel(&faces[2]).1.abs() < 1e-3);
+        assert!((face_texel(&faces[3]).1 - 3.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn equirectangular_wraparound() {
+        // +Z is at the left and right edges, so it is filtered from
+        // the last and first columns
+        let data = float_image(8, 4, |x| (x * x) as f32);
+        let faces =
+            cube_faces_of_equirectangular(&data, 8, 4, 2, BufferElementType::Float32, 1).unwrap();
+        assert_texel(&faces[4], (24.5, 1.5));
+    }
+
+    #[test]
+    fn equirectangular_bytes() {
+        let data = vec![200; 16 * 8 * 3];
+        let faces =
+            cube_faces_of_equirectangular(&data, 16, 8, 3, BufferElementType::Int8, 4).unwrap();
+        for face in &faces {
+            assert_eq!(face.len(), 4 * 4 * 3);
+            assert!(face.iter().all(|b| *b == 200));
+        }
+    }
+
+    #[test]
+    fn equirectangular_errors() {
+        let data = vec![0; 16 * 8 * 3];
+        assert!(
+            cube_faces_of_equirectangular(&data, 16, 8, 3, BufferElementType::Int16, 4).is_err()
+        );
+        assert!(
+            cube_faces_of_equirectangular(&data, 16, 8, 4, BufferElementType::Int8, 4).is_err()
+        );
+    }
+}
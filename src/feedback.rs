@@ -0,0 +1,163 @@
+//a Documentation
+
+/*!
+
+Capture of vertex shader outputs with transform feedback
+
+A program whose [crate::ShaderProgramDesc] lists feedback varyings
+writes those outputs, interleaved, for each vertex drawn between
+[FeedbackBuffer::begin] and [FeedbackBuffer::end], in to the GPU
+buffer of a [FeedbackBuffer].
+
+The captured data can be read back to the CPU (e.g. skinned positions
+for collision checks) with [FeedbackBuffer::read], or used as the
+input to later draws (e.g. particle state updated on the GPU) by
+binding a [crate::VertexBuffer] from [FeedbackBuffer::attribute] to
+a VAO. A buffer must not be drawn from while it is being captured to.
+
+With WebGL2 only points, lines and triangles can be captured, and the
+draws while capturing must be non-indexed and of exactly that type.
+
+!*/
+
+//a Imports
+use mod3d_base::BufferElementType;
+
+use crate::{BufferUsage, Gl, VertexBuffer};
+
+//a FeedbackBuffer
+//tp FeedbackBuffer
+/// A GPU buffer that transform feedback writes vertex data in to
+pub struct FeedbackBuffer<G>
+where
+    G: Gl,
+{
+    /// The GPU buffer
+    gl_buffer: <G as Gl>::Buffer,
+    /// Maximum number of vertices that may be captured
+    num_vertices: usize,
+    /// Byte size of the interleaved varyings of a vertex
+    vertex_byte_size: usize,
+}
+
+//ip FeedbackBuffer
+impl<G> FeedbackBuffer<G>
+where
+    G: Gl,
+{
+    //fp new
+    /// Create a buffer to capture a number of vertices, each of
+    /// which has a byte size (the total of its varyings, e.g. 16 for
+    /// a vec4)
+    pub fn new(
+        context: &mut G,
+        num_vertices: usize,
+        vertex_byte_size: usize,
+        usage: BufferUsage,
+    ) -> Self {
+        let gl_buffer = context.buffer_create(num_vertices * vertex_byte_size, usage);
+        Self {
+            gl_buffer,
+            num_vertices,
+            vertex_byte_size,
+        }
+    }
+
+    //ap gl_buffer
+    /// Borrow the GPU buffer
+    pub fn gl_buffer(&self) -> &<G as Gl>::Buffer {
+        &self.gl_buffer
+    }
+
+    //ap num_vertices
+    /// Get the maximum number of vertices that may be captured
+    pub fn num_vertices(&self) -> usize {
+        self.num_vertices
+    }
+
+    //ap vertex_byte_size
+    /// Get the byte size of each vertex
+    pub fn vertex_byte_size(&self) -> usize {
+        self.vertex_byte_size
+    }
+
+    //ap byte_length
+    /// Get the byte length of the buffer
+    pub fn byte_length(&self) -> usize {
+        self.num_vertices * self.vertex_byte_size
+    }
+
+    //mp begin
+    /// Start capturing the feedback varyings of the program in use,
+    /// for draws of a type of primitive; if discard is true then the
+    /// primitives are not rasterized (e.g. for a particle update)
+    pub fn begin(
+        &self,
+        context: &mut G,
+        primitive_type: mod3d_base::PrimitiveType,
+        discard: bool,
+    ) -> Result<(), String> {
+        context.transform_feedback_begin(
+            &self.gl_buffer,
+            0,
+            self.byte_length(),
+            primitive_type,
+            discard,
+        )
+    }
+
+    //mp end
+    /// Stop capturing
+    pub fn end(&self, context: &mut G) {
+        context.transform_feedback_end();
+    }
+
+    //mp attribute
+    /// Get a [VertexBuffer] for one float varying of the captured
+    /// vertices, with a number of elements (e.g. 3 for a vec3) at a
+    /// byte offset within each vertex
+    pub fn attribute(&self, elements_per_data: u32, byte_offset: u32) -> VertexBuffer<G> {
+        VertexBuffer::of_gl_buffer(
+            self.gl_buffer.clone(),
+            elements_per_data,
+            BufferElementType::Float32,
+            byte_offset,
+            self.vertex_byte_size as u32,
+        )
+    }
+
+    //mp read
+    /// Read back the data of the first vertices captured; this waits
+    /// for the GPU to finish the capture
+    pub fn read(&self, context: &mut G, num_vertices: usize) -> Result<Vec<u8>, String> {
+        let num_vertices = num_vertices.min(self.num_vertices);
+        let mut data = vec![0_u8; num_vertices * self.vertex_byte_size];
+        context.buffer_read_data(&self.gl_buffer, 0, &mut data)?;
+        Ok(data)
+    }
+
+    //mp read_f32
+    /// Read back the data of the first vertices captured as floats
+    /// (for varyings that are all float, vecN or matN)
+    pub fn read_f32(&self, context: &mut G, num_vertices: usize) -> Result<Vec<f32>, String> {
+        let data = self.read(context, num_vertices)?;
+        Ok(data
+            .chunks_exact(4)
+            .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+            .collect())
+    }
+}
+
+//ip Debug for FeedbackBuffer
+impl<G> std::fmt::Debug for FeedbackBuffer<G>
+where
+    G: Gl,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        f.debug_struct("FeedbackBuffer")
+            .field("gl_buffer", &self.gl_buffer)
+            .field("num_vertices", &self.num_vertices)
+            .field("vertex_byte_size", &self.vertex_byte_size)
+            .finish()
+    }
+}
@@ -0,0 +1,317 @@
+//a Documentation
+
+/*!
+
+Translation of dialect-neutral GLSL to the dialect required by a backend
+
+A dialect-neutral shader source is GLSL 3.30 / ES 3.00 (or the older
+GLSL 1.x 'attribute', 'varying', 'texture2D' and 'gl_FragColor'
+style), with or without a '#version' directive. The translation:
+
+* removes any '#version' directive and prepends the one for the
+  dialect (keeping a higher desktop version if the source requests
+  one, and raising it as required by the shader stage)
+
+* moves any '#extension' directives to directly after the version
+
+* for GLSL ES, adds a default precision statement for each type
+  that requires one and that the source does not declare a precision
+  for
+
+* replaces 'texture2D', 'texture3D' and 'textureCube' with 'texture'
+
+* replaces 'attribute' and 'varying' with 'in' and 'out' as
+  appropriate for the shader stage
+
+* replaces 'gl_FragColor' in fragment shaders with a declared output
+
+Identifiers in comments are not replaced.
+
+A '#line 1' directive is inserted after the generated header (and
+removed lines are left blank) so that compiler errors refer to the
+line numbers of the original source.
+
+!*/
+
+//a Imports
+use crate::GlShaderType;
+
+//a GlslDialect
+//tp GlslDialect
+/// The GLSL dialect that a backend compiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlslDialect {
+    /// Desktop GLSL core profile with a minimum version (e.g. 330)
+    Desktop(u32),
+    /// GLSL ES 3.00, as used by WebGL2
+    Es300,
+}
+
+//ip GlslDialect
+impl GlslDialect {
+    //mp version_directive
+    /// Get the '#version' directive for a shader stage, given the
+    /// desktop version that the source requested (if any)
+    fn version_directive(&self, shader_type: GlShaderType, requested: Option<u32>) -> String {
+        match self {
+            Self::Es300 => "#version 300 es".into(),
+            Self::Desktop(min_version) => {
+                let stage_version = match shader_type {
+                    GlShaderType::TessControl | GlShaderType::TessEvaluation => 400,
+                    GlShaderType::Compute => 430,
+                    _ => 330,
+                };
+                let version = (*min_version)
+                    .max(stage_version)
+                    .max(requested.unwrap_or(0));
+                format!("#version {version} core")
+            }
+        }
+    }
+}
+
+//a Constants
+/// The output declared to replace gl_FragColor
+const FRAG_COLOR_OUT: &str = "mod3d_FragColor";
+
+/// Types given a default (highp) precision for GLSL ES if the source
+/// does not declare one (float in fragment shaders, sampler3D and the
+/// array and shadow samplers have no default precision)
+const ES_PRECISION_TYPES: &[&str] = &[
+    "float",
+    "int",
+    "sampler3D",
+    "sampler2DArray",
+    "sampler2DShadow",
+    "sampler2DArrayShadow",
+    "samplerCubeShadow",
+];
+
+//a Functions
+//fi replace_identifiers
+/// Replace whole identifiers in a line using a mapping function,
+/// leaving comments unchanged; in_comment is true if the line starts
+/// within a '/* */' comment, and is updated for the end of the line
+pub(crate) fn replace_identifiers<'a, F>(line: &str, in_comment: &mut bool, map: F) -> String
+where
+    F: Fn(&str) -> Option<&'a str>,
+{
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        if *in_comment {
+            if let Some(end) = rest.find("*/") {
+                result.push_str(&rest[..end + 2]);
+                rest = &rest[end + 2..];
+                *in_comment = false;
+            } else {
+                result.push_str(rest);
+                break;
+            }
+        } else if rest.starts_with("//") {
+            result.push_str(rest);
+            break;
+        } else if rest.starts_with("/*") {
+            result.push_str("/*");
+            rest = &rest[2..];
+            *in_comment = true;
+        } else if is_ident(c) {
+            let len = rest.find(|c| !is_ident(c)).unwrap_or(rest.len());
+            let ident = &rest[..len];
+            result.push_str(map(ident).unwrap_or(ident));
+            rest = &rest[len..];
+        } else {
+            result.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    result
+}
+
+//fi precision_type
+/// If a line is a precision statement, return the type it is for
+fn precision_type(line: &str) -> Option<&str> {
+    let mut words = line.split_whitespace();
+    if words.next() != Some("precision") {
+        return None;
+    }
+    words.next()?;
+    words.next().map(|w| w.trim_end_matches(';'))
+}
+
+//fi requested_desktop_version
+/// Parse the argument of a '#version' directive, returning the
+/// version number if it is a desktop version
+fn requested_desktop_version(args: &str) -> Option<u32> {
+    let mut words = args.split_whitespace();
+    let version = words.next()?.parse().ok()?;
+    if words.next() == Some("es") {
+        None
+    } else {
+        Some(version)
+    }
+}
+
+//fp translate
+/// Translate a dialect-neutral shader source to a dialect for a shader stage
+pub fn translate(source: &str, shader_type: GlShaderType, dialect: GlslDialect) -> String {
+    let mut requested = None;
+    let mut extensions = Vec::new();
+    let mut precision_types = Vec::new();
+    let uses_frag_color = std::cell::Cell::new(false);
+    let mut in_comment = false;
+    let mut body = String::with_capacity(source.len());
+
+    let input = shader_type != GlShaderType::Vertex;
+    let map = |ident: &str| match ident {
+        "texture2D" | "texture3D" | "textureCube" => Some("texture"),
+        "attribute" if shader_type == GlShaderType::Vertex => Some("in"),
+        "varying" if input => Some("in"),
+        "varying" => Some("out"),
+        "gl_FragColor" if shader_type == GlShaderType::Fragment => {
+            uses_frag_color.set(true);
+            Some(FRAG_COLOR_OUT)
+        }
+        _ => None,
+    };
+
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        if let Some(args) = trimmed.strip_prefix("#version") {
+            requested = requested_desktop_version(args);
+            body.push('\n');
+            continue;
+        }
+        if trimmed.starts_with("#extension") {
+            extensions.push(trimmed.to_string());
+            body.push('\n');
+            continue;
+        }
+        if !in_comment {
+            if let Some(t) = precision_type(trimmed) {
+                precision_types.push(t.to_string());
+            }
+        }
+        body.push_str(&replace_identifiers(line, &mut in_comment, map));
+        body.push('\n');
+    }
+
+    let mut result = String::with_capacity(body.len() + 256);
+    result.push_str(&dialect.version_directive(shader_type, requested));
+    result.push('\n');
+    for e in extensions {
+        result.push_str(&e);
+        result.push('\n');
+    }
+    if dialect == GlslDialect::Es300 {
+        for t in ES_PRECISION_TYPES {
+            if !precision_types.iter().any(|p| p == t) {
+                result.push_str(&format!("precision highp {t};\n"));
+            }
+        }
+    }
+    if uses_frag_color.get() {
+        result.push_str(&format!("out vec4 {FRAG_COLOR_OUT};\n"));
+    }
+    result.push_str("#line 1\n");
+    result.push_str(&body);
+    result
+}
+
+//a Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //fi body
+    /// The translated source after the '#line 1' directive
+    fn body(translated: &str) -> &str {
+        translated.split_once("#line 1\n").unwrap().1
+    }
+
+    #[test]
+    fn desktop_vertex() {
+        let src = "#version 100\nattribute vec3 Position;\nvarying vec2 Uv;\n";
+        let t = translate(src, GlShaderType::Vertex, GlslDialect::Desktop(330));
+        assert!(t.starts_with("#version 330 core\n"));
+        assert_eq!(body(&t), "\nin vec3 Position;\nout vec2 Uv;\n");
+    }
+
+    #[test]
+    fn desktop_versions() {
+        let src = "#version 450 core\nvoid main() {}\n";
+        let t = translate(src, GlShaderType::Vertex, GlslDialect::Desktop(330));
+        assert!(t.starts_with("#version 450 core\n"));
+        let t = translate("", GlShaderType::Compute, GlslDialect::Desktop(330));
+        assert!(t.starts_with("#version 430 core\n"));
+        let t = translate(
+            "#version 300 es\n",
+            GlShaderType::Vertex,
+            GlslDialect::Desktop(410),
+        );
+        assert!(t.starts_with("#version 410 core\n"));
+    }
+
+    #[test]
+    fn es_fragment() {
+        let src = "varying vec2 Uv;\nuniform sampler2D Tex;\nvoid main() { gl_FragColor = texture2D(Tex, Uv); }\n";
+        let t = translate(src, GlShaderType::Fragment, GlslDialect::Es300);
+        assert!(t.starts_with("#version 300 es\n"));
+        for ty in ES_PRECISION_TYPES {
+            assert!(t.contains(&format!("precision highp {ty};\n")));
+        }
+        assert!(t.contains(&format!("out vec4 {FRAG_COLOR_OUT};\n")));
+        assert_eq!(
+            body(&t),
+            format!("in vec2 Uv;\nuniform sampler2D Tex;\nvoid main() {{ {FRAG_COLOR_OUT} = texture(Tex, Uv); }}\n")
+        );
+    }
+
+    #[test]
+    fn es_partial_precision() {
+        let src = "precision mediump float;\nvoid main() {}\n";
+        let t = translate(src, GlShaderType::Fragment, GlslDialect::Es300);
+        assert!(!t.contains("precision highp float;"));
+        assert!(t.contains("precision highp int;\n"));
+        assert!(t.contains("precision highp sampler3D;\n"));
+        assert!(t.contains("precision highp sampler2DArray;\n"));
+        assert!(t.contains("precision mediump float;\n"));
+    }
+
+    #[test]
+    fn extensions_moved() {
+        let src = "#version 300 es\n#extension GL_EXT_foo : enable\nvoid main() {}\n";
+        let t = translate(src, GlShaderType::Vertex, GlslDialect::Es300);
+        assert!(t.starts_with("#version 300 es\n#extension GL_EXT_foo : enable\n"));
+        // Removed lines are left blank to keep the line numbers
+        assert_eq!(body(&t), "\n\nvoid main() {}\n");
+    }
+
+    #[test]
+    fn comments_unchanged() {
+        let src =
+            "varying vec2 Uv; // varying texture2D\n/* attribute\n varying */ varying vec3 N;\n";
+        let t = translate(src, GlShaderType::Vertex, GlslDialect::Desktop(330));
+        assert_eq!(
+            body(&t),
+            "out vec2 Uv; // varying texture2D\n/* attribute\n varying */ out vec3 N;\n"
+        );
+        let src = "// gl_FragColor\n/* precision mediump float; */\n";
+        let t = translate(src, GlShaderType::Fragment, GlslDialect::Es300);
+        assert!(!t.contains(FRAG_COLOR_OUT));
+        assert!(t.contains("precision highp float;\n"));
+    }
+
+    #[test]
+    fn identifiers_whole() {
+        let mut in_comment = false;
+        let map = |i: &str| (i == "varying").then_some("out");
+        let line = replace_identifiers("varying varying_x xvarying varying;", &mut in_comment, map);
+        assert_eq!(line, "out varying_x xvarying out;");
+        assert!(!in_comment);
+        let line = replace_identifiers("varying /* varying", &mut in_comment, map);
+        assert_eq!(line, "out /* varying");
+        assert!(in_comment);
+    }
+}
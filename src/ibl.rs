@@ -0,0 +1,539 @@
+//a Documentation
+
+/*!
+
+Image-based lighting (IBL) precomputation on the GPU
+
+From an environment [CubeMap] an [IblPrecompute] renders (with
+[crate::Gl::render_to_texture]) the three textures used by the
+split-sum approximation of image-based lighting for a PBR material:
+
+* the diffuse irradiance cube map - the cosine-weighted convolution of
+  the environment, to be sampled in the direction of the normal
+
+* the prefiltered specular cube map - the environment convolved with
+  the GGX distribution, with a roughness of (level / (levels - 1)) in
+  each mipmap level, to be sampled in the direction of reflection at
+  a level of roughness * (levels - 1)
+
+* the BRDF lookup texture - indexed by (N.V, roughness), giving the
+  scale (red) and bias (green) to apply to F0
+
+These are gathered in an [IblTextures], and are bound to a program as
+[TextureId::Irradiance], [TextureId::Prefiltered] and
+[TextureId::BrdfLut] by [IblTextures::use_textures] (a PBR program
+names its samplers for these when it is linked, as for any other
+[TextureId]).
+
+The precomputation draws full-screen triangles with no depth buffer;
+blending should be disabled while it runs. Floating-point targets
+([PixelFormat::RgbaF32], the default) should be used for HDR
+environments; WebGL2 requires EXT_color_buffer_float to render to
+them.
+
+!*/
+
+//a Imports
+use crate::{CubeMap, Gl, GlShaderType, PixelFormat};
+use crate::{TextureId, TextureKind, UniformId, UniformValue};
+
+//a Constants
+/// Vertex shader for all the passes: a full-screen triangle with Uv
+/// from -1 to 1 across the target
+const IBL_VERTEX_SHADER: &str = r#"
+varying vec2 Uv;
+void main() {
+    vec2 xy = vec2(float((gl_VertexID & 1) << 2) - 1.0,
+                   float((gl_VertexID & 2) << 1) - 1.0);
+    Uv = xy;
+    gl_Position = vec4(xy, 0.0, 1.0);
+}
+"#;
+
+/// Functions shared by the fragment shaders: the direction through
+/// a point of the cube face being rendered, and GGX importance
+/// sampling of Hammersley points
+const IBL_COMMON: &str = r#"
+const float PI = 3.14159265359;
+varying vec2 Uv;
+
+vec3 face_direction(int face, vec2 uv) {
+    if (face == 0) return vec3(1.0, -uv.y, -uv.x);
+    if (face == 1) return vec3(-1.0, -uv.y, uv.x);
+    if (face == 2) return vec3(uv.x, 1.0, uv.y);
+    if (face == 3) return vec3(uv.x, -1.0, -uv.y);
+    if (face == 4) return vec3(uv.x, -uv.y, 1.0);
+    return vec3(-uv.x, -uv.y, -1.0);
+}
+
+float radical_inverse(uint bits) {
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return float(bits) * 2.3283064365386963e-10;
+}
+
+vec2 hammersley(int i, int n) {
+    return vec2(float(i) / float(n), radical_inverse(uint(i)));
+}
+
+vec3 importance_sample_ggx(vec2 xi, vec3 n, float roughness) {
+    float a = roughness * roughness;
+    float phi = 2.0 * PI * xi.x;
+    float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    vec3 up = abs(n.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tx = normalize(cross(up, n));
+    vec3 ty = cross(n, tx);
+    return normalize(tx * (cos(phi) * sin_theta) + ty * (sin(phi) * sin_theta) + n * cos_theta);
+}
+"#;
+
+/// Fragment shader for the diffuse irradiance cube map
+const IBL_IRRADIANCE_SHADER: &str = r#"
+uniform samplerCube Environment;
+uniform int Face;
+void main() {
+    vec3 n = normalize(face_direction(Face, Uv));
+    vec3 up = abs(n.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+    vec3 right = normalize(cross(up, n));
+    up = cross(n, right);
+    vec3 sum = vec3(0.0);
+    float count = 0.0;
+    for (float phi = 0.0; phi < 2.0 * PI; phi += 0.025) {
+        for (float theta = 0.0; theta < 0.5 * PI; theta += 0.025) {
+            vec3 t = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 dir = t.x * right + t.y * up + t.z * n;
+            sum += textureLod(Environment, dir, 0.0).rgb * cos(theta) * sin(theta);
+            count += 1.0;
+        }
+    }
+    gl_FragColor = vec4(PI * sum / count, 1.0);
+}
+"#;
+
+/// Fragment shader for a level of the prefiltered specular cube map;
+/// samples are taken from lower-resolution levels of the environment
+/// (if it has mipmaps) where the GGX distribution is wide
+const IBL_PREFILTER_SHADER: &str = r#"
+uniform samplerCube Environment;
+uniform int Face;
+uniform float Roughness;
+uniform int SampleCount;
+uniform float EnvironmentSize;
+void main() {
+    vec3 n = normalize(face_direction(Face, Uv));
+    vec3 sum = vec3(0.0);
+    float weight = 0.0;
+    float a2 = Roughness * Roughness * Roughness * Roughness;
+    float sa_texel = 4.0 * PI / (6.0 * EnvironmentSize * EnvironmentSize);
+    for (int i = 0; i < SampleCount; i++) {
+        vec3 h = importance_sample_ggx(hammersley(i, SampleCount), n, Roughness);
+        vec3 l = normalize(2.0 * dot(n, h) * h - n);
+        float n_dot_l = dot(n, l);
+        if (n_dot_l > 0.0) {
+            float n_dot_h = max(dot(n, h), 0.0);
+            float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+            float pdf = a2 / (PI * d * d) * 0.25 + 0.0001;
+            float sa_sample = 1.0 / (float(SampleCount) * pdf + 0.0001);
+            float lod = Roughness == 0.0 ? 0.0 : max(0.5 * log2(sa_sample / sa_texel), 0.0);
+            sum += textureLod(Environment, l, lod).rgb * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+    gl_FragColor = vec4(sum / max(weight, 0.0001), 1.0);
+}
+"#;
+
+/// Fragment shader for the BRDF lookup texture, with N.V across and
+/// roughness up the texture
+const IBL_BRDF_SHADER: &str = r#"
+uniform int SampleCount;
+void main() {
+    float n_dot_v = max(Uv.x * 0.5 + 0.5, 0.001);
+    float roughness = Uv.y * 0.5 + 0.5;
+    vec3 v = vec3(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    vec3 n = vec3(0.0, 0.0, 1.0);
+    float k = roughness * roughness * 0.5;
+    float scale = 0.0;
+    float bias = 0.0;
+    for (int i = 0; i < SampleCount; i++) {
+        vec3 h = importance_sample_ggx(hammersley(i, SampleCount), n, roughness);
+        vec3 l = normalize(2.0 * dot(v, h) * h - v);
+        float n_dot_l = max(l.z, 0.0);
+        if (n_dot_l > 0.0) {
+            float n_dot_h = max(h.z, 0.0);
+            float v_dot_h = max(dot(v, h), 0.0);
+            float g = (n_dot_v / (n_dot_v * (1.0 - k) + k)) * (n_dot_l / (n_dot_l * (1.0 - k) + k));
+            float g_vis = g * v_dot_h / (n_dot_h * n_dot_v);
+            float fc = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fc) * g_vis;
+            bias += fc * g_vis;
+        }
+    }
+    gl_FragColor = vec4(scale / float(SampleCount), bias / float(SampleCount), 0.0, 1.0);
+}
+"#;
+
+/// Uniform id of the face of the cube map being rendered
+const FACE: UniformId = UniformId::User(0);
+/// Uniform id of the roughness of the prefiltered level being rendered
+const ROUGHNESS: UniformId = UniformId::User(1);
+/// Uniform id of the number of importance samples
+const SAMPLE_COUNT: UniformId = UniformId::User(2);
+/// Uniform id of the face size of the environment
+const ENVIRONMENT_SIZE: UniformId = UniformId::User(3);
+
+//a IblOptions
+//tp IblOptions
+/// The sizes and quality of the textures produced by an [IblPrecompute]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IblOptions {
+    /// Face size of the irradiance cube map
+    pub irradiance_size: usize,
+    /// Face size of level 0 of the prefiltered specular cube map
+    pub prefiltered_size: usize,
+    /// Number of mipmap levels (roughnesses) of the prefiltered
+    /// specular cube map
+    pub prefiltered_levels: usize,
+    /// Width and height of the BRDF lookup texture
+    pub brdf_lut_size: usize,
+    /// Number of importance samples per texel for the prefiltered
+    /// specular cube map and the BRDF lookup texture
+    pub sample_count: usize,
+    /// Pixel format of the textures
+    pub format: PixelFormat,
+}
+
+//ip Default for IblOptions
+impl Default for IblOptions {
+    fn default() -> Self {
+        Self {
+            irradiance_size: 32,
+            prefiltered_size: 128,
+            prefiltered_levels: 5,
+            brdf_lut_size: 256,
+            sample_count: 1024,
+            format: PixelFormat::RgbaF32,
+        }
+    }
+}
+
+//a IblTextures
+//tp IblTextures
+/// The precomputed textures for image-based lighting of an environment
+pub struct IblTextures<G>
+where
+    G: Gl,
+{
+    /// The diffuse irradiance cube map
+    irradiance: <G as Gl>::Texture,
+    /// The prefiltered specular cube map
+    prefiltered: <G as Gl>::Texture,
+    /// Number of mipmap levels of the prefiltered specular cube map
+    prefiltered_levels: usize,
+    /// The BRDF lookup texture
+    brdf_lut: <G as Gl>::Texture,
+}
+
+//ip IblTextures
+impl<G> IblTextures<G>
+where
+    G: Gl,
+{
+    //fp of_environment
+    /// Precompute the textures for an environment, with a temporary
+    /// [IblPrecompute]
+    pub fn of_environment(
+        context: &mut G,
+        environment: &CubeMap<G>,
+        options: &IblOptions,
+    ) -> Result<Self, String> {
+        IblPrecompute::new(context)?.compute(context, environment, options)
+    }
+
+    //ap irradiance
+    /// Borrow the diffuse irradiance cube map
+    pub fn irradiance(&self) -> &<G as Gl>::Texture {
+        &self.irradiance
+    }
+
+    //ap prefiltered
+    /// Borrow the prefiltered specular cube map
+    pub fn prefiltered(&self) -> &<G as Gl>::Texture {
+        &self.prefiltered
+    }
+
+    //ap prefiltered_levels
+    /// Get the number of mipmap levels of the prefiltered specular
+    /// cube map; a roughness r is sampled at level r * (levels - 1)
+    pub fn prefiltered_levels(&self) -> usize {
+        self.prefiltered_levels
+    }
+
+    //ap brdf_lut
+    /// Borrow the BRDF lookup texture
+    pub fn brdf_lut(&self) -> &<G as Gl>::Texture {
+        &self.brdf_lut
+    }
+
+    //mp use_textures
+    /// Bind the textures to a program (which must be 'used') as
+    /// [TextureId::Irradiance], [TextureId::Prefiltered] and
+    /// [TextureId::BrdfLut], for those that the program has
+    pub fn use_textures(&self, context: &mut G, program: &<G as Gl>::Program) {
+        context.program_use_texture(program, TextureId::Irradiance, &self.irradiance);
+        context.program_use_texture(program, TextureId::Prefiltered, &self.prefiltered);
+        context.program_use_texture(program, TextureId::BrdfLut, &self.brdf_lut);
+    }
+}
+
+//ip Debug for IblTextures
+impl<G> std::fmt::Debug for IblTextures<G>
+where
+    G: Gl,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        f.debug_struct("IblTextures")
+            .field("prefiltered_levels", &self.prefiltered_levels)
+            .finish()
+    }
+}
+
+//a IblPrecompute
+//tp IblPrecompute
+/// The programs that precompute the textures for image-based lighting
+///
+/// This may be kept to precompute the textures of many environments
+pub struct IblPrecompute<G>
+where
+    G: Gl,
+{
+    /// Program for the irradiance cube map
+    irradiance_program: G::Program,
+    /// Program for the levels of the prefiltered specular cube map
+    prefilter_program: G::Program,
+    /// Program for the BRDF lookup texture
+    brdf_program: G::Program,
+    /// An empty VAO (the vertices are generated from gl_VertexID)
+    vao: G::Vao,
+}
+
+//ip IblPrecompute
+impl<G> IblPrecompute<G>
+where
+    G: Gl,
+{
+    //fp new
+    /// Compile the precomputation programs
+    pub fn new(context: &mut G) -> Result<Self, String> {
+        let irradiance_fs = format!("{IBL_COMMON}{IBL_IRRADIANCE_SHADER}");
+        let prefilter_fs = format!("{IBL_COMMON}{IBL_PREFILTER_SHADER}");
+        let brdf_fs = format!("{IBL_COMMON}{IBL_BRDF_SHADER}");
+        let environment = [("Environment", TextureId::Environment, 0)];
+        let irradiance_program = context.compile_program(
+            &[
+                (GlShaderType::Vertex, IBL_VERTEX_SHADER),
+                (GlShaderType::Fragment, &irradiance_fs),
+            ],
+            &[],
+            &[("Face", FACE)],
+            &[],
+            &[],
+            &environment,
+            &[],
+        )?;
+        let prefilter_program = context.compile_program(
+            &[
+                (GlShaderType::Vertex, IBL_VERTEX_SHADER),
+                (GlShaderType::Fragment, &prefilter_fs),
+            ],
+            &[],
+            &[
+                ("Face", FACE),
+                ("Roughness", ROUGHNESS),
+                ("SampleCount", SAMPLE_COUNT),
+                ("EnvironmentSize", ENVIRONMENT_SIZE),
+            ],
+            &[],
+            &[],
+            &environment,
+            &[],
+        )?;
+        let brdf_program = context.compile_program(
+            &[
+                (GlShaderType::Vertex, IBL_VERTEX_SHADER),
+                (GlShaderType::Fragment, &brdf_fs),
+            ],
+            &[],
+            &[("SampleCount", SAMPLE_COUNT)],
+            &[],
+            &[],
+            &[],
+            &[],
+        )?;
+        let vao = context
+            .vao_create()
+            .map_err(|_| "Failed to create IBL VAO".to_string())?;
+        context.bind_vao(None);
+        Ok(Self {
+            irradiance_program,
+            prefilter_program,
+            brdf_program,
+            vao,
+        })
+    }
+
+    //mi draw_to
+    /// Draw the full-screen triangle to a level and face of a target
+    fn draw_to(
+        &self,
+        context: &mut G,
+        target: &<G as Gl>::Texture,
+        level: usize,
+        face: usize,
+    ) -> Result<(), String> {
+        context.render_to_texture(Some((target, level, face)))?;
+        context.draw_vertices(Some(&self.vao), mod3d_base::PrimitiveType::Triangles, 0, 3);
+        Ok(())
+    }
+
+    //mi finish
+    /// Return rendering to the default framebuffer after a pass,
+    /// passing on the result of the pass
+    fn finish<T>(&self, context: &mut G, result: Result<T, String>) -> Result<T, String> {
+        context.bind_vao(None);
+        context.use_program(None);
+        context.render_to_texture(None)?;
+        result
+    }
+
+    //mp irradiance
+    /// Render the diffuse irradiance cube map of an environment, with
+    /// faces of a size
+    pub fn irradiance(
+        &self,
+        context: &mut G,
+        environment: &CubeMap<G>,
+        size: usize,
+        format: PixelFormat,
+    ) -> Result<<G as Gl>::Texture, String> {
+        let target = context.texture_create_target(TextureKind::CubeMap, size, format, 1)?;
+        let program = &self.irradiance_program;
+        context.use_program(Some(program));
+        context.program_use_texture(program, TextureId::Environment, environment.texture());
+        let result = (0..6).try_for_each(|face| {
+            context.program_set_uniform(program, FACE, &UniformValue::Int(&[face as i32]));
+            self.draw_to(context, &target, 0, face)
+        });
+        self.finish(context, result.map(|_| target))
+    }
+
+    //mp prefiltered
+    /// Render the prefiltered specular cube map of an environment, with
+    /// level 0 faces of a size and a number of mipmap levels (from a
+    /// roughness of 0 to 1)
+    pub fn prefiltered(
+        &self,
+        context: &mut G,
+        environment: &CubeMap<G>,
+        size: usize,
+        levels: usize,
+        sample_count: usize,
+        format: PixelFormat,
+    ) -> Result<<G as Gl>::Texture, String> {
+        let target = context.texture_create_target(TextureKind::CubeMap, size, format, levels)?;
+        let program = &self.prefilter_program;
+        context.use_program(Some(program));
+        context.program_use_texture(program, TextureId::Environment, environment.texture());
+        context.program_set_uniform(
+            program,
+            SAMPLE_COUNT,
+            &UniformValue::Int(&[sample_count as i32]),
+        );
+        context.program_set_uniform(
+            program,
+            ENVIRONMENT_SIZE,
+            &UniformValue::Float(&[environment.face_size() as f32]),
+        );
+        let result = (0..levels).try_for_each(|level| {
+            let roughness = level as f32 / (levels - 1).max(1) as f32;
+            context.program_set_uniform(program, ROUGHNESS, &UniformValue::Float(&[roughness]));
+            (0..6).try_for_each(|face| {
+                context.program_set_uniform(program, FACE, &UniformValue::Int(&[face as i32]));
+                self.draw_to(context, &target, level, face)
+            })
+        });
+        self.finish(context, result.map(|_| target))
+    }
+
+    //mp brdf_lut
+    /// Render the split-sum BRDF lookup texture, of a size
+    pub fn brdf_lut(
+        &self,
+        context: &mut G,
+        size: usize,
+        sample_count: usize,
+        format: PixelFormat,
+    ) -> Result<<G as Gl>::Texture, String> {
+        let target = context.texture_create_target(TextureKind::Texture2D, size, format, 1)?;
+        let program = &self.brdf_program;
+        context.use_program(Some(program));
+        context.program_set_uniform(
+            program,
+            SAMPLE_COUNT,
+            &UniformValue::Int(&[sample_count as i32]),
+        );
+        let result = self.draw_to(context, &target, 0, 0);
+        self.finish(context, result.map(|_| target))
+    }
+
+    //mp compute
+    /// Precompute all the textures for image-based lighting of an
+    /// environment
+    pub fn compute(
+        &self,
+        context: &mut G,
+        environment: &CubeMap<G>,
+        options: &IblOptions,
+    ) -> Result<IblTextures<G>, String> {
+        let irradiance = self.irradiance(
+            context,
+            environment,
+            options.irradiance_size,
+            options.format,
+        )?;
+        let prefiltered = self.prefiltered(
+            context,
+            environment,
+            options.prefiltered_size,
+            options.prefiltered_levels,
+            options.sample_count,
+            options.format,
+        )?;
+        let brdf_lut = self.brdf_lut(
+            context,
+            options.brdf_lut_size,
+            options.sample_count,
+            options.format,
+        )?;
+        Ok(IblTextures {
+            irradiance,
+            prefiltered,
+            prefiltered_levels: options.prefiltered_levels,
+            brdf_lut,
+        })
+    }
+}
+
+//ip Debug for IblPrecompute
+impl<G> std::fmt::Debug for IblPrecompute<G>
+where
+    G: Gl,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        f.debug_struct("IblPrecompute").finish()
+    }
+}
@@ -0,0 +1,145 @@
+//a Documentation
+
+/*!
+
+Indirect draw commands held in a GPU buffer
+
+An [IndirectBuffer] contains an array of [DrawElementsIndirectCommand]
+in a GPU buffer; the commands can be written by the CPU (with
+[IndirectBuffer::update]) or by a compute shader that binds the
+buffer, and are then drawn with [crate::Gl::draw_indirect] without the
+CPU reading them back.
+
+[crate::ShaderInstantiable::indirect_commands] creates one command
+for each primitive of an instantiable, and
+[crate::ShaderInstantiable::gl_draw_indirect] draws them; a compute
+shader may (for example) cull primitives by setting their instance
+count to zero.
+
+Indirect drawing is only supported by OpenGL (4.3 or later); WebGL2
+does not support it.
+
+!*/
+
+//a Imports
+use crate::{BufferUsage, Gl};
+
+//a DrawElementsIndirectCommand
+//tp DrawElementsIndirectCommand
+/// The layout of an indirect indexed draw command, as
+/// glDrawElementsIndirect requires
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct DrawElementsIndirectCommand {
+    /// Number of indices to draw
+    pub count: u32,
+    /// Number of instances to draw (zero to skip the command)
+    pub instance_count: u32,
+    /// Index (not byte offset) of the first index in the element array buffer
+    pub first_index: u32,
+    /// Value added to each index before fetching the vertex
+    pub base_vertex: i32,
+    /// First instance for instanced vertex attributes
+    pub base_instance: u32,
+}
+
+//ip DrawElementsIndirectCommand
+impl DrawElementsIndirectCommand {
+    /// Byte size of a command in a buffer
+    pub const BYTE_SIZE: usize = std::mem::size_of::<Self>();
+
+    //fp as_bytes
+    /// Get a slice of commands as bytes
+    pub fn as_bytes(commands: &[Self]) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(
+                commands.as_ptr() as *const u8,
+                std::mem::size_of_val(commands),
+            )
+        }
+    }
+}
+
+//a IndirectBuffer
+//tp IndirectBuffer
+/// A GPU buffer of [DrawElementsIndirectCommand]
+pub struct IndirectBuffer<G>
+where
+    G: Gl,
+{
+    /// The GPU buffer
+    gl_buffer: <G as Gl>::Buffer,
+    /// Number of commands in the buffer
+    num_commands: usize,
+}
+
+//ip IndirectBuffer
+impl<G> IndirectBuffer<G>
+where
+    G: Gl,
+{
+    //fp of_commands
+    /// Create a GPU buffer containing the commands; the usage should
+    /// be [BufferUsage::Dynamic] if they are to be updated
+    pub fn of_commands(
+        context: &mut G,
+        commands: &[DrawElementsIndirectCommand],
+        usage: BufferUsage,
+    ) -> Self {
+        let data = DrawElementsIndirectCommand::as_bytes(commands);
+        let gl_buffer = context.buffer_create(data.len(), usage);
+        context.buffer_update_data(&gl_buffer, data, 0);
+        Self {
+            gl_buffer,
+            num_commands: commands.len(),
+        }
+    }
+
+    //mp update
+    /// Replace commands in the buffer starting at an index
+    pub fn update(
+        &self,
+        context: &mut G,
+        first: usize,
+        commands: &[DrawElementsIndirectCommand],
+    ) -> Result<(), String> {
+        if first + commands.len() > self.num_commands {
+            return Err(format!(
+                "Indirect buffer of {} commands cannot update {} commands at {first}",
+                self.num_commands,
+                commands.len()
+            ));
+        }
+        context.buffer_update_data(
+            &self.gl_buffer,
+            DrawElementsIndirectCommand::as_bytes(commands),
+            first * DrawElementsIndirectCommand::BYTE_SIZE,
+        );
+        Ok(())
+    }
+
+    //ap gl_buffer
+    /// Borrow the GPU buffer (e.g. to bind it for a compute shader)
+    pub fn gl_buffer(&self) -> &<G as Gl>::Buffer {
+        &self.gl_buffer
+    }
+
+    //ap num_commands
+    /// Get the number of commands in the buffer
+    pub fn num_commands(&self) -> usize {
+        self.num_commands
+    }
+}
+
+//ip Debug for IndirectBuffer
+impl<G> std::fmt::Debug for IndirectBuffer<G>
+where
+    G: Gl,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        f.debug_struct("IndirectBuffer")
+            .field("gl_buffer", &self.gl_buffer)
+            .field("num_commands", &self.num_commands)
+            .finish()
+    }
+}
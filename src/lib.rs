@@ -12,26 +12,81 @@ This library provides structures for OpenGL shaders ...
 !*/
 
 //a Imports and exports
+// Allow the derive macros to refer to ::mod3d_gl within this crate
+extern crate self as mod3d_gl;
+
 pub use mod3d_base::{Mat3, Mat4, Quat, Transformation, Vec3, Vec4};
 
 mod types;
-pub use types::{TextureId, UniformId};
+pub use types::{AttributeFormat, AttributeKind, BufferUsage, MemoryBarrier};
+pub use types::{PixelFormat, PrimitiveRestart, TextureId, TextureKind, UniformId, UniformValue};
 
 mod traits;
 pub use traits::{Gl, GlBuffer, GlProgram, GlShader, GlShaderType, GlVao};
 
+mod glsl_dialect;
+pub use glsl_dialect::{translate as translate_glsl, GlslDialect};
+
+mod reflection;
+pub use reflection::{ActiveVariable, GlslType, ProgramReflection};
+
+mod uniform_cache;
+pub use uniform_cache::{UniformCache, UniformStats};
+
+mod validation;
+pub use validation::{expected_uniform_types, ProgramValidation, ValidationIssue};
+
+mod resource_stats;
+pub use resource_stats::{ResourceKind, ResourceStats, ResourceTracker, ResourceUsage};
+pub use resource_stats::TrackedResource;
+
 //a Submodules
 mod material;
 mod texture;
 pub use material::Material;
 pub use texture::Texture;
 
+pub mod std140;
+pub use std140::{Std140, Std140Array};
+#[cfg(feature = "derive")]
+pub use mod3d_gl_derive::Std140;
+
 mod buffer;
-pub use buffer::{BufferView, IndexBuffer, UniformBuffer, VertexBuffer};
+pub use buffer::{BufferView, IndexBuffer, StorageBuffer, TypedUniformBuffer};
+pub use buffer::{UniformBuffer, VertexBuffer};
+
+mod buffer_pool;
+pub use buffer_pool::{BufferPool, PoolAllocation};
+
+mod streaming;
+pub use streaming::StreamingBuffer;
+
+mod indirect;
+pub use indirect::{DrawElementsIndirectCommand, IndirectBuffer};
+
+mod feedback;
+pub use feedback::FeedbackBuffer;
+
+mod readback;
+pub use readback::{PendingRead, Pixels};
+
+mod cube_map;
+pub use cube_map::{cube_faces_of_equirectangular, CubeMap};
+
+mod skybox;
+pub use skybox::Skybox;
+
+mod ibl;
+pub use ibl::{IblOptions, IblPrecompute, IblTextures};
 
 mod program;
 pub use program::ShaderProgramDesc;
 
+#[cfg(feature = "naga")]
+mod shader_check;
+#[cfg(feature = "naga")]
+pub use shader_check::{parse_shader, GlslTarget, ShaderInterface};
+
 mod vertices;
 pub use vertices::Vertices;
 
@@ -52,4 +107,4 @@ mod opengl;
 #[cfg(feature = "opengl")]
 pub use opengl::utils as opengl_utils;
 #[cfg(feature = "opengl")]
-pub use opengl::Model3DOpenGL;
+pub use opengl::{Model3DOpenGL, ProgramCache};
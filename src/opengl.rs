@@ -1,14 +1,19 @@
 use mod3d_base::{BufferAccessor, BufferElementType, VertexAttr};
 
-use crate::{Gl, GlProgram, GlShaderType, Mat4, UniformBuffer};
+use crate::{BufferPool, GlBuffer, ProgramReflection, StorageBuffer, UniformBuffer, UniformValue};
+use crate::{Gl, GlProgram, GlShaderType, GlslDialect, Mat4, MemoryBarrier, PrimitiveRestart};
+use crate::{ResourceKind, ResourceStats, ResourceTracker, TextureKind};
 
 mod shader;
 pub mod utils;
 pub use shader::Shader;
 mod program;
 pub use program::Program;
+mod program_cache;
+pub use program_cache::ProgramCache;
 
 mod buffer;
+mod fence;
 mod texture;
 
 mod vao;
@@ -17,7 +22,36 @@ use vao::Vao;
 //a Model3DOpenGL
 //tp Model3DOpenGL
 #[derive(Debug)]
-pub struct Model3DOpenGL {}
+pub struct Model3DOpenGL {
+    /// Minimum desktop GLSL version that shaders are translated to
+    glsl_version: u32,
+    /// Optional disk cache of linked program binaries
+    program_cache: Option<ProgramCache>,
+    /// True if the program in use has tessellation shaders, so
+    /// primitives are drawn as patches
+    draw_patches: std::cell::Cell<bool>,
+    /// If true, indices are drawn from the buffer of the data that
+    /// contains them rather than from a copy
+    share_index_buffers: bool,
+    /// Optional pool from which the buffers of vertex data are allocated
+    buffer_pool: Option<BufferPool<Self>>,
+    /// Fixed size of points drawn, or None if set by programs; this
+    /// starts as 1.0, the initial OpenGL state
+    point_size: Option<f32>,
+    /// Statistics of the buffers, textures, VAOs and programs created
+    resources: ResourceTracker,
+    /// Kind of texture created for model textures with a non-zero depth
+    depth_texture_kind: TextureKind,
+    /// If true, mipmaps are generated for model textures
+    texture_mipmaps: bool,
+    /// Formats of integer vertex data that override those of
+    /// [crate::AttributeFormat::of_vertex_attr]
+    attribute_formats: Vec<(VertexAttr, crate::AttributeFormat)>,
+    /// The framebuffer used by [Gl::render_to_texture], and the
+    /// viewport to restore when rendering returns to the default
+    /// framebuffer
+    render_target: Option<(gl::types::GLuint, [i32; 4])>,
+}
 
 //ip Default for Model3DOpenGL
 impl Default for Model3DOpenGL {
@@ -29,7 +63,196 @@ impl Default for Model3DOpenGL {
 //ip Model3DOpenGL
 impl Model3DOpenGL {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            glsl_version: 330,
+            program_cache: None,
+            draw_patches: std::cell::Cell::new(false),
+            share_index_buffers: false,
+            buffer_pool: None,
+            point_size: Some(1.0),
+            resources: ResourceTracker::default(),
+            depth_texture_kind: TextureKind::Texture2DArray,
+            texture_mipmaps: false,
+            attribute_formats: Vec::new(),
+            render_target: None,
+        }
+    }
+
+    //mp set_texture_options
+    /// Set the kind of texture created for model textures whose data
+    /// has a non-zero depth ([TextureKind::Texture2DArray] or
+    /// [TextureKind::Texture3D]), and whether mipmaps are generated
+    /// for model textures
+    ///
+    /// This only affects textures whose clients are created after it
+    /// is set; other textures may be created with [Gl::texture_create]
+    pub fn set_texture_options(&mut self, depth_texture_kind: TextureKind, mipmaps: bool) {
+        self.depth_texture_kind = depth_texture_kind;
+        self.texture_mipmaps = mipmaps;
+    }
+
+    //ap stats
+    /// Get the statistics of the live GPU resources (buffers,
+    /// textures, VAOs and programs) created by this context, and
+    /// their peaks
+    pub fn stats(&self) -> ResourceStats {
+        self.resources.stats()
+    }
+
+    //mp reset_stats_peaks
+    /// Reset the peaks of the resource statistics to their current
+    /// values (e.g. before loading a model)
+    pub fn reset_stats_peaks(&self) {
+        self.resources.reset_peaks();
+    }
+
+    //mi track_buffer
+    /// Record the byte length of the storage of a buffer in the
+    /// resource statistics, as a new buffer or a respecification
+    fn track_buffer(&self, buffer: &mut buffer::Buffer, kind: ResourceKind, byte_length: usize) {
+        if let Some(tracked) = buffer.tracked() {
+            tracked.resize(byte_length);
+        } else {
+            buffer.set_tracked(self.resources.track(kind, byte_length));
+        }
+    }
+
+    //mp set_buffer_pool
+    /// Set a pool from which the buffers of vertex data are allocated
+    /// (rather than each having its own OpenGL buffer), or None
+    ///
+    /// This only affects buffer data whose clients are created after
+    /// it is set
+    pub fn set_buffer_pool(&mut self, buffer_pool: Option<BufferPool<Self>>) {
+        self.buffer_pool = buffer_pool;
+    }
+
+    //mp set_share_index_buffers
+    /// Set whether indices should be drawn from the buffer of the
+    /// data that contains them (e.g. for glTF files with index and
+    /// vertex data in the same buffer), rather than from a copy
+    ///
+    /// This only affects vertices created after it is set
+    pub fn set_share_index_buffers(&mut self, share_index_buffers: bool) {
+        self.share_index_buffers = share_index_buffers;
+    }
+
+    //mp set_patch_vertices
+    /// Set the number of vertices per patch, or None for the GL
+    /// default of 3
+    ///
+    /// Primitives are drawn as patches only while a program with
+    /// tessellation shaders is in use; other programs draw primitives
+    /// with their own type
+    ///
+    /// This requires OpenGL 4.0 or later
+    pub fn set_patch_vertices(&mut self, patch_vertices: Option<u32>) -> Result<(), String> {
+        if !gl::PatchParameteri::is_loaded() {
+            return Err("Tessellation patches require OpenGL 4.0".into());
+        }
+        let n = patch_vertices.unwrap_or(3);
+        unsafe {
+            gl::PatchParameteri(gl::PATCH_VERTICES, n as i32);
+        }
+        Ok(())
+    }
+
+    //mp set_glsl_version
+    /// Set the minimum desktop GLSL version (e.g. 330 or 410) that
+    /// shader sources are translated to
+    pub fn set_glsl_version(&mut self, glsl_version: u32) {
+        self.glsl_version = glsl_version;
+    }
+
+    //mp set_program_cache
+    /// Set (or clear) the program binary cache used by
+    /// [Gl::compile_program]
+    ///
+    /// The cache is not used if the GL does not support program
+    /// binaries (OpenGL 4.1 or ARB_get_program_binary)
+    pub fn set_program_cache(&mut self, program_cache: Option<ProgramCache>) {
+        self.program_cache = program_cache;
+    }
+
+    //fi gl_primitive_type
+    /// Get the GL primitive type to draw a type of primitive as
+    fn gl_primitive_type(&self, primitive_type: mod3d_base::PrimitiveType) -> gl::types::GLenum {
+        if self.draw_patches.get() {
+            return gl::PATCHES;
+        }
+        use mod3d_base::PrimitiveType::*;
+        match primitive_type {
+            Points => gl::POINTS,
+            Lines => gl::LINES,
+            LineLoop => gl::LINE_LOOP,
+            LineStrip => gl::LINE_STRIP,
+            Triangles => gl::TRIANGLES,
+            TriangleFan => gl::TRIANGLE_FAN,
+            TriangleStrip => gl::TRIANGLE_STRIP,
+        }
+    }
+
+    //fi gl_pixel_format
+    /// Get the GL format and type to read pixels as
+    fn gl_pixel_format(format: crate::PixelFormat) -> (gl::types::GLenum, gl::types::GLenum) {
+        match format {
+            crate::PixelFormat::Rgba8 => (gl::RGBA, gl::UNSIGNED_BYTE),
+            crate::PixelFormat::RgbaF32 => (gl::RGBA, gl::FLOAT),
+        }
+    }
+
+    //fi get_tex_image
+    /// Read a level of a texture to a pointer (or, if a pixel pack
+    /// buffer is bound, a byte offset in to the buffer)
+    ///
+    /// Only 2D textures may be read
+    fn get_tex_image(
+        &self,
+        texture: &texture::Texture,
+        level: usize,
+        format: crate::PixelFormat,
+        pixels: *mut std::ffi::c_void,
+    ) -> Result<(), String> {
+        if texture.kind() != TextureKind::Texture2D {
+            return Err(format!("Cannot read a {:?}", texture.kind()));
+        }
+        let (gl_format, gl_type) = Self::gl_pixel_format(format);
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::BindTexture(gl::TEXTURE_2D, texture.gl_texture());
+            gl::GetTexImage(gl::TEXTURE_2D, level as i32, gl_format, gl_type, pixels);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        Ok(())
+    }
+
+    //fi add_program_names
+    /// Add the names of attributes, uniforms, uniform and storage
+    /// blocks, and textures to a linked program
+    fn add_program_names(
+        program: &mut Program,
+        named_attrs: &[(&str, VertexAttr)],
+        named_uniforms: &[(&str, crate::UniformId)],
+        named_uniform_buffers: &[(&str, usize)],
+        named_storage_buffers: &[(&str, usize)],
+        named_textures: &[(&str, crate::TextureId, usize)],
+    ) -> Result<(), String> {
+        for (name, attr) in named_attrs {
+            program.add_attr_name(name, *attr)?;
+        }
+        for (name, uniform) in named_uniforms {
+            program.add_uniform_name(name, *uniform)?;
+        }
+        for (name, uniform) in named_uniform_buffers {
+            program.add_uniform_buffer_name(name, *uniform)?;
+        }
+        for (name, storage) in named_storage_buffers {
+            program.add_storage_buffer_name(name, *storage)?;
+        }
+        for (name, texture_id, unit) in named_textures {
+            program.add_uniform_texture_name(name, *texture_id, *unit)?;
+        }
+        Ok(())
     }
 }
 
@@ -41,46 +264,140 @@ impl Gl for Model3DOpenGL {
     type Buffer = buffer::Buffer;
     type Vao = vao::Vao;
     type Texture = texture::Texture;
+    type Fence = fence::Fence;
 
     //mp link_program
     /// Create a program from a list of compiled shaders
+    #[allow(clippy::too_many_arguments)]
     fn link_program(
         &self,
         srcs: &[&Self::Shader],
         named_attrs: &[(&str, VertexAttr)],
         named_uniforms: &[(&str, crate::UniformId)],
         named_uniform_buffers: &[(&str, usize)],
+        named_storage_buffers: &[(&str, usize)],
         named_textures: &[(&str, crate::TextureId, usize)],
+        feedback_varyings: &[&str],
     ) -> Result<Self::Program, String> {
-        let mut program = Program::link_program(srcs)?;
-        for (name, attr) in named_attrs {
-            program.add_attr_name(name, *attr)?;
-        }
-        for (name, uniform) in named_uniforms {
-            program.add_uniform_name(name, *uniform)?;
-        }
-        for (name, uniform) in named_uniform_buffers {
-            program.add_uniform_buffer_name(name, *uniform)?;
-        }
-        for (name, texture_id, unit) in named_textures {
-            program.add_uniform_texture_name(name, *texture_id, *unit)?;
-        }
+        let mut program = Program::link_program(srcs, feedback_varyings)?;
+        program.set_tracked(self.resources.track(ResourceKind::Program, 0));
+        Self::add_program_names(
+            &mut program,
+            named_attrs,
+            named_uniforms,
+            named_uniform_buffers,
+            named_storage_buffers,
+            named_textures,
+        )?;
+        Ok(program)
+    }
+
+    //mp compile_program
+    /// Compile and link a program, using the program cache if there is one
+    ///
+    /// If the cache has a binary for the sources (and driver) then
+    /// that is used; if the driver rejects it then the program is
+    /// compiled in full and the cache entry replaced
+    ///
+    /// Programs with feedback varyings are not cached, as the cache
+    /// is keyed only by the sources
+    #[allow(clippy::too_many_arguments)]
+    fn compile_program(
+        &self,
+        srcs: &[(GlShaderType, &str)],
+        named_attrs: &[(&str, VertexAttr)],
+        named_uniforms: &[(&str, crate::UniformId)],
+        named_uniform_buffers: &[(&str, usize)],
+        named_storage_buffers: &[(&str, usize)],
+        named_textures: &[(&str, crate::TextureId, usize)],
+        feedback_varyings: &[&str],
+    ) -> Result<Self::Program, String> {
+        let opt_cache = self
+            .program_cache
+            .as_ref()
+            .filter(|_| feedback_varyings.is_empty() && Program::binaries_supported());
+        let Some(cache) = opt_cache else {
+            let mut shaders = Vec::new();
+            for (shader_type, source) in srcs {
+                shaders.push(self.compile_shader(*shader_type, source)?);
+            }
+            let shaders: Vec<&Shader> = shaders.iter().collect();
+            return self.link_program(
+                &shaders,
+                named_attrs,
+                named_uniforms,
+                named_uniform_buffers,
+                named_storage_buffers,
+                named_textures,
+                feedback_varyings,
+            );
+        };
+        // Key the cache on the translated sources, as those are what is compiled
+        let translated: Vec<(GlShaderType, String)> = srcs
+            .iter()
+            .map(|(t, s)| (*t, crate::translate_glsl(s, *t, self.glsl_dialect())))
+            .collect();
+        let srcs: Vec<(GlShaderType, &str)> =
+            translated.iter().map(|(t, s)| (*t, s.as_str())).collect();
+        let cached = cache
+            .load(&srcs)
+            .and_then(|(format, binary)| Program::of_binary(format, &binary).ok());
+        let mut program = {
+            if let Some(mut program) = cached {
+                program.set_stages(srcs.iter().map(|(t, _)| *t).collect());
+                program
+            } else {
+                cache.remove(&srcs);
+                let mut shaders = Vec::new();
+                for (shader_type, source) in &srcs {
+                    shaders.push(Shader::compile(source, *shader_type)?);
+                }
+                let shaders: Vec<&Shader> = shaders.iter().collect();
+                let program = Program::link(&shaders, &[], true)?;
+                if let Some((format, binary)) = program.binary() {
+                    cache.store(&srcs, format, &binary);
+                }
+                program
+            }
+        };
+        program.set_tracked(self.resources.track(ResourceKind::Program, 0));
+        Self::add_program_names(
+            &mut program,
+            named_attrs,
+            named_uniforms,
+            named_uniform_buffers,
+            named_storage_buffers,
+            named_textures,
+        )?;
         Ok(program)
     }
 
     //mp compile_shader
-    /// Compile a shader
+    /// Compile a shader, translating it to the desktop GLSL dialect
     fn compile_shader(
         &self,
         shader_type: GlShaderType,
         source: &str,
     ) -> Result<Self::Shader, String> {
-        Shader::compile(source, shader_type)
+        let source = crate::translate_glsl(source, shader_type, self.glsl_dialect());
+        Shader::compile(&source, shader_type)
+    }
+
+    //mp glsl_dialect
+    fn glsl_dialect(&self) -> GlslDialect {
+        GlslDialect::Desktop(self.glsl_version)
+    }
+
+    //mp program_reflection
+    fn program_reflection(&self, program: &Self::Program) -> ProgramReflection {
+        program.reflection()
     }
 
     //mp use_program
     /// Use the program
     fn use_program(&self, program: Option<&Self::Program>) {
+        self.draw_patches
+            .set(program.is_some_and(|p| p.has_tessellation()));
         if let Some(program) = program {
             program.set_used();
         } else {
@@ -89,6 +406,11 @@ impl Gl for Model3DOpenGL {
             }
         }
     }
+    //mp share_index_buffers
+    fn share_index_buffers(&self) -> bool {
+        self.share_index_buffers
+    }
+
     //mp init_buffer_of_indices
     fn init_buffer_of_indices(
         &mut self,
@@ -96,11 +418,391 @@ impl Gl for Model3DOpenGL {
         view: &BufferAccessor<Self>,
     ) {
         buffer.of_indices(view);
+        let byte_length =
+            crate::buffer::ele_byte_size(view.ele_type) as usize * view.elements_per_data as usize;
+        self.track_buffer(buffer, ResourceKind::IndexBuffer, byte_length);
+    }
+
+    //mp buffer_create
+    fn buffer_create(
+        &mut self,
+        byte_length: usize,
+        usage: crate::BufferUsage,
+    ) -> <Self as Gl>::Buffer {
+        let mut buffer = <Self as Gl>::Buffer::default();
+        buffer.create(byte_length, usage);
+        self.track_buffer(&mut buffer, ResourceKind::Buffer, byte_length);
+        buffer
+    }
+
+    //mp buffer_data_create
+    fn buffer_data_create(
+        &mut self,
+        buffer: &mut <Self as Gl>::Buffer,
+        data: &mod3d_base::BufferData<Self>,
+        usage: crate::BufferUsage,
+    ) {
+        buffer.of_data_with_usage(data, usage);
+        self.track_buffer(
+            buffer,
+            ResourceKind::VertexBuffer,
+            data.byte_length as usize,
+        );
+    }
+
+    //mp buffer_update_data
+    fn buffer_update_data(
+        &mut self,
+        buffer: &<Self as Gl>::Buffer,
+        data: &[u8],
+        byte_offset: usize,
+    ) {
+        buffer.update_data(data, byte_offset);
+    }
+
+    //mp buffer_read_data
+    fn buffer_read_data(
+        &mut self,
+        buffer: &<Self as Gl>::Buffer,
+        byte_offset: usize,
+        data: &mut [u8],
+    ) -> Result<(), String> {
+        buffer.read_data(byte_offset, data);
+        utils::check_errors().map_err(|e| format!("Buffer read failed: {}", e.join(", ")))
+    }
+
+    //mp buffer_copy
+    fn buffer_copy(
+        &mut self,
+        src: &<Self as Gl>::Buffer,
+        src_byte_offset: usize,
+        dst: &<Self as Gl>::Buffer,
+        dst_byte_offset: usize,
+        byte_length: usize,
+    ) {
+        let src_byte_offset = src.byte_offset() as usize + src_byte_offset;
+        let dst_byte_offset = dst.byte_offset() as usize + dst_byte_offset;
+        unsafe {
+            gl::BindBuffer(gl::COPY_READ_BUFFER, src.gl_buffer());
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, dst.gl_buffer());
+            gl::CopyBufferSubData(
+                gl::COPY_READ_BUFFER,
+                gl::COPY_WRITE_BUFFER,
+                src_byte_offset as isize,
+                dst_byte_offset as isize,
+                byte_length as isize,
+            );
+            gl::BindBuffer(gl::COPY_READ_BUFFER, 0);
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+        }
+    }
+
+    //ap buffer_can_map
+    fn buffer_can_map(&self) -> bool {
+        gl::MapBufferRange::is_loaded()
+    }
+
+    //mp buffer_write_unsynchronized
+    fn buffer_write_unsynchronized(
+        &mut self,
+        buffer: &<Self as Gl>::Buffer,
+        data: &[u8],
+        byte_offset: usize,
+    ) -> Result<(), String> {
+        if !self.buffer_can_map() {
+            return Err("Mapping buffers requires OpenGL 3.0".into());
+        }
+        buffer.write_unsynchronized(data, byte_offset)
+    }
+
+    //mp buffer_orphan
+    fn buffer_orphan(
+        &mut self,
+        buffer: &<Self as Gl>::Buffer,
+        byte_length: usize,
+        usage: crate::BufferUsage,
+    ) {
+        buffer.orphan(byte_length, usage);
+        if let Some(tracked) = buffer.tracked() {
+            tracked.resize(byte_length);
+        }
+    }
+
+    //mp uniform_buffer_offset_alignment
+    fn uniform_buffer_offset_alignment(&self) -> usize {
+        let mut alignment = 0;
+        unsafe {
+            gl::GetIntegerv(gl::UNIFORM_BUFFER_OFFSET_ALIGNMENT, &mut alignment);
+        }
+        (alignment as usize).max(1)
+    }
+
+    //mp fence_insert
+    fn fence_insert(&mut self) -> Option<Self::Fence> {
+        fence::Fence::insert()
+    }
+
+    //mp fence_wait
+    fn fence_wait(&mut self, fence: Self::Fence) -> Result<(), String> {
+        fence.wait()
+    }
+
+    //mp fence_is_signalled
+    fn fence_is_signalled(&mut self, fence: &Self::Fence) -> bool {
+        fence.is_signalled()
+    }
+
+    //mp texture_create
+    fn texture_create(
+        &mut self,
+        texture: &mod3d_base::Texture<Self>,
+        kind: TextureKind,
+        mipmaps: bool,
+    ) -> Result<<Self as Gl>::Texture, String> {
+        let mut client = texture::Texture::of_texture(texture, kind, mipmaps)?;
+        let byte_size = texture.data().len();
+        client.set_tracked(self.resources.track(ResourceKind::Texture, byte_size));
+        Ok(client)
+    }
+
+    //mp texture_create_cube
+    fn texture_create_cube(
+        &mut self,
+        size: usize,
+        channels: usize,
+        ele_type: BufferElementType,
+        faces: [&[u8]; 6],
+        mipmaps: bool,
+    ) -> Result<<Self as Gl>::Texture, String> {
+        let mut client = texture::Texture::of_cube_faces(size, channels, ele_type, faces, mipmaps)?;
+        let byte_size =
+            6 * size * size * channels * crate::buffer::ele_byte_size(ele_type) as usize;
+        client.set_tracked(self.resources.track(ResourceKind::Texture, byte_size));
+        Ok(client)
+    }
+
+    //mp texture_create_target
+    fn texture_create_target(
+        &mut self,
+        kind: TextureKind,
+        size: usize,
+        format: crate::PixelFormat,
+        levels: usize,
+    ) -> Result<<Self as Gl>::Texture, String> {
+        let mut client = texture::Texture::of_target(kind, size, format, levels)?;
+        let faces = if kind == TextureKind::CubeMap { 6 } else { 1 };
+        let texel_bytes = if format == crate::PixelFormat::Rgba8 {
+            4
+        } else {
+            8
+        };
+        let byte_size = (0..levels)
+            .map(|l| faces * (size >> l) * (size >> l) * texel_bytes)
+            .sum();
+        client.set_tracked(self.resources.track(ResourceKind::Texture, byte_size));
+        Ok(client)
+    }
+
+    //mp render_to_texture
+    fn render_to_texture(
+        &mut self,
+        target: Option<(&<Self as Gl>::Texture, usize, usize)>,
+    ) -> Result<(), String> {
+        let Some((texture, level, face)) = target else {
+            if let Some((framebuffer, viewport)) = self.render_target.take() {
+                unsafe {
+                    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                    gl::DeleteFramebuffers(1, &framebuffer);
+                    gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+                }
+            }
+            return Ok(());
+        };
+        let tex_target = match texture.kind() {
+            TextureKind::Texture2D if face == 0 => gl::TEXTURE_2D,
+            TextureKind::CubeMap if face < 6 => gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+            kind => {
+                return Err(format!("Cannot render to face {face} of a {kind:?}"));
+            }
+        };
+        let (width, height) = self.texture_size(texture, level);
+        if width == 0 || height == 0 {
+            return Err(format!("Texture has no level {level} to render to"));
+        }
+        let framebuffer = {
+            if let Some((framebuffer, _)) = self.render_target {
+                framebuffer
+            } else {
+                let mut framebuffer = 0;
+                let mut viewport = [0; 4];
+                unsafe {
+                    gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+                    gl::GenFramebuffers(1, &mut framebuffer);
+                }
+                self.render_target = Some((framebuffer, viewport));
+                framebuffer
+            }
+        };
+        let status = unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                tex_target,
+                texture.gl_texture(),
+                level as i32,
+            );
+            gl::Viewport(0, 0, width as i32, height as i32);
+            gl::CheckFramebufferStatus(gl::FRAMEBUFFER)
+        };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!(
+                "Render target framebuffer is incomplete ({status:#x})"
+            ));
+        }
+        Ok(())
+    }
+
+    //mp texture_size
+    fn texture_size(&mut self, texture: &<Self as Gl>::Texture, level: usize) -> (usize, usize) {
+        let mut width = 0;
+        let mut height = 0;
+        let target = texture.target();
+        // The size of a cube map is queried through one of its faces
+        let level_target = {
+            if texture.kind() == TextureKind::CubeMap {
+                gl::TEXTURE_CUBE_MAP_POSITIVE_X
+            } else {
+                target
+            }
+        };
+        unsafe {
+            gl::BindTexture(target, texture.gl_texture());
+            gl::GetTexLevelParameteriv(level_target, level as i32, gl::TEXTURE_WIDTH, &mut width);
+            gl::GetTexLevelParameteriv(level_target, level as i32, gl::TEXTURE_HEIGHT, &mut height);
+            gl::BindTexture(target, 0);
+        }
+        (width as usize, height as usize)
+    }
+
+    //mp texture_read
+    fn texture_read(
+        &mut self,
+        texture: &<Self as Gl>::Texture,
+        level: usize,
+        format: crate::PixelFormat,
+        data: &mut [u8],
+    ) -> Result<(), String> {
+        let (width, height) = self.texture_size(texture, level);
+        if data.len() < width * height * format.bytes_per_pixel() {
+            return Err(format!(
+                "Buffer of {} bytes too small for {width}x{height} texture level {level}",
+                data.len()
+            ));
+        }
+        self.get_tex_image(
+            texture,
+            level,
+            format,
+            data.as_mut_ptr() as *mut std::ffi::c_void,
+        )?;
+        utils::check_errors().map_err(|e| format!("Texture read failed: {}", e.join(", ")))
+    }
+
+    //mp texture_read_to_buffer
+    fn texture_read_to_buffer(
+        &mut self,
+        texture: &<Self as Gl>::Texture,
+        level: usize,
+        format: crate::PixelFormat,
+        buffer: &<Self as Gl>::Buffer,
+    ) -> Result<(), String> {
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, buffer.gl_buffer());
+        }
+        let byte_offset = buffer.byte_offset() as usize;
+        let result =
+            self.get_tex_image(texture, level, format, byte_offset as *mut std::ffi::c_void);
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+        result?;
+        utils::check_errors().map_err(|e| format!("Texture read failed: {}", e.join(", ")))
+    }
+
+    //mp read_pixels
+    fn read_pixels(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        format: crate::PixelFormat,
+        data: &mut [u8],
+    ) -> Result<(), String> {
+        if data.len() < width * height * format.bytes_per_pixel() {
+            return Err(format!(
+                "Buffer of {} bytes too small for {width}x{height} pixels",
+                data.len()
+            ));
+        }
+        let (gl_format, gl_type) = Self::gl_pixel_format(format);
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                x as i32,
+                y as i32,
+                width as i32,
+                height as i32,
+                gl_format,
+                gl_type,
+                data.as_mut_ptr() as *mut std::ffi::c_void,
+            );
+        }
+        utils::check_errors().map_err(|e| format!("Read pixels failed: {}", e.join(", ")))
+    }
+
+    //mp read_pixels_to_buffer
+    fn read_pixels_to_buffer(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        format: crate::PixelFormat,
+        buffer: &<Self as Gl>::Buffer,
+    ) -> Result<(), String> {
+        let (gl_format, gl_type) = Self::gl_pixel_format(format);
+        let byte_offset = buffer.byte_offset() as usize;
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, buffer.gl_buffer());
+            gl::ReadPixels(
+                x as i32,
+                y as i32,
+                width as i32,
+                height as i32,
+                gl_format,
+                gl_type,
+                byte_offset as *mut std::ffi::c_void,
+            );
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+        utils::check_errors().map_err(|e| format!("Read pixels failed: {}", e.join(", ")))
     }
 
     //mp vao_create_from_indices
     fn vao_create_from_indices(&mut self, indices: &crate::IndexBuffer<Self>) -> Result<Vao, ()> {
-        Vao::create_from_indices(self, indices)
+        let mut vao = Vao::create_from_indices(self, indices)?;
+        vao.set_tracked(self.resources.track(ResourceKind::Vao, 0));
+        Ok(vao)
+    }
+
+    //mp vao_create
+    fn vao_create(&mut self) -> Result<Vao, ()> {
+        let mut vao = Vao::create(self)?;
+        vao.set_tracked(self.resources.track(ResourceKind::Vao, 0));
+        Ok(vao)
     }
 
     //mp buffer_bind_to_vao_attr
@@ -110,15 +812,26 @@ impl Gl for Model3DOpenGL {
         attr_id: &<Program as GlProgram>::GlAttrId,
         count: u32,
         ele_type: BufferElementType,
+        format: crate::AttributeFormat,
         byte_offset: u32,
         stride: u32,
     ) {
-        buffer.bind_to_vao_attr(*attr_id, count, ele_type, byte_offset, stride);
+        buffer.bind_to_vao_attr(*attr_id, count, ele_type, format, byte_offset, stride);
+    }
+
+    //mp attr_set_default
+    fn attr_set_default(&mut self, attr_id: &<Program as GlProgram>::GlAttrId, value: &[f32; 4]) {
+        unsafe {
+            gl::VertexAttrib4fv(*attr_id, value.as_ptr());
+        }
     }
 
     //mp program_set_uniform_mat4
     fn program_set_uniform_mat4(&mut self, program: &Program, id: crate::UniformId, mat4: &Mat4) {
         if let Some(u) = program.uniform(id) {
+            if !program.uniform_changed(id, &UniformValue::Mat4(mat4, false)) {
+                return;
+            }
             unsafe {
                 gl::UniformMatrix4fv(u, 1, gl::FALSE, mat4.as_ptr());
             }
@@ -133,12 +846,51 @@ impl Gl for Model3DOpenGL {
         floats: &[f32],
     ) {
         if let Some(u) = program.uniform(id) {
+            if !program.uniform_changed(id, &UniformValue::Vec4(floats)) {
+                return;
+            }
             unsafe {
                 gl::Uniform4fv(u, (floats.len() / 4) as i32, floats.as_ptr());
             }
         }
     }
 
+    //mp program_set_uniform
+    fn program_set_uniform(
+        &mut self,
+        program: &Self::Program,
+        id: crate::UniformId,
+        value: &UniformValue,
+    ) {
+        let Some(u) = program.uniform(id) else {
+            return;
+        };
+        if !program.uniform_changed(id, value) {
+            return;
+        }
+        let n = value.count() as i32;
+        use UniformValue::*;
+        unsafe {
+            match value {
+                Float(d) => gl::Uniform1fv(u, n, d.as_ptr()),
+                Vec2(d) => gl::Uniform2fv(u, n, d.as_ptr()),
+                Vec3(d) => gl::Uniform3fv(u, n, d.as_ptr()),
+                Vec4(d) => gl::Uniform4fv(u, n, d.as_ptr()),
+                Int(d) => gl::Uniform1iv(u, n, d.as_ptr()),
+                IVec2(d) => gl::Uniform2iv(u, n, d.as_ptr()),
+                IVec3(d) => gl::Uniform3iv(u, n, d.as_ptr()),
+                IVec4(d) => gl::Uniform4iv(u, n, d.as_ptr()),
+                UInt(d) => gl::Uniform1uiv(u, n, d.as_ptr()),
+                UVec2(d) => gl::Uniform2uiv(u, n, d.as_ptr()),
+                UVec3(d) => gl::Uniform3uiv(u, n, d.as_ptr()),
+                UVec4(d) => gl::Uniform4uiv(u, n, d.as_ptr()),
+                Mat2(d, t) => gl::UniformMatrix2fv(u, n, *t as gl::types::GLboolean, d.as_ptr()),
+                Mat3(d, t) => gl::UniformMatrix3fv(u, n, *t as gl::types::GLboolean, d.as_ptr()),
+                Mat4(d, t) => gl::UniformMatrix4fv(u, n, *t as gl::types::GLboolean, d.as_ptr()),
+            }
+        }
+    }
+
     //mp program_bind_uniform_index
     fn program_bind_uniform_index(
         &mut self,
@@ -159,6 +911,22 @@ impl Gl for Model3DOpenGL {
         Ok(())
     }
 
+    //mp program_bind_storage_index
+    fn program_bind_storage_index(
+        &mut self,
+        program: &<Self as Gl>::Program,
+        storage_buffer_id: usize,
+        gl_index: u32,
+    ) -> Result<(), ()> {
+        if let Some(block_index) = program.storage_buffer(storage_buffer_id) {
+            unsafe {
+                gl::ShaderStorageBlockBinding(program.id(), block_index, gl_index);
+            }
+            utils::check_errors().map_err(|_| ())?;
+        }
+        Ok(())
+    }
+
     //mp program_use_texture
     /// Requires the program to be 'used'
     fn program_use_texture(
@@ -170,7 +938,7 @@ impl Gl for Model3DOpenGL {
         if let Some((u, unit)) = program.texture_uniform(texture_id) {
             unsafe {
                 gl::ActiveTexture(gl::TEXTURE0 + unit);
-                gl::BindTexture(gl::TEXTURE_2D, gl_texture.gl_texture());
+                gl::BindTexture(gl_texture.target(), gl_texture.gl_texture());
                 gl::Uniform1i(u as i32, unit as i32);
             }
         }
@@ -180,39 +948,260 @@ impl Gl for Model3DOpenGL {
     fn draw_primitive(&mut self, vaos: &[Vao], primitive: &mod3d_base::Primitive) {
         // (if p.vertices_index different to last)
         // (if p.material_index ...
-        use mod3d_base::PrimitiveType::*;
-        let gl_type = match primitive.primitive_type() {
-            Points => gl::POINTS,
-            Lines => gl::LINES,
-            LineLoop => gl::LINE_LOOP,
-            LineStrip => gl::LINE_STRIP,
-            Triangles => gl::TRIANGLES,
-            TriangleFan => gl::TRIANGLE_FAN,
-            TriangleStrip => gl::TRIANGLE_STRIP,
-        };
+        let gl_type = self.gl_primitive_type(primitive.primitive_type());
 
         let opt_vertices_index: Option<usize> = primitive.vertices_index().into();
         if let Some(vertices_index) = opt_vertices_index {
-            let index_type = vaos[vertices_index].bind_vao();
+            let vao = &vaos[vertices_index];
+            let index_type = vao.bind_vao();
+            if vao.indexed() {
+                let byte_offset = vao.index_byte_offset() + primitive.byte_offset() as usize;
+                unsafe {
+                    gl::DrawElements(
+                        gl_type,
+                        primitive.index_count() as i32,
+                        index_type,
+                        byte_offset as *const std::ffi::c_void,
+                    );
+                }
+            } else if let Some(first) = crate::vertices::first_vertex(primitive) {
+                unsafe {
+                    gl::DrawArrays(gl_type, first, primitive.index_count() as i32);
+                }
+            }
+        } else if let Some(first) = crate::vertices::first_vertex(primitive) {
             unsafe {
-                gl::DrawElements(
+                gl::DrawArrays(gl_type, first, primitive.index_count() as i32);
+            }
+        }
+    }
+
+    //mp draw_primitives
+    fn draw_primitives(&mut self, vaos: &[Vao], primitives: &[&mod3d_base::Primitive]) {
+        let opt_vertices_index: Option<usize> =
+            primitives.first().and_then(|p| p.vertices_index().into());
+        let Some(vertices_index) = opt_vertices_index else {
+            for p in primitives {
+                self.draw_primitive(vaos, p);
+            }
+            return;
+        };
+        if primitives.len() == 1 {
+            self.draw_primitive(vaos, primitives[0]);
+            return;
+        }
+        let gl_type = self.gl_primitive_type(primitives[0].primitive_type());
+        let vao = &vaos[vertices_index];
+        let index_type = vao.bind_vao();
+        let counts: Vec<i32> = primitives.iter().map(|p| p.index_count() as i32).collect();
+        if vao.indexed() {
+            let offsets: Vec<*const std::ffi::c_void> = primitives
+                .iter()
+                .map(|p| {
+                    (vao.index_byte_offset() + p.byte_offset() as usize) as *const std::ffi::c_void
+                })
+                .collect();
+            unsafe {
+                gl::MultiDrawElements(
                     gl_type,
-                    primitive.index_count() as i32,
+                    counts.as_ptr(),
                     index_type,
-                    primitive.byte_offset() as *const std::ffi::c_void,
+                    offsets.as_ptr(),
+                    primitives.len() as i32,
                 );
             }
         } else {
+            let firsts: Option<Vec<i32>> = primitives
+                .iter()
+                .map(|p| crate::vertices::first_vertex(p))
+                .collect();
+            let Some(firsts) = firsts else {
+                for p in primitives {
+                    self.draw_primitive(vaos, p);
+                }
+                return;
+            };
             unsafe {
-                gl::DrawArrays(
+                gl::MultiDrawArrays(
                     gl_type,
-                    primitive.byte_offset() as i32,
-                    primitive.index_count() as i32,
+                    firsts.as_ptr(),
+                    counts.as_ptr(),
+                    primitives.len() as i32,
                 );
             }
         }
     }
 
+    //mp draw_vertices
+    fn draw_vertices(
+        &mut self,
+        vao: Option<&Vao>,
+        primitive_type: mod3d_base::PrimitiveType,
+        first: usize,
+        count: usize,
+    ) {
+        self.bind_vao(vao);
+        let gl_type = self.gl_primitive_type(primitive_type);
+        unsafe {
+            gl::DrawArrays(gl_type, first as i32, count as i32);
+        }
+    }
+
+    //mp draw_indirect
+    fn draw_indirect(
+        &mut self,
+        vao: &Vao,
+        primitive_type: mod3d_base::PrimitiveType,
+        buffer: &<Self as Gl>::Buffer,
+        byte_offset: usize,
+        draw_count: usize,
+    ) -> Result<(), String> {
+        if !vao.indexed() {
+            return Err("Indirect drawing requires vertices with indices".into());
+        }
+        if !gl::DrawElementsIndirect::is_loaded() {
+            return Err("Indirect drawing requires OpenGL 4.0".into());
+        }
+        if draw_count != 1 && !gl::MultiDrawElementsIndirect::is_loaded() {
+            return Err("Indirect drawing of more than one command requires OpenGL 4.3".into());
+        }
+        let gl_type = self.gl_primitive_type(primitive_type);
+        let index_type = vao.bind_vao();
+        let byte_offset = buffer.byte_offset() as usize + byte_offset;
+        unsafe {
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, buffer.gl_buffer());
+            if draw_count == 1 {
+                gl::DrawElementsIndirect(
+                    gl_type,
+                    index_type,
+                    byte_offset as *const std::ffi::c_void,
+                );
+            } else {
+                gl::MultiDrawElementsIndirect(
+                    gl_type,
+                    index_type,
+                    byte_offset as *const std::ffi::c_void,
+                    draw_count as i32,
+                    0,
+                );
+            }
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0);
+        }
+        utils::check_errors().map_err(|e| format!("Indirect draw failed: {}", e.join(", ")))
+    }
+
+    //mp transform_feedback_begin
+    fn transform_feedback_begin(
+        &mut self,
+        buffer: &<Self as Gl>::Buffer,
+        byte_offset: usize,
+        byte_length: usize,
+        primitive_type: mod3d_base::PrimitiveType,
+        discard: bool,
+    ) -> Result<(), String> {
+        use mod3d_base::PrimitiveType::*;
+        let gl_type = match primitive_type {
+            Points => gl::POINTS,
+            Lines | LineLoop | LineStrip => gl::LINES,
+            Triangles | TriangleFan | TriangleStrip => gl::TRIANGLES,
+        };
+        let byte_offset = buffer.byte_offset() as usize + byte_offset;
+        unsafe {
+            gl::BindBufferRange(
+                gl::TRANSFORM_FEEDBACK_BUFFER,
+                0,
+                buffer.gl_buffer(),
+                byte_offset as isize,
+                byte_length as isize,
+            );
+            if discard {
+                gl::Enable(gl::RASTERIZER_DISCARD);
+            }
+            gl::BeginTransformFeedback(gl_type);
+        }
+        utils::check_errors()
+            .map_err(|e| format!("Failed to begin transform feedback: {}", e.join(", ")))
+    }
+
+    //mp transform_feedback_end
+    fn transform_feedback_end(&mut self) {
+        unsafe {
+            gl::EndTransformFeedback();
+            gl::Disable(gl::RASTERIZER_DISCARD);
+            gl::BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, 0, 0);
+        }
+    }
+
+    //mp set_primitive_restart
+    fn set_primitive_restart(&mut self, restart: PrimitiveRestart) -> Result<(), String> {
+        unsafe {
+            match restart {
+                PrimitiveRestart::Disabled => {
+                    gl::Disable(gl::PRIMITIVE_RESTART);
+                    gl::Disable(gl::PRIMITIVE_RESTART_FIXED_INDEX);
+                }
+                PrimitiveRestart::FixedIndex => {
+                    gl::Disable(gl::PRIMITIVE_RESTART);
+                    gl::Enable(gl::PRIMITIVE_RESTART_FIXED_INDEX);
+                }
+                PrimitiveRestart::Index(index) => {
+                    gl::Disable(gl::PRIMITIVE_RESTART_FIXED_INDEX);
+                    gl::Enable(gl::PRIMITIVE_RESTART);
+                    gl::PrimitiveRestartIndex(index);
+                }
+            }
+        }
+        utils::check_errors().map_err(|e| {
+            format!(
+                "Failed to set primitive restart {restart:?}: {}",
+                e.join(", ")
+            )
+        })
+    }
+
+    //mp set_point_size
+    fn set_point_size(&mut self, point_size: Option<f32>) {
+        unsafe {
+            if let Some(size) = point_size {
+                gl::Disable(gl::PROGRAM_POINT_SIZE);
+                gl::PointSize(size);
+            } else {
+                gl::Enable(gl::PROGRAM_POINT_SIZE);
+            }
+        }
+        self.point_size = point_size;
+    }
+
+    //ap point_size
+    fn point_size(&self) -> Option<f32> {
+        self.point_size
+    }
+
+    //mp set_attribute_format
+    fn set_attribute_format(
+        &mut self,
+        vertex_attr: VertexAttr,
+        format: Option<crate::AttributeFormat>,
+    ) {
+        self.attribute_formats.retain(|(va, _)| *va != vertex_attr);
+        if let Some(format) = format {
+            self.attribute_formats.push((vertex_attr, format));
+        }
+    }
+
+    //ap attribute_format
+    fn attribute_format(
+        &self,
+        vertex_attr: VertexAttr,
+        ele_type: BufferElementType,
+    ) -> crate::AttributeFormat {
+        self.attribute_formats
+            .iter()
+            .find(|(va, _)| *va == vertex_attr)
+            .map(|(_, format)| *format)
+            .unwrap_or_else(|| crate::AttributeFormat::of_vertex_attr(vertex_attr, ele_type))
+    }
+
     //mp bind_vao
     fn bind_vao(&mut self, vao: Option<&Self::Vao>) {
         if let Some(vao) = vao {
@@ -224,6 +1213,53 @@ impl Gl for Model3DOpenGL {
         }
     }
 
+    //mp dispatch_compute
+    fn dispatch_compute(
+        &mut self,
+        program: &Self::Program,
+        num_groups: (u32, u32, u32),
+    ) -> Result<(), String> {
+        self.use_program(Some(program));
+        unsafe {
+            gl::DispatchCompute(num_groups.0, num_groups.1, num_groups.2);
+        }
+        utils::check_errors().map_err(|e| format!("DispatchCompute failed: {}", e.join(", ")))
+    }
+
+    //mp memory_barrier
+    fn memory_barrier(&mut self, barrier: MemoryBarrier) {
+        let mut bits = 0;
+        for (b, gl_bit) in [
+            (
+                MemoryBarrier::VERTEX_ATTRIB_ARRAY,
+                gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT,
+            ),
+            (MemoryBarrier::ELEMENT_ARRAY, gl::ELEMENT_ARRAY_BARRIER_BIT),
+            (MemoryBarrier::UNIFORM, gl::UNIFORM_BARRIER_BIT),
+            (MemoryBarrier::TEXTURE_FETCH, gl::TEXTURE_FETCH_BARRIER_BIT),
+            (
+                MemoryBarrier::SHADER_IMAGE_ACCESS,
+                gl::SHADER_IMAGE_ACCESS_BARRIER_BIT,
+            ),
+            (MemoryBarrier::COMMAND, gl::COMMAND_BARRIER_BIT),
+            (MemoryBarrier::BUFFER_UPDATE, gl::BUFFER_UPDATE_BARRIER_BIT),
+            (
+                MemoryBarrier::SHADER_STORAGE,
+                gl::SHADER_STORAGE_BARRIER_BIT,
+            ),
+        ] {
+            if barrier.contains(b) {
+                bits |= gl_bit;
+            }
+        }
+        if barrier == MemoryBarrier::ALL {
+            bits = gl::ALL_BARRIER_BITS;
+        }
+        unsafe {
+            gl::MemoryBarrier(bits);
+        }
+    }
+
     //mp uniform_buffer_create
     fn uniform_buffer_create<F: Sized>(
         &mut self,
@@ -233,6 +1269,7 @@ impl Gl for Model3DOpenGL {
         let byte_length = std::mem::size_of_val(data);
         let mut gl = buffer::Buffer::default();
         gl.uniform_buffer(data, is_dynamic)?;
+        self.track_buffer(&mut gl, ResourceKind::UniformBuffer, byte_length);
         Ok(UniformBuffer::new(gl, byte_length))
     }
 
@@ -267,6 +1304,43 @@ impl Gl for Model3DOpenGL {
             );
         }
     }
+
+    //mp storage_buffer_create
+    /// Storage buffers require OpenGL 4.3 or later
+    fn storage_buffer_create(
+        &mut self,
+        data: &[u8],
+        usage: crate::BufferUsage,
+    ) -> Result<StorageBuffer<Self>, String> {
+        if !gl::ShaderStorageBlockBinding::is_loaded() {
+            return Err("Shader storage buffers require OpenGL 4.3 or later".into());
+        }
+        let buffer = self.buffer_create(data.len(), usage);
+        buffer.update_data(data, 0);
+        Ok(StorageBuffer::new(buffer, data.len()))
+    }
+
+    //mp storage_index_of_range
+    fn storage_index_of_range(
+        &mut self,
+        storage_buffer: &StorageBuffer<Self>,
+        gl_index: u32,
+        byte_offset: usize,
+        byte_length: usize,
+    ) {
+        let (byte_offset, byte_length) = storage_buffer.offset_and_length(byte_offset, byte_length);
+        let buffer = storage_buffer.gl_buffer();
+        let byte_offset = buffer.byte_offset() as usize + byte_offset;
+        unsafe {
+            gl::BindBufferRange(
+                gl::SHADER_STORAGE_BUFFER,
+                gl_index,
+                buffer.gl_buffer(),
+                byte_offset as isize,
+                byte_length as isize,
+            );
+        }
+    }
 }
 
 //ip mod3d_base::Renderable for Model3DOpenGL
@@ -288,7 +1362,20 @@ impl mod3d_base::Renderable for Model3DOpenGL {
         buffer_data: &mod3d_base::BufferData<Self>,
     ) {
         if client.is_none() {
-            client.of_data(buffer_data)
+            if let Some(mut buffer_pool) = self.buffer_pool.take() {
+                if let Some(buffer) = buffer_pool.allocate(self, buffer_data.as_slice()) {
+                    *client = buffer;
+                }
+                self.buffer_pool = Some(buffer_pool);
+            }
+        }
+        if client.is_none() {
+            client.of_data(buffer_data);
+            self.track_buffer(
+                client,
+                ResourceKind::VertexBuffer,
+                buffer_data.byte_length as usize,
+            );
         }
     }
 
@@ -309,9 +1396,22 @@ impl mod3d_base::Renderable for Model3DOpenGL {
     }
 
     //mp create_texture_client
+    /// Create the client texture; if this fails the error is logged
+    /// and an empty texture is used, which samples as black
     fn create_texture_client(&mut self, texture: &mod3d_base::Texture<Self>) -> Self::Texture {
         eprintln!("Create texture client");
-        Self::Texture::of_texture(texture) // , self)
+        let kind = {
+            if texture.dims().2 == 0 {
+                TextureKind::Texture2D
+            } else {
+                self.depth_texture_kind
+            }
+        };
+        self.texture_create(texture, kind, self.texture_mipmaps)
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to create texture client: {e}");
+                texture::Texture::default()
+            })
     }
 
     fn create_material_client<M>(
@@ -1,8 +1,37 @@
 //a Imports
 use super::Model3DOpenGL;
+use crate::{AttributeFormat, AttributeKind, BufferUsage, PoolAllocation, TrackedResource};
 use mod3d_base::{BufferClient, BufferData, BufferElementType};
 use std::rc::Rc;
 
+//a Functions
+//fi gl_usage
+/// Get the OpenGL usage hint for a [BufferUsage]
+fn gl_usage(usage: BufferUsage) -> gl::types::GLenum {
+    match usage {
+        BufferUsage::Static => gl::STATIC_DRAW,
+        BufferUsage::Dynamic => gl::DYNAMIC_DRAW,
+        BufferUsage::Stream => gl::STREAM_DRAW,
+    }
+}
+
+//fi gl_ele_type
+/// Get the OpenGL type for vertex attribute elements, and whether it
+/// is an integer type
+fn gl_ele_type(ele_type: BufferElementType, signed: bool) -> (gl::types::GLenum, bool) {
+    use BufferElementType::*;
+    match (ele_type, signed) {
+        (Float32, _) => (gl::FLOAT, false),
+        (Float16, _) => (gl::HALF_FLOAT, false),
+        (Int8, true) => (gl::BYTE, true),
+        (Int8, false) => (gl::UNSIGNED_BYTE, true),
+        (Int16, true) => (gl::SHORT, true),
+        (Int16, false) => (gl::UNSIGNED_SHORT, true),
+        (Int32, true) => (gl::INT, true),
+        (Int32, false) => (gl::UNSIGNED_INT, true),
+    }
+}
+
 //a Buffer
 //tp Buffer
 /// A simple structure provides a reference-counted OpenGl buffer;
@@ -19,17 +48,33 @@ use std::rc::Rc;
 /// the buffer in this case must be an OpenGL ELEMENT_ARRAY_BUFFER;
 /// this could perhaps be optimized to reduce the number of OpenGL
 /// buffers with much more code.
+///
+/// A buffer allocated from a [crate::BufferPool] shares the OpenGL
+/// buffer of a pool block, and its data starts at a byte offset
 #[derive(Debug, Clone)]
 pub struct Buffer {
     /// The OpenGL Buffer
     gl: Rc<gl::types::GLuint>,
+    /// Byte offset of the data in the OpenGL buffer
+    byte_offset: u32,
+    /// The pool allocation, if the buffer is from a pool; this is
+    /// released when the last clone is dropped
+    allocation: Option<Rc<PoolAllocation>>,
+    /// The record of the OpenGL buffer in the resource statistics,
+    /// shared by all clones
+    tracked: Option<Rc<TrackedResource>>,
 }
 
 //ip Default for Buffer
 impl Default for Buffer {
     fn default() -> Self {
         let gl = Rc::new(0);
-        Self { gl }
+        Self {
+            gl,
+            byte_offset: 0,
+            allocation: None,
+            tracked: None,
+        }
     }
 }
 
@@ -50,6 +95,18 @@ impl Buffer {
         Rc::as_ptr(&self.gl)
     }
 
+    //ap tracked
+    /// Get the record of the buffer in the resource statistics, if any
+    pub fn tracked(&self) -> Option<&TrackedResource> {
+        self.tracked.as_deref()
+    }
+
+    //mp set_tracked
+    /// Set the record of the buffer in the resource statistics
+    pub fn set_tracked(&mut self, tracked: TrackedResource) {
+        self.tracked = Some(Rc::new(tracked));
+    }
+
     //mp is_none
     /// Return true if the buffer is not initialized
     pub fn is_none(&self) -> bool {
@@ -60,19 +117,153 @@ impl Buffer {
     /// Create the OpenGL ARRAY_BUFFER buffer using STATIC_DRAW - this copies the data in to OpenGL
     pub fn of_data(&mut self, data: &BufferData<Model3DOpenGL>) {
         assert!(self.is_none());
-        let mut gl: gl::types::GLuint = 0;
+        self.of_data_with_usage(data, BufferUsage::Static);
+    }
+
+    //mp of_data_with_usage
+    /// Create the OpenGL ARRAY_BUFFER buffer with a usage - this
+    /// copies the data in to OpenGL
+    ///
+    /// If the buffer already exists then its storage is respecified
+    /// with the data, keeping the same OpenGL buffer
+    pub fn of_data_with_usage(&mut self, data: &BufferData<Model3DOpenGL>, usage: BufferUsage) {
+        assert!(self.allocation.is_none());
+        let mut gl: gl::types::GLuint = *self.gl;
         unsafe {
-            gl::GenBuffers(1, (&mut gl) as *mut gl::types::GLuint);
+            if gl == 0 {
+                gl::GenBuffers(1, (&mut gl) as *mut gl::types::GLuint);
+            }
             gl::BindBuffer(gl::ARRAY_BUFFER, gl);
             gl::BufferData(
                 gl::ARRAY_BUFFER,
                 data.byte_length as gl::types::GLsizeiptr,
                 data.as_ptr() as *const gl::types::GLvoid,
-                gl::STATIC_DRAW,
+                gl_usage(usage),
             );
             gl::BindBuffer(gl::ARRAY_BUFFER, 0); // unbind to protect
         }
+        if self.is_none() {
+            self.gl = Rc::new(gl);
+        }
+    }
+
+    //mp create
+    /// Create an OpenGL ARRAY_BUFFER buffer of a byte length and
+    /// usage, with uninitialized contents
+    pub fn create(&mut self, byte_length: usize, usage: BufferUsage) {
+        assert!(self.is_none());
+        let mut gl: gl::types::GLuint = 0;
+        unsafe {
+            gl::GenBuffers(1, (&mut gl) as *mut gl::types::GLuint);
+        }
         self.gl = Rc::new(gl);
+        self.orphan(byte_length, usage);
+    }
+
+    //mp of_pool_allocation
+    /// Create a buffer for an allocation within this (pool block) buffer
+    pub fn of_pool_allocation(&self, allocation: PoolAllocation) -> Self {
+        Self {
+            gl: self.gl.clone(),
+            byte_offset: allocation.byte_offset() as u32,
+            allocation: Some(Rc::new(allocation)),
+            tracked: self.tracked.clone(),
+        }
+    }
+
+    //mp update_data
+    /// Update (a portion of) the buffer data starting at a byte
+    /// offset (relative to the start of the data)
+    ///
+    /// The buffer is bound to COPY_WRITE_BUFFER, so this may be used
+    /// for vertex and index data
+    pub fn update_data(&self, data: &[u8], byte_offset: usize) {
+        assert!(!self.is_none());
+        let byte_offset = self.byte_offset as usize + byte_offset;
+        unsafe {
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.gl_buffer());
+            gl::BufferSubData(
+                gl::COPY_WRITE_BUFFER,
+                byte_offset as gl::types::GLintptr,
+                data.len() as gl::types::GLsizeiptr,
+                data.as_ptr() as *const gl::types::GLvoid,
+            );
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0); // unbind to protect
+        }
+    }
+
+    //mp write_unsynchronized
+    /// Write data to a byte range (relative to the start of the data)
+    /// through a mapping of the range that does not wait for the GPU,
+    /// and which invalidates the previous contents of the range
+    pub fn write_unsynchronized(&self, data: &[u8], byte_offset: usize) -> Result<(), String> {
+        assert!(!self.is_none());
+        if data.is_empty() {
+            return Ok(());
+        }
+        let byte_offset = self.byte_offset as usize + byte_offset;
+        unsafe {
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.gl_buffer());
+            let ptr = gl::MapBufferRange(
+                gl::COPY_WRITE_BUFFER,
+                byte_offset as gl::types::GLintptr,
+                data.len() as gl::types::GLsizeiptr,
+                gl::MAP_WRITE_BIT | gl::MAP_INVALIDATE_RANGE_BIT | gl::MAP_UNSYNCHRONIZED_BIT,
+            );
+            let result = {
+                if ptr.is_null() {
+                    Err("Failed to map buffer range".to_string())
+                } else {
+                    std::ptr::copy_nonoverlapping(data.as_ptr(), ptr as *mut u8, data.len());
+                    if gl::UnmapBuffer(gl::COPY_WRITE_BUFFER) == gl::FALSE {
+                        Err("Mapped buffer data was lost".to_string())
+                    } else {
+                        Ok(())
+                    }
+                }
+            };
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0); // unbind to protect
+            result
+        }
+    }
+
+    //mp read_data
+    /// Read (a portion of) the buffer data starting at a byte offset
+    /// (relative to the start of the data) in to a slice
+    pub fn read_data(&self, byte_offset: usize, data: &mut [u8]) {
+        assert!(!self.is_none());
+        let byte_offset = self.byte_offset as usize + byte_offset;
+        unsafe {
+            gl::BindBuffer(gl::COPY_READ_BUFFER, self.gl_buffer());
+            gl::GetBufferSubData(
+                gl::COPY_READ_BUFFER,
+                byte_offset as gl::types::GLintptr,
+                data.len() as gl::types::GLsizeiptr,
+                data.as_mut_ptr() as *mut gl::types::GLvoid,
+            );
+            gl::BindBuffer(gl::COPY_READ_BUFFER, 0);
+        }
+    }
+
+    //mp orphan
+    /// Replace the buffer storage with new uninitialized storage of a
+    /// byte length and usage; it is bound to COPY_WRITE_BUFFER, so this
+    /// may be used for vertex and index data
+    ///
+    /// This must not be used for a buffer allocated from a pool
+    pub fn orphan(&self, byte_length: usize, usage: BufferUsage) {
+        assert!(!self.is_none());
+        assert!(self.allocation.is_none());
+        unsafe {
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, self.gl_buffer());
+            gl::BufferData(
+                gl::COPY_WRITE_BUFFER,
+                byte_length as gl::types::GLsizeiptr,
+                std::ptr::null(),
+                gl_usage(usage),
+            );
+            gl::BindBuffer(gl::COPY_WRITE_BUFFER, 0); // unbind to protect
+        }
     }
 
     //mp of_indices
@@ -110,44 +301,63 @@ impl Buffer {
 
     //fp bind_to_vao_attr
     /// Bind the buffer as a vertex attribute to the current VAO
+    ///
+    /// Integer data uses glVertexAttribIPointer if the format is
+    /// [AttributeKind::Integer], and is normalized if it is
+    /// [AttributeKind::Normalized]
+    ///
+    /// A count of 9 or 16 (mat3 or mat4) is bound as one column per
+    /// attribute location, starting at attr_id
     pub fn bind_to_vao_attr(
         &self,
         attr_id: gl::types::GLuint,
         count: u32,
         ele_type: mod3d_base::BufferElementType,
+        format: AttributeFormat,
         byte_offset: u32,
         stride: u32,
     ) {
-        let ele_type = {
-            use BufferElementType::*;
-            match ele_type {
-                Float32 => gl::FLOAT,
-                Float16 => gl::HALF_FLOAT,
-                Int8 => gl::BYTE,
-                Int16 => gl::SHORT,
-                Int32 => gl::INT,
-            }
+        let (columns, rows) = crate::buffer::attribute_columns(count);
+        let column_size = rows * crate::buffer::ele_byte_size(ele_type);
+        // A stride of 0 would be tightly packed columns, not matrices
+        let stride = if stride == 0 && columns > 1 {
+            columns * column_size
+        } else {
+            stride
         };
+        let (ele_type, is_integer) = gl_ele_type(ele_type, format.signed);
         unsafe {
             gl::BindBuffer(gl::ARRAY_BUFFER, self.gl_buffer());
             crate::opengl_utils::check_errors().unwrap();
-            gl::EnableVertexAttribArray(attr_id);
-            crate::opengl_utils::check_errors().unwrap();
-            gl::VertexAttribPointer(
-                attr_id,
-                count as i32, // size
-                ele_type,
-                gl::FALSE,     // normalized
-                stride as i32, // stride
-                byte_offset as usize as *const std::ffi::c_void,
-            );
-            crate::opengl_utils::check_errors().unwrap();
+        }
+        for c in 0..columns {
+            let attr_id = attr_id + c;
+            let offset = (byte_offset + c * column_size) as usize as *const std::ffi::c_void;
+            unsafe {
+                gl::EnableVertexAttribArray(attr_id);
+                crate::opengl_utils::check_errors().unwrap();
+                if is_integer && format.kind == AttributeKind::Integer {
+                    gl::VertexAttribIPointer(attr_id, rows as i32, ele_type, stride as i32, offset);
+                } else {
+                    let normalized = is_integer && format.kind == AttributeKind::Normalized;
+                    gl::VertexAttribPointer(
+                        attr_id,
+                        rows as i32, // size
+                        ele_type,
+                        if normalized { gl::TRUE } else { gl::FALSE },
+                        stride as i32, // stride
+                        offset,
+                    );
+                }
+                crate::opengl_utils::check_errors().unwrap();
+            }
         }
     }
 
     //mp uniform_buffer
-    /// Create the OpenGL
-    pub fn uniform_buffer<F: Sized>(&mut self, data: &[F], _is_dynamic: bool) -> Result<(), ()> {
+    /// Create the OpenGL UNIFORM_BUFFER buffer, using DYNAMIC_DRAW if
+    /// it is dynamic and STATIC_DRAW otherwise
+    pub fn uniform_buffer<F: Sized>(&mut self, data: &[F], is_dynamic: bool) -> Result<(), ()> {
         assert!(self.is_none());
         let buffer = data.as_ptr();
         let byte_length = std::mem::size_of_val(data);
@@ -160,7 +370,11 @@ impl Buffer {
                 gl::UNIFORM_BUFFER,
                 byte_length as gl::types::GLsizeiptr,
                 buffer as *const gl::types::GLvoid,
-                gl::STATIC_DRAW,
+                if is_dynamic {
+                    gl::DYNAMIC_DRAW
+                } else {
+                    gl::STATIC_DRAW
+                },
             );
             gl::BindBuffer(gl::UNIFORM_BUFFER, 0); // unbind to protect
         }
@@ -200,11 +414,25 @@ impl Drop for Buffer {
 }
 
 //ip GlBuffer for Buffer
-impl crate::GlBuffer for Buffer {}
+impl crate::GlBuffer for Buffer {
+    fn byte_offset(&self) -> u32 {
+        self.byte_offset
+    }
+    fn of_pool_allocation(&self, allocation: PoolAllocation) -> Self {
+        self.of_pool_allocation(allocation)
+    }
+    fn is_pool_allocation(&self) -> bool {
+        self.allocation.is_some()
+    }
+}
 
 //ip Display for Buffer
 impl std::fmt::Display for Buffer {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-        write!(f, "GL({})", self.gl)
+        if self.allocation.is_some() {
+            write!(f, "GL({}@{})", self.gl, self.byte_offset)
+        } else {
+            write!(f, "GL({})", self.gl)
+        }
     }
 }
@@ -0,0 +1,74 @@
+//a Imports
+
+//a Constants
+/// Timeout of each client wait on a fence, in nanoseconds
+const WAIT_TIMEOUT: u64 = 1_000_000_000;
+
+/// Number of client waits on a fence before waiting fails
+const WAIT_RETRIES: usize = 5;
+
+//a Fence
+//tp Fence
+/// An OpenGL sync object for the completion of GPU commands; it is
+/// deleted when dropped
+#[derive(Debug)]
+pub struct Fence {
+    sync: gl::types::GLsync,
+}
+
+//ip Fence
+impl Fence {
+    //fp insert
+    /// Insert a fence after the commands issued so far
+    pub fn insert() -> Option<Self> {
+        let sync = unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+        if sync.is_null() {
+            None
+        } else {
+            Some(Self { sync })
+        }
+    }
+
+    //mp wait
+    /// Wait for the GPU to complete the commands before the fence,
+    /// flushing the commands if required
+    ///
+    /// This fails if the wait fails, or if the GPU has not completed
+    /// the commands after a number of timeouts
+    pub fn wait(&self) -> Result<(), String> {
+        for _ in 0..WAIT_RETRIES {
+            let result =
+                unsafe { gl::ClientWaitSync(self.sync, gl::SYNC_FLUSH_COMMANDS_BIT, WAIT_TIMEOUT) };
+            match result {
+                gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => {
+                    return Ok(());
+                }
+                gl::WAIT_FAILED => {
+                    return Err("Wait on a fence failed".into());
+                }
+                _ => (),
+            }
+        }
+        Err(format!(
+            "GPU did not signal a fence within {} seconds",
+            WAIT_RETRIES as u64 * WAIT_TIMEOUT / 1_000_000_000
+        ))
+    }
+
+    //mp is_signalled
+    /// Return true if the GPU has completed the commands before the
+    /// fence, without waiting
+    pub fn is_signalled(&self) -> bool {
+        let result = unsafe { gl::ClientWaitSync(self.sync, 0, 0) };
+        result == gl::ALREADY_SIGNALED || result == gl::CONDITION_SATISFIED
+    }
+}
+
+//ip Drop for Fence
+impl Drop for Fence {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteSync(self.sync);
+        }
+    }
+}
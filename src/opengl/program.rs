@@ -7,11 +7,13 @@ A shader program consists of a number of [GlShader]s linked together
 !*/
 
 //a Imports
+use std::cell::RefCell;
 use std::ffi::CString;
 
 use super::utils;
 use super::Shader;
-use crate::{TextureId, UniformId};
+use crate::{ActiveVariable, GlShaderType, ProgramReflection, TextureId, UniformId};
+use crate::{TrackedResource, UniformCache, UniformStats, UniformValue};
 
 //a Program
 //tp Program
@@ -25,6 +27,16 @@ pub struct Program {
     uniforms: Vec<(gl::types::GLint, UniformId)>,
     /// texture map from TextureId to uniform location and unit
     textures: Vec<(gl::types::GLint, TextureId, u32)>,
+    /// storage block map from storage buffer id to block index
+    storage_buffers: Vec<(gl::types::GLuint, usize)>,
+    /// shadow copy of the uniform values last uploaded
+    uniform_cache: RefCell<UniformCache>,
+    /// default values of vertex attributes that a mesh may lack
+    attribute_defaults: Vec<(mod3d_base::VertexAttr, [f32; 4])>,
+    /// The record of the program in the resource statistics
+    tracked: Option<TrackedResource>,
+    /// The shader stages linked in to the program
+    stages: Vec<GlShaderType>,
 }
 
 ///ip Program
@@ -93,17 +105,142 @@ impl Program {
         result
     }
 
+    //mp reflection
+    /// Get the active attributes, uniforms and uniform blocks of the
+    /// program; uniforms that are members of uniform blocks are not
+    /// included
+    pub fn reflection(&self) -> ProgramReflection {
+        let mut reflection = ProgramReflection::default();
+
+        let count = utils::get_programiv(self.id, gl::ACTIVE_ATTRIBUTES);
+        let mut name = utils::name_buffer(self.id, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH);
+        for i in 0..(count as u32) {
+            let mut length = 0;
+            let mut size = 0;
+            let mut gl_type = 0;
+            unsafe {
+                gl::GetActiveAttrib(
+                    self.id,
+                    i,
+                    name.len() as i32,
+                    &mut length,
+                    &mut size,
+                    &mut gl_type,
+                    name.as_mut_ptr() as *mut gl::types::GLchar,
+                );
+            }
+            let name = String::from_utf8_lossy(&name[..length as usize]);
+            reflection
+                .attributes
+                .push(ActiveVariable::new(&name, gl_type, size as usize));
+        }
+
+        let count = utils::get_programiv(self.id, gl::ACTIVE_UNIFORMS);
+        let mut name = utils::name_buffer(self.id, gl::ACTIVE_UNIFORM_MAX_LENGTH);
+        for i in 0..(count as u32) {
+            let mut length = 0;
+            let mut size = 0;
+            let mut gl_type = 0;
+            let mut block_index = -1;
+            unsafe {
+                gl::GetActiveUniform(
+                    self.id,
+                    i,
+                    name.len() as i32,
+                    &mut length,
+                    &mut size,
+                    &mut gl_type,
+                    name.as_mut_ptr() as *mut gl::types::GLchar,
+                );
+                gl::GetActiveUniformsiv(
+                    self.id,
+                    1,
+                    &i,
+                    gl::UNIFORM_BLOCK_INDEX,
+                    &mut block_index,
+                );
+            }
+            if block_index >= 0 {
+                continue;
+            }
+            let name = String::from_utf8_lossy(&name[..length as usize]);
+            reflection
+                .uniforms
+                .push(ActiveVariable::new(&name, gl_type, size as usize));
+        }
+
+        let count = utils::get_programiv(self.id, gl::ACTIVE_UNIFORM_BLOCKS);
+        let mut name = utils::name_buffer(self.id, gl::ACTIVE_UNIFORM_BLOCK_MAX_NAME_LENGTH);
+        for i in 0..(count as u32) {
+            let mut length = 0;
+            unsafe {
+                gl::GetActiveUniformBlockName(
+                    self.id,
+                    i,
+                    name.len() as i32,
+                    &mut length,
+                    name.as_mut_ptr() as *mut gl::types::GLchar,
+                );
+            }
+            let name = String::from_utf8_lossy(&name[..length as usize]);
+            reflection.uniform_blocks.push(name.into_owned());
+        }
+        reflection
+    }
+
     //fp link_program
-    /// Compile a program from a slice of kind/source pairs
-    pub fn link_program(shaders: &[&Shader]) -> Result<Program, String> {
+    /// Compile a program from a slice of kind/source pairs, capturing
+    /// the feedback varyings (if any)
+    pub fn link_program(
+        shaders: &[&Shader],
+        feedback_varyings: &[&str],
+    ) -> Result<Program, String> {
+        Self::link(shaders, feedback_varyings, false)
+    }
+
+    //fp link
+    /// Link a program from compiled shaders, with the feedback
+    /// varyings interleaved in a transform feedback buffer; if
+    /// `retrievable` then hint to the driver that the binary will be
+    /// retrieved with [Self::binary]
+    pub fn link(
+        shaders: &[&Shader],
+        feedback_varyings: &[&str],
+        retrievable: bool,
+    ) -> Result<Program, String> {
+        let varyings_c: Vec<CString> = feedback_varyings
+            .iter()
+            .map(|v| CString::new(*v).unwrap())
+            .collect();
+        let varyings_ptrs: Vec<*const gl::types::GLchar> =
+            varyings_c.iter().map(|v| v.as_ptr()).collect();
         let program_id = unsafe {
             let program_id = gl::CreateProgram();
             for shader in shaders {
                 gl::AttachShader(program_id, shader.id());
             }
+            if !varyings_ptrs.is_empty() {
+                gl::TransformFeedbackVaryings(
+                    program_id,
+                    varyings_ptrs.len() as gl::types::GLsizei,
+                    varyings_ptrs.as_ptr(),
+                    gl::INTERLEAVED_ATTRIBS,
+                );
+            }
+            if retrievable && Self::binaries_supported() {
+                gl::ProgramParameteri(
+                    program_id,
+                    gl::PROGRAM_BINARY_RETRIEVABLE_HINT,
+                    gl::TRUE as i32,
+                );
+            }
             gl::LinkProgram(program_id);
             program_id
         };
+        let stages = shaders
+            .iter()
+            .filter_map(|s| Self::shader_stage(s))
+            .collect();
 
         if utils::get_programiv(program_id, gl::LINK_STATUS) == 0 {
             let err = utils::get_shader_error(
@@ -111,23 +248,154 @@ impl Program {
                 |id| utils::get_programiv(id, gl::INFO_LOG_LENGTH),
                 |id, len, buf| unsafe { gl::GetProgramInfoLog(id, len, std::ptr::null_mut(), buf) },
             );
+            unsafe {
+                gl::DeleteProgram(program_id);
+            }
             Err(format!(
                 "Unable to create shader program, linking error {}",
                 err
             ))?;
         }
         utils::check_errors().expect("Linked");
+        let mut program = Self::of_id(program_id);
+        program.set_stages(stages);
+        Ok(program)
+    }
+
+    //fi shader_stage
+    /// Get the stage of a compiled shader
+    fn shader_stage(shader: &Shader) -> Option<GlShaderType> {
+        let mut gl_type = 0;
+        unsafe {
+            gl::GetShaderiv(shader.id(), gl::SHADER_TYPE, &mut gl_type);
+        }
+        match gl_type as gl::types::GLenum {
+            gl::VERTEX_SHADER => Some(GlShaderType::Vertex),
+            gl::FRAGMENT_SHADER => Some(GlShaderType::Fragment),
+            gl::GEOMETRY_SHADER => Some(GlShaderType::Geometry),
+            gl::TESS_CONTROL_SHADER => Some(GlShaderType::TessControl),
+            gl::TESS_EVALUATION_SHADER => Some(GlShaderType::TessEvaluation),
+            gl::COMPUTE_SHADER => Some(GlShaderType::Compute),
+            _ => None,
+        }
+    }
+
+    //fp of_binary
+    /// Create a program from a binary previously retrieved with
+    /// [Self::binary]; this fails if the driver rejects the binary
+    pub fn of_binary(format: gl::types::GLenum, binary: &[u8]) -> Result<Program, String> {
+        if !Self::binaries_supported() {
+            return Err("Program binaries require OpenGL 4.1 or ARB_get_program_binary".into());
+        }
+        let program_id = unsafe {
+            let program_id = gl::CreateProgram();
+            gl::ProgramBinary(
+                program_id,
+                format,
+                binary.as_ptr() as *const gl::types::GLvoid,
+                binary.len() as gl::types::GLsizei,
+            );
+            program_id
+        };
+        // Drivers report a rejected binary as a GL error and/or a link failure
+        let gl_ok = utils::check_errors().is_ok();
+        if !gl_ok || utils::get_programiv(program_id, gl::LINK_STATUS) == 0 {
+            unsafe {
+                gl::DeleteProgram(program_id);
+            }
+            Err("Program binary rejected by the driver".to_string())
+        } else {
+            Ok(Self::of_id(program_id))
+        }
+    }
+
+    //fp binaries_supported
+    /// Return true if the GL supports program binaries (OpenGL 4.1 or
+    /// ARB_get_program_binary)
+    pub fn binaries_supported() -> bool {
+        gl::ProgramBinary::is_loaded()
+            && gl::GetProgramBinary::is_loaded()
+            && gl::ProgramParameteri::is_loaded()
+    }
 
+    //fi of_id
+    fn of_id(id: gl::types::GLuint) -> Program {
         let attributes = Vec::new();
         let uniforms = Vec::new();
         let textures = Vec::new();
-        let program = Program {
-            id: program_id,
+        let storage_buffers = Vec::new();
+        let uniform_cache = RefCell::new(UniformCache::default());
+        let attribute_defaults = Vec::new();
+        Program {
+            id,
             attributes,
             uniforms,
             textures,
-        };
-        Ok(program)
+            storage_buffers,
+            uniform_cache,
+            attribute_defaults,
+            tracked: None,
+            stages: Vec::new(),
+        }
+    }
+
+    //mp set_stages
+    /// Set the shader stages of the program; this is required for a
+    /// program created with [Self::of_binary], whose stages the GL
+    /// does not report
+    pub fn set_stages(&mut self, stages: Vec<GlShaderType>) {
+        self.stages = stages;
+    }
+
+    //ap stages
+    /// Get the shader stages linked in to the program
+    pub fn stages(&self) -> &[GlShaderType] {
+        &self.stages
+    }
+
+    //ap has_tessellation
+    /// Return true if the program has a tessellation stage, and so
+    /// must draw patches
+    pub fn has_tessellation(&self) -> bool {
+        self.stages
+            .iter()
+            .any(|s| matches!(s, GlShaderType::TessControl | GlShaderType::TessEvaluation))
+    }
+
+    //mp set_tracked
+    /// Set the record of the program in the resource statistics
+    pub fn set_tracked(&mut self, tracked: TrackedResource) {
+        self.tracked = Some(tracked);
+    }
+
+    //mp binary
+    /// Retrieve the binary format and data of the linked program, if
+    /// the driver supports it
+    pub fn binary(&self) -> Option<(gl::types::GLenum, Vec<u8>)> {
+        if !Self::binaries_supported() {
+            return None;
+        }
+        let length = utils::get_programiv(self.id, gl::PROGRAM_BINARY_LENGTH);
+        if length <= 0 {
+            return None;
+        }
+        let mut binary = vec![0_u8; length as usize];
+        let mut format = 0;
+        let mut written = 0;
+        unsafe {
+            gl::GetProgramBinary(
+                self.id,
+                length,
+                &mut written,
+                &mut format,
+                binary.as_mut_ptr() as *mut gl::types::GLvoid,
+            );
+        }
+        if utils::check_errors().is_err() || written <= 0 {
+            return None;
+        }
+        binary.truncate(written as usize);
+        Some((format, binary))
     }
 
     //mp add_attr_name
@@ -183,6 +451,31 @@ impl Program {
         }
     }
 
+    //mp add_storage_buffer_name
+    /// Add a shader storage block to the [Program] from its name
+    /// (that should be in the shader source)
+    pub fn add_storage_buffer_name(&mut self, name: &str, id: usize) -> Result<&mut Self, String> {
+        let name_c = CString::new(name).unwrap();
+        let block_index = unsafe {
+            gl::GetProgramResourceIndex(self.id, gl::SHADER_STORAGE_BLOCK, name_c.as_ptr())
+        };
+        if block_index == gl::INVALID_INDEX {
+            Err(format!("Unable to find storage block {} in program", name))
+        } else {
+            self.storage_buffers.push((block_index, id));
+            Ok(self)
+        }
+    }
+
+    //ap storage_buffer
+    /// Get the block index of a storage block from its storage buffer id
+    pub fn storage_buffer(&self, storage_buffer_id: usize) -> Option<gl::types::GLuint> {
+        self.storage_buffers
+            .iter()
+            .find(|(_, id)| *id == storage_buffer_id)
+            .map(|(block_index, _)| *block_index)
+    }
+
     //mp add_uniform_texture_name
     /// Add a texture assigned to a texture unit and a named uniform sampler
     ///
@@ -260,4 +553,22 @@ impl crate::GlProgram for Program {
         }
         None
     }
+    fn uniform_changed(&self, uniform_id: UniformId, value: &UniformValue) -> bool {
+        self.uniform_cache.borrow_mut().update(uniform_id, value)
+    }
+    fn invalidate_uniform_cache(&self) {
+        self.uniform_cache.borrow_mut().invalidate();
+    }
+    fn uniform_stats(&self) -> UniformStats {
+        self.uniform_cache.borrow().stats()
+    }
+    fn set_attribute_defaults(&mut self, defaults: &[(mod3d_base::VertexAttr, [f32; 4])]) {
+        self.attribute_defaults = defaults.to_vec();
+    }
+    fn attribute_default(&self, vertex_attr: mod3d_base::VertexAttr) -> Option<[f32; 4]> {
+        self.attribute_defaults
+            .iter()
+            .find(|(va, _)| *va == vertex_attr)
+            .map(|(_, d)| *d)
+    }
 }
@@ -0,0 +1,131 @@
+//a Documentation
+
+/*!
+
+A disk cache of linked program binaries
+
+The cache key is a hash of the shader sources and the GL vendor,
+renderer and version strings, so that a driver update invalidates
+the cache. Each entry is a file containing the binary format (as a
+little-endian u32) followed by the binary from glGetProgramBinary.
+
+!*/
+
+//a Imports
+use std::cell::Cell;
+use std::ffi::CStr;
+use std::path::PathBuf;
+
+use crate::GlShaderType;
+
+//a Functions
+//fi fnv1a
+/// A 64-bit FNV-1a hash - this is used rather than the std hasher as
+/// it must be stable across builds
+fn fnv1a(mut hash: u64, data: &[u8]) -> u64 {
+    for b in data {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100_0000_01b3);
+    }
+    hash
+}
+
+//fi gl_string
+/// Get a GL string (such as gl::VENDOR) - the context must be current
+fn gl_string(name: gl::types::GLenum) -> String {
+    unsafe {
+        let s = gl::GetString(name);
+        if s.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(s as *const std::ffi::c_char)
+                .to_string_lossy()
+                .into_owned()
+        }
+    }
+}
+
+//a ProgramCache
+//tp ProgramCache
+/// A directory of cached program binaries for the current GL driver
+#[derive(Debug)]
+pub struct ProgramCache {
+    /// Directory containing the cached binaries
+    dir: PathBuf,
+    /// The driver vendor, renderer and version strings
+    driver_id: String,
+    /// Set once a failure to write an entry has been reported
+    write_failed: Cell<bool>,
+}
+
+//ip ProgramCache
+impl ProgramCache {
+    //fp new
+    /// Create a program cache in a directory, creating the directory
+    /// if required
+    ///
+    /// The GL context must be current, as the driver strings are read
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Result<Self, String> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create program cache {dir:?}: {e}"))?;
+        let driver_id = format!(
+            "{}\n{}\n{}",
+            gl_string(gl::VENDOR),
+            gl_string(gl::RENDERER),
+            gl_string(gl::VERSION)
+        );
+        Ok(Self {
+            dir,
+            driver_id,
+            write_failed: Cell::new(false),
+        })
+    }
+
+    //mp path
+    /// Get the path of the cache entry for a set of shader sources
+    fn path(&self, srcs: &[(GlShaderType, &str)]) -> PathBuf {
+        let mut hash = fnv1a(0xcbf2_9ce4_8422_2325, self.driver_id.as_bytes());
+        for (shader_type, src) in srcs {
+            hash = fnv1a(hash, format!("\0{shader_type:?}\0").as_bytes());
+            hash = fnv1a(hash, src.as_bytes());
+        }
+        self.dir.join(format!("{hash:016x}.bin"))
+    }
+
+    //mp load
+    /// Load the cached binary format and data for a set of shader
+    /// sources, if there is one
+    pub fn load(&self, srcs: &[(GlShaderType, &str)]) -> Option<(gl::types::GLenum, Vec<u8>)> {
+        let data = std::fs::read(self.path(srcs)).ok()?;
+        if data.len() <= 4 {
+            return None;
+        }
+        let format = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        Some((format, data[4..].to_vec()))
+    }
+
+    //mp store
+    /// Store the binary format and data for a set of shader sources
+    ///
+    /// The cache is only an optimization, so a failure to write is
+    /// not an error; the first failure is reported on stderr
+    pub fn store(&self, srcs: &[(GlShaderType, &str)], format: gl::types::GLenum, binary: &[u8]) {
+        let mut data = Vec::with_capacity(binary.len() + 4);
+        data.extend_from_slice(&format.to_le_bytes());
+        data.extend_from_slice(binary);
+        let path = self.path(srcs);
+        if let Err(e) = std::fs::write(&path, data) {
+            if !self.write_failed.replace(true) {
+                eprintln!("Failed to write program cache {path:?}: {e}");
+            }
+        }
+    }
+
+    //mp remove
+    /// Remove the cache entry for a set of shader sources (e.g. if the
+    /// driver rejected it)
+    pub fn remove(&self, srcs: &[(GlShaderType, &str)]) {
+        let _ = std::fs::remove_file(self.path(srcs));
+    }
+}
@@ -24,6 +24,12 @@ impl Shader {
         let (shader_type, shader_kind) = match shader_type {
             GlShaderType::Fragment => (gl::FRAGMENT_SHADER, "fragment"),
             GlShaderType::Vertex => (gl::VERTEX_SHADER, "vertex"),
+            GlShaderType::Geometry => (gl::GEOMETRY_SHADER, "geometry"),
+            GlShaderType::TessControl => (gl::TESS_CONTROL_SHADER, "tessellation control"),
+            GlShaderType::TessEvaluation => {
+                (gl::TESS_EVALUATION_SHADER, "tessellation evaluation")
+            }
+            GlShaderType::Compute => (gl::COMPUTE_SHADER, "compute"),
         };
         let id = unsafe {
             let id = gl::CreateShader(shader_type);
@@ -3,7 +3,74 @@ use std::rc::Rc;
 
 use mod3d_base::TextureClient;
 
-use crate::Model3DOpenGL;
+use crate::{Model3DOpenGL, PixelFormat, TextureKind, TrackedResource};
+
+//a Functions
+//fi gl_target
+/// Get the OpenGL target for a kind of texture
+fn gl_target(kind: TextureKind) -> gl::types::GLenum {
+    match kind {
+        TextureKind::Texture2D => gl::TEXTURE_2D,
+        TextureKind::Texture2DArray => gl::TEXTURE_2D_ARRAY,
+        TextureKind::Texture3D => gl::TEXTURE_3D,
+        TextureKind::CubeMap => gl::TEXTURE_CUBE_MAP,
+    }
+}
+
+//fi gl_data_format
+/// Get the OpenGL format and type of texture data with a number of
+/// channels of an element type
+fn gl_data_format(
+    channels: usize,
+    ele_type: mod3d_base::BufferElementType,
+) -> (gl::types::GLenum, gl::types::GLenum) {
+    let data_format = {
+        match channels {
+            1 => gl::RED,
+            2 => gl::RG,
+            3 => gl::RGB,
+            4 => gl::RGBA,
+            _ => gl::RGBA,
+        }
+    };
+    let data_type = {
+        match ele_type {
+            mod3d_base::BufferElementType::Int8 => gl::UNSIGNED_BYTE,
+            mod3d_base::BufferElementType::Int16 => gl::UNSIGNED_SHORT,
+            mod3d_base::BufferElementType::Float16 => gl::HALF_FLOAT,
+            mod3d_base::BufferElementType::Float32 => gl::FLOAT,
+            _ => gl::UNSIGNED_BYTE,
+        }
+    };
+    (data_format, data_type)
+}
+
+//fi gl_internal_format
+/// Get the OpenGL sized internal format for texture data with a
+/// number of channels of an element type
+///
+/// 8-bit and 16-bit integer data is unsigned normalized
+fn gl_internal_format(
+    channels: usize,
+    ele_type: mod3d_base::BufferElementType,
+) -> Result<gl::types::GLenum, String> {
+    use mod3d_base::BufferElementType::*;
+    let formats = match ele_type {
+        Int8 => [gl::R8, gl::RG8, gl::RGB8, gl::RGBA8],
+        Int16 => [gl::R16, gl::RG16, gl::RGB16, gl::RGBA16],
+        Float16 => [gl::R16F, gl::RG16F, gl::RGB16F, gl::RGBA16F],
+        Float32 => [gl::R32F, gl::RG32F, gl::RGB32F, gl::RGBA32F],
+        _ => {
+            return Err(format!("Textures of {ele_type:?} data are not supported"));
+        }
+    };
+    if !(1..=4).contains(&channels) {
+        return Err(format!(
+            "Textures must have 1 to 4 channels, not {channels}"
+        ));
+    }
+    Ok(formats[channels - 1])
+}
 
 //a Texture
 //tp Texture
@@ -15,13 +82,22 @@ use crate::Model3DOpenGL;
 pub struct Texture {
     /// The OpenGL Texture
     gl: Rc<gl::types::GLuint>,
+    /// The kind of the texture
+    kind: TextureKind,
+    /// The record of the texture in the resource statistics, shared
+    /// by all clones
+    tracked: Option<Rc<TrackedResource>>,
 }
 
 //ip Default for Texture
 impl Default for Texture {
     fn default() -> Self {
         let gl = Rc::new(0);
-        Self { gl }
+        Self {
+            gl,
+            kind: TextureKind::default(),
+            tracked: None,
+        }
     }
 }
 
@@ -42,61 +118,266 @@ impl Texture {
         Rc::as_ptr(&self.gl)
     }
 
+    //mp set_tracked
+    /// Set the record of the texture in the resource statistics
+    pub fn set_tracked(&mut self, tracked: TrackedResource) {
+        self.tracked = Some(Rc::new(tracked));
+    }
+
     //mp is_none
     /// Return true if the buffer is not initialized
     pub fn is_none(&self) -> bool {
         *self.gl == 0
     }
 
+    //ap kind
+    /// Get the kind of the texture
+    pub fn kind(&self) -> TextureKind {
+        self.kind
+    }
+
+    //ap target
+    /// Get the OpenGL target that the texture is bound to
+    pub fn target(&self) -> gl::types::GLenum {
+        gl_target(self.kind)
+    }
+
     //mp of_texture
-    /// Create a texture
-    pub fn of_texture(texture: &mod3d_base::Texture<Model3DOpenGL>) -> Self {
+    /// Create a texture of a kind, optionally generating mipmaps
+    ///
+    /// 3D textures clamp to their edges; 2D and array textures repeat
+    pub fn of_texture(
+        texture: &mod3d_base::Texture<Model3DOpenGL>,
+        kind: TextureKind,
+        mipmaps: bool,
+    ) -> Result<Self, String> {
         let mut gl: gl::types::GLuint = 0;
         let (width, height, depth) = *texture.dims();
-        assert!(depth == 0);
-        assert!(height != 0);
+        kind.check_depth(depth != 0)?;
+        if height == 0 {
+            return Err("Texture data must have a non-zero height".into());
+        }
         let data_type = texture.data_type();
-        let data_format = {
-            match data_type.0 {
-                1 => gl::RED,
-                2 => gl::RG,
-                3 => gl::RGB,
-                4 => gl::RGBA,
-                _ => gl::RGBA,
+        let (data_format, data_type) = gl_data_format(data_type.0 as usize, data_type.1);
+        let target = gl_target(kind);
+        let wrap = {
+            if kind == TextureKind::Texture3D {
+                gl::CLAMP_TO_EDGE
+            } else {
+                gl::REPEAT
             }
         };
-        let data_type = {
-            match data_type.1 {
-                mod3d_base::BufferElementType::Int8 => gl::UNSIGNED_BYTE,
-                mod3d_base::BufferElementType::Int16 => gl::UNSIGNED_SHORT,
-                mod3d_base::BufferElementType::Float16 => gl::SHORT,
-                mod3d_base::BufferElementType::Float32 => gl::FLOAT,
-                _ => gl::UNSIGNED_BYTE,
+        let min_filter = {
+            if mipmaps {
+                gl::LINEAR_MIPMAP_LINEAR
+            } else {
+                gl::LINEAR
             }
         };
         unsafe {
             gl::GenTextures(1, (&mut gl) as *mut gl::types::GLuint);
-            gl::BindTexture(gl::TEXTURE_2D, gl);
-            gl::TexImage2D(
-                gl::TEXTURE_2D,
-                0,
-                gl::RGB as i32,
-                width as i32,
-                height as i32,
-                0,
-                data_format,
-                data_type,
-                texture.data().as_ptr() as *const gl::types::GLvoid,
-            );
+            gl::BindTexture(target, gl);
+            if kind.has_depth() {
+                gl::TexImage3D(
+                    target,
+                    0,
+                    gl::RGB as i32,
+                    width as i32,
+                    height as i32,
+                    depth as i32,
+                    0,
+                    data_format,
+                    data_type,
+                    texture.data().as_ptr() as *const gl::types::GLvoid,
+                );
+            } else {
+                gl::TexImage2D(
+                    target,
+                    0,
+                    gl::RGB as i32,
+                    width as i32,
+                    height as i32,
+                    0,
+                    data_format,
+                    data_type,
+                    texture.data().as_ptr() as *const gl::types::GLvoid,
+                );
+            }
+            if mipmaps {
+                gl::GenerateMipmap(target);
+            }
 
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::TexParameteri(target, gl::TEXTURE_WRAP_S, wrap as i32);
+            gl::TexParameteri(target, gl::TEXTURE_WRAP_T, wrap as i32);
+            if kind == TextureKind::Texture3D {
+                gl::TexParameteri(target, gl::TEXTURE_WRAP_R, wrap as i32);
+            }
+            gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, min_filter as i32);
+            gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::BindTexture(target, 0);
             eprintln!("Created texture {gl:?}");
         }
-        Self { gl: Rc::new(gl) }
+        Ok(Self {
+            gl: Rc::new(gl),
+            kind,
+            tracked: None,
+        })
+    }
+
+    //mp of_cube_faces
+    /// Create a cube map from the data of six square faces of a size
+    /// (in the order +X, -X, +Y, -Y, +Z, -Z), optionally generating
+    /// mipmaps
+    ///
+    /// The internal format has the channels and precision of the data
+    pub fn of_cube_faces(
+        size: usize,
+        channels: usize,
+        ele_type: mod3d_base::BufferElementType,
+        faces: [&[u8]; 6],
+        mipmaps: bool,
+    ) -> Result<Self, String> {
+        let face_byte_size =
+            size * size * channels * crate::buffer::ele_byte_size(ele_type) as usize;
+        if size == 0 || faces.iter().any(|f| f.len() < face_byte_size) {
+            return Err(format!(
+                "Cube map faces must each have {face_byte_size} bytes of data"
+            ));
+        }
+        let mut gl: gl::types::GLuint = 0;
+        let internal_format = gl_internal_format(channels, ele_type)?;
+        let (data_format, data_type) = gl_data_format(channels, ele_type);
+        let min_filter = {
+            if mipmaps {
+                gl::LINEAR_MIPMAP_LINEAR
+            } else {
+                gl::LINEAR
+            }
+        };
+        unsafe {
+            gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+            gl::GenTextures(1, (&mut gl) as *mut gl::types::GLuint);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, gl);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            for (i, face) in faces.iter().enumerate() {
+                gl::TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as u32,
+                    0,
+                    internal_format as i32,
+                    size as i32,
+                    size as i32,
+                    0,
+                    data_format,
+                    data_type,
+                    face.as_ptr() as *const gl::types::GLvoid,
+                );
+            }
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            if mipmaps {
+                gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+            }
+            for wrap in [gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R] {
+                gl::TexParameteri(gl::TEXTURE_CUBE_MAP, wrap, gl::CLAMP_TO_EDGE as i32);
+            }
+            gl::TexParameteri(
+                gl::TEXTURE_CUBE_MAP,
+                gl::TEXTURE_MIN_FILTER,
+                min_filter as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_CUBE_MAP,
+                gl::TEXTURE_MAG_FILTER,
+                gl::LINEAR as i32,
+            );
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+        }
+        Ok(Self {
+            gl: Rc::new(gl),
+            kind: TextureKind::CubeMap,
+            tracked: None,
+        })
+    }
+
+    //mp of_target
+    /// Create a 2D texture or cube map, of square faces of a size, with
+    /// a number of mipmap levels of uninitialized storage in a format,
+    /// to be rendered to
+    ///
+    /// [PixelFormat::RgbaF32] is stored as 16-bit floats, which are
+    /// filterable
+    pub fn of_target(
+        kind: TextureKind,
+        size: usize,
+        format: PixelFormat,
+        levels: usize,
+    ) -> Result<Self, String> {
+        let faces = match kind {
+            TextureKind::Texture2D => 1,
+            TextureKind::CubeMap => 6,
+            _ => {
+                return Err(format!("Cannot render to a {kind:?}"));
+            }
+        };
+        if size == 0 || levels == 0 || (size >> (levels - 1)) == 0 {
+            return Err(format!(
+                "Cannot create a render target of size {size} with {levels} levels"
+            ));
+        }
+        let (internal_format, data_type) = match format {
+            PixelFormat::Rgba8 => (gl::RGBA8, gl::UNSIGNED_BYTE),
+            PixelFormat::RgbaF32 => (gl::RGBA16F, gl::FLOAT),
+        };
+        let target = gl_target(kind);
+        let min_filter = {
+            if levels > 1 {
+                gl::LINEAR_MIPMAP_LINEAR
+            } else {
+                gl::LINEAR
+            }
+        };
+        let mut gl: gl::types::GLuint = 0;
+        unsafe {
+            if kind == TextureKind::CubeMap {
+                gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+            }
+            gl::GenTextures(1, (&mut gl) as *mut gl::types::GLuint);
+            gl::BindTexture(target, gl);
+            for level in 0..levels {
+                let level_size = (size >> level) as i32;
+                for face in 0..faces {
+                    let face_target = {
+                        if kind == TextureKind::CubeMap {
+                            gl::TEXTURE_CUBE_MAP_POSITIVE_X + face
+                        } else {
+                            target
+                        }
+                    };
+                    gl::TexImage2D(
+                        face_target,
+                        level as i32,
+                        internal_format as i32,
+                        level_size,
+                        level_size,
+                        0,
+                        gl::RGBA,
+                        data_type,
+                        std::ptr::null(),
+                    );
+                }
+            }
+            gl::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, (levels - 1) as i32);
+            for wrap in [gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R] {
+                gl::TexParameteri(target, wrap, gl::CLAMP_TO_EDGE as i32);
+            }
+            gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, min_filter as i32);
+            gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::BindTexture(target, 0);
+        }
+        Ok(Self {
+            gl: Rc::new(gl),
+            kind,
+            tracked: None,
+        })
     }
 
     //zz All done
@@ -64,6 +64,14 @@ pub fn get_programiv(id: gl::types::GLuint, x: gl::types::GLuint) -> gl::types::
     }
 }
 
+//fp name_buffer
+/// Create a buffer for the names of the active resources of a
+/// program, sized by a maximum name length query (such as
+/// gl::ACTIVE_UNIFORM_MAX_LENGTH) which includes the terminating nul
+pub fn name_buffer(id: gl::types::GLuint, max_length: gl::types::GLuint) -> Vec<u8> {
+    vec![0; get_programiv(id, max_length).max(1) as usize]
+}
+
 //fp get_shader_error
 /// Assummes an error exists; get its length (using 'f') and then get
 /// the error (using 'e') as a String
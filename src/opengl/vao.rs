@@ -1,6 +1,7 @@
 //a Imports
 use super::utils;
 use super::Model3DOpenGL;
+use crate::TrackedResource;
 
 //a Vao
 //tp Vao
@@ -13,6 +14,12 @@ use super::Model3DOpenGL;
 pub struct Vao {
     gl_vao: u32,
     index_type: gl::types::GLuint,
+    /// Byte offset of the indices in the element array buffer
+    index_byte_offset: usize,
+    /// True if the VAO has an element array buffer
+    indexed: bool,
+    /// The record of the VAO in the resource statistics
+    tracked: Option<TrackedResource>,
 }
 
 //ip Vao
@@ -25,6 +32,42 @@ impl Vao {
         self.index_type
     }
 
+    //ap index_byte_offset
+    /// Get the byte offset of the indices in the element array buffer
+    pub fn index_byte_offset(&self) -> usize {
+        self.index_byte_offset
+    }
+
+    //ap indexed
+    /// Return true if the VAO has an element array buffer
+    pub fn indexed(&self) -> bool {
+        self.indexed
+    }
+
+    //mp set_tracked
+    /// Set the record of the VAO in the resource statistics
+    pub fn set_tracked(&mut self, tracked: TrackedResource) {
+        self.tracked = Some(tracked);
+    }
+
+    //fp create
+    /// Create a VAO with no element array buffer, and leave it bound
+    pub fn create(_context: &Model3DOpenGL) -> Result<Self, ()> {
+        let mut gl_vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut gl_vao);
+            gl::BindVertexArray(gl_vao);
+        }
+        utils::check_errors().expect("Created VAO");
+        Ok(Self {
+            gl_vao,
+            index_type: gl::UNSIGNED_BYTE,
+            index_byte_offset: 0,
+            indexed: false,
+            tracked: None,
+        })
+    }
+
     //fp create_from_indices
     pub fn create_from_indices(
         _context: &Model3DOpenGL,
@@ -45,7 +88,25 @@ impl Vao {
                 _ => gl::UNSIGNED_BYTE,
             }
         };
-        Ok(Self { gl_vao, index_type })
+        let index_byte_offset = indices.byte_offset as usize;
+        Ok(Self {
+            gl_vao,
+            index_type,
+            index_byte_offset,
+            indexed: true,
+            tracked: None,
+        })
+    }
+}
+
+//ip Drop for Vao
+impl Drop for Vao {
+    //fp drop
+    /// Delete the OpenGL VAO
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.gl_vao);
+        }
     }
 }
 
@@ -1,4 +1,6 @@
-use crate::{Gl, GlShaderType, TextureId, UniformId};
+use crate::validation::{check_texture_type, check_uniform_type};
+use crate::{AttributeKind, Gl, GlProgram, GlShaderType, TextureId, UniformId, Vertices};
+use crate::{ProgramValidation, ValidationIssue};
 
 use std::collections::HashMap;
 
@@ -11,6 +13,25 @@ mod deserialize {
     use serde::Deserializer;
     use std::collections::HashMap;
 
+    //fi vertex_attr_of_name
+    /// Get the mod3d_base::VertexAttr with a name
+    fn vertex_attr_of_name(name: &str) -> Option<mod3d_base::VertexAttr> {
+        use mod3d_base::VertexAttr::*;
+        Some(match name {
+            "Position" => Position,
+            "Normal" => Normal,
+            "Color" => Color,
+            "Tangent" => Tangent,
+            "Joints" => Joints,
+            "Weights" => Weights,
+            "TexCoords0" => TexCoords0,
+            "TexCoords1" => TexCoords1,
+            _ => {
+                return None;
+            }
+        })
+    }
+
     //fi map_name_to_attr
     /// Map an array of attribute name/value pairs to a Vec of
     /// tuples of named and mod3d_base::VertexAttr
@@ -23,23 +44,34 @@ mod deserialize {
         let m: HashMap<String, String> = serde::de::Deserialize::deserialize(de)?;
         let mut r = vec![];
         for (k, v) in m.into_iter() {
-            use mod3d_base::VertexAttr::*;
-            let v = match v.as_ref() {
-                "Position" => Position,
-                "Normal" => Normal,
-                "Color" => Color,
-                "Tangent" => Tangent,
-                "Joints" => Joints,
-                "Weights" => Weights,
-                "TexCoords0" => TexCoords0,
-                "TexCoords1" => TexCoords1,
-                _ => {
-                    return Err(serde::de::Error::custom(format!(
-                        "Unknown attribute name {k}"
-                    )));
-                }
+            let Some(v) = vertex_attr_of_name(&v) else {
+                return Err(serde::de::Error::custom(format!(
+                    "Unknown attribute name {k}"
+                )));
             };
-            r.push((k, v.into()));
+            r.push((k, v));
+        }
+        Ok(r)
+    }
+
+    //fi map_attr_to_default
+    /// Map an array of mod3d_base::VertexAttr name/default value
+    /// pairs to a Vec of tuples of VertexAttr and value
+    pub fn map_attr_to_default<'de, D>(
+        de: D,
+    ) -> std::result::Result<Vec<(mod3d_base::VertexAttr, [f32; 4])>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let m: HashMap<String, [f32; 4]> = serde::de::Deserialize::deserialize(de)?;
+        let mut r = vec![];
+        for (k, v) in m.into_iter() {
+            let Some(attr) = vertex_attr_of_name(&k) else {
+                return Err(serde::de::Error::custom(format!(
+                    "Unknown attribute name {k}"
+                )));
+            };
+            r.push((attr, v));
         }
         Ok(r)
     }
@@ -83,12 +115,31 @@ mod deserialize {
 
 #[cfg_attr(feature = "serde", derive(Deserialize))]
 pub struct ShaderProgramDesc {
-    /// The vertex shader path name
+    /// The vertex shader path name (empty for a compute program)
+    #[cfg_attr(feature = "serde", serde(default))]
     vertex_src: String,
 
-    /// The fragment shader path name
+    /// The fragment shader path name (empty for a compute program)
+    #[cfg_attr(feature = "serde", serde(default))]
     fragment_src: String,
 
+    /// The optional geometry shader path name
+    #[cfg_attr(feature = "serde", serde(default))]
+    geometry_src: Option<String>,
+
+    /// The optional tessellation control shader path name
+    #[cfg_attr(feature = "serde", serde(default))]
+    tess_control_src: Option<String>,
+
+    /// The optional tessellation evaluation shader path name
+    #[cfg_attr(feature = "serde", serde(default))]
+    tess_evaluation_src: Option<String>,
+
+    /// The compute shader path name, for a compute program (which
+    /// must then have no other shaders)
+    #[cfg_attr(feature = "serde", serde(default))]
+    compute_src: Option<String>,
+
     /// The map from shader attribute names to the mod3d_base names
     #[cfg_attr(
         feature = "serde",
@@ -106,25 +157,136 @@ pub struct ShaderProgramDesc {
     /// The map from shader uniform names to the UniformId names
     uniform_buffer_map: HashMap<String, usize>,
 
+    /// The map from shader storage block names to storage buffer ids
+    /// (OpenGL 4.3 or later only)
+    #[cfg_attr(feature = "serde", serde(default))]
+    storage_buffer_map: HashMap<String, usize>,
+
     /// The map from shader uniform names to the UniformId names
     #[cfg_attr(
         feature = "serde",
         serde(deserialize_with = "deserialize::map_name_to_texture_unit")
     )]
     texture_map: Vec<(String, TextureId, usize)>,
+
+    /// Default values of vertex attributes, used for attributes that
+    /// the program has but a mesh does not (e.g. a white Color)
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, deserialize_with = "deserialize::map_attr_to_default")
+    )]
+    attribute_defaults: Vec<(mod3d_base::VertexAttr, [f32; 4])>,
+
+    /// Names of vertex shader outputs to capture with transform
+    /// feedback, interleaved in the order given
+    #[cfg_attr(feature = "serde", serde(default))]
+    feedback_varyings: Vec<String>,
 }
 
 impl ShaderProgramDesc {
+    //ap vertex_src
+    /// The path name of the vertex shader source
+    pub fn vertex_src(&self) -> &str {
+        &self.vertex_src
+    }
+
+    //ap fragment_src
+    /// The path name of the fragment shader source
+    pub fn fragment_src(&self) -> &str {
+        &self.fragment_src
+    }
+
+    //mp stages
+    /// The shader type and path name of each shader of the program,
+    /// in pipeline order
+    pub fn stages(&self) -> Result<Vec<(GlShaderType, &str)>, String> {
+        if let Some(compute_src) = &self.compute_src {
+            if !self.vertex_src.is_empty()
+                || !self.fragment_src.is_empty()
+                || self.geometry_src.is_some()
+                || self.tess_control_src.is_some()
+                || self.tess_evaluation_src.is_some()
+            {
+                return Err("A compute program must have no other shaders".into());
+            }
+            return Ok(vec![(GlShaderType::Compute, compute_src.as_str())]);
+        }
+        if self.vertex_src.is_empty() || self.fragment_src.is_empty() {
+            return Err("A program requires both a vertex and a fragment shader".into());
+        }
+        if self.tess_control_src.is_some() && self.tess_evaluation_src.is_none() {
+            return Err("A tessellation control shader requires an evaluation shader".into());
+        }
+        let mut stages = vec![(GlShaderType::Vertex, self.vertex_src.as_str())];
+        if let Some(src) = &self.tess_control_src {
+            stages.push((GlShaderType::TessControl, src.as_str()));
+        }
+        if let Some(src) = &self.tess_evaluation_src {
+            stages.push((GlShaderType::TessEvaluation, src.as_str()));
+        }
+        if let Some(src) = &self.geometry_src {
+            stages.push((GlShaderType::Geometry, src.as_str()));
+        }
+        stages.push((GlShaderType::Fragment, self.fragment_src.as_str()));
+        Ok(stages)
+    }
+
+    //ap attribute_map
+    /// The map from shader attribute names to the mod3d_base names
+    pub fn attribute_map(&self) -> &[(String, mod3d_base::VertexAttr)] {
+        &self.attribute_map
+    }
+
+    //ap uniform_map
+    /// The map from shader uniform names to the UniformId names
+    pub fn uniform_map(&self) -> &[(String, UniformId)] {
+        &self.uniform_map
+    }
+
+    //ap uniform_buffer_map
+    /// The map from shader uniform block names to uniform buffer ids
+    pub fn uniform_buffer_map(&self) -> &HashMap<String, usize> {
+        &self.uniform_buffer_map
+    }
+
+    //ap storage_buffer_map
+    /// The map from shader storage block names to storage buffer ids
+    pub fn storage_buffer_map(&self) -> &HashMap<String, usize> {
+        &self.storage_buffer_map
+    }
+
+    //ap texture_map
+    /// The map from shader sampler names to TextureId and texture unit
+    pub fn texture_map(&self) -> &[(String, TextureId, usize)] {
+        &self.texture_map
+    }
+
+    //ap attribute_defaults
+    /// The default values of vertex attributes that a mesh may lack
+    pub fn attribute_defaults(&self) -> &[(mod3d_base::VertexAttr, [f32; 4])] {
+        &self.attribute_defaults
+    }
+
+    //ap feedback_varyings
+    /// The names of the outputs captured with transform feedback
+    pub fn feedback_varyings(&self) -> &[String] {
+        &self.feedback_varyings
+    }
+
+    //mp compile
+    /// Read the shader sources, compile them, and link them into a
+    /// program with the attributes, uniforms and textures mapped
     pub fn compile<F, G>(&self, gl: &G, read_src: &F) -> Result<<G as Gl>::Program, String>
     where
         F: Fn(&str) -> Result<String, String>,
         G: Gl,
     {
-        let frag_src = read_src(&self.fragment_src)?;
-        let vert_src = read_src(&self.vertex_src)?;
-
-        let frag_shader = gl.compile_shader(GlShaderType::Fragment, &frag_src)?;
-        let vert_shader = gl.compile_shader(GlShaderType::Vertex, &vert_src)?;
+        let mut srcs = Vec::new();
+        for (shader_type, path) in self.stages()? {
+            srcs.push((shader_type, read_src(path)?));
+        }
+        let srcs: Vec<(GlShaderType, &str)> =
+            srcs.iter().map(|(t, s)| (*t, s.as_str())).collect();
 
         let named_attrs: Vec<(&str, mod3d_base::VertexAttr)> = self
             .attribute_map
@@ -141,18 +303,167 @@ impl ShaderProgramDesc {
             .iter()
             .map(|(s, a)| (s.as_str(), *a))
             .collect();
+        let named_storage_buffers: Vec<(&str, usize)> = self
+            .storage_buffer_map
+            .iter()
+            .map(|(s, a)| (s.as_str(), *a))
+            .collect();
         let named_textures: Vec<(&str, TextureId, usize)> = self
             .texture_map
             .iter()
             .map(|(s, t, u)| (s.as_str(), *t, *u))
             .collect();
-        let program = gl.link_program(
-            &[&vert_shader, &frag_shader],
+        let feedback_varyings: Vec<&str> =
+            self.feedback_varyings.iter().map(|s| s.as_str()).collect();
+        let mut program = gl.compile_program(
+            &srcs,
             &named_attrs,
             &named_uniforms,
             &named_uniform_buffers,
+            &named_storage_buffers,
             &named_textures,
+            &feedback_varyings,
         )?;
+        program.set_attribute_defaults(&self.attribute_defaults);
         Ok(program)
     }
+
+    //mp validate
+    /// Validate a linked program against this description, using the
+    /// program reflection from the GL
+    ///
+    /// Active attributes, uniforms and uniform blocks that are not
+    /// mapped are reported as warnings; uniforms whose type does not
+    /// match that required by their [UniformId] are errors
+    pub fn validate<G>(&self, gl: &G, program: &<G as Gl>::Program) -> ProgramValidation
+    where
+        G: Gl,
+    {
+        let reflection = gl.program_reflection(program);
+        let mut validation = ProgramValidation::default();
+        for a in &reflection.attributes {
+            if a.is_builtin() {
+                continue;
+            }
+            if !self.attribute_map.iter().any(|(s, _)| *s == a.name) {
+                validation.warn(ValidationIssue::UnmappedAttribute(a.name.clone()));
+            }
+        }
+        for u in &reflection.uniforms {
+            if u.is_builtin() {
+                continue;
+            }
+            if let Some((_, uniform_id)) = self.uniform_map.iter().find(|(s, _)| *s == u.name) {
+                check_uniform_type(&mut validation, &u.name, *uniform_id, u.glsl_type);
+            } else if let Some((_, texture_id, _)) =
+                self.texture_map.iter().find(|(s, _, _)| *s == u.name)
+            {
+                check_texture_type(&mut validation, &u.name, *texture_id, u.glsl_type);
+            } else {
+                validation.warn(ValidationIssue::UnmappedUniform(u.name.clone()));
+            }
+        }
+        for b in &reflection.uniform_blocks {
+            if !self.uniform_buffer_map.contains_key(b) {
+                validation.warn(ValidationIssue::UnmappedUniformBlock(b.clone()));
+            }
+        }
+        validation
+    }
+
+    //mp validate_vertices
+    /// Validate a linked program against the vertices of a mesh,
+    /// checking that each mapped attribute matches the data of the
+    /// mesh's [crate::VertexBuffer]
+    ///
+    /// Data that cannot be converted to the attribute type (integer
+    /// data for a float attribute, or float data for an integer
+    /// attribute) is an error. A component count that differs is a
+    /// warning: the GL drops extra components, and fills missing ones
+    /// from (0, 0, 0, 1). An attribute that the mesh does not have at
+    /// all is a warning, noting the default value (if any) used for it
+    pub fn validate_vertices<G>(
+        &self,
+        gl: &G,
+        program: &<G as Gl>::Program,
+        vertices: &Vertices<G>,
+    ) -> ProgramValidation
+    where
+        G: Gl,
+    {
+        let reflection = gl.program_reflection(program);
+        let (_, position, attrs) = vertices.borrow();
+        let mut validation = ProgramValidation::default();
+        for (name, vertex_attr) in &self.attribute_map {
+            let Some(a) = reflection.find_attribute(name) else {
+                continue;
+            };
+            let mesh = {
+                if *vertex_attr == mod3d_base::VertexAttr::Position {
+                    Some(position)
+                } else {
+                    attrs
+                        .iter()
+                        .find(|(va, _)| *va == *vertex_attr)
+                        .map(|(_, b)| b)
+                }
+            };
+            let Some(mesh) = mesh else {
+                let default = self
+                    .attribute_defaults
+                    .iter()
+                    .find(|(va, _)| *va == *vertex_attr)
+                    .map(|(_, d)| *d);
+                validation.warn(ValidationIssue::MissingAttribute {
+                    name: name.clone(),
+                    vertex_attr: *vertex_attr,
+                    default,
+                });
+                continue;
+            };
+            let integer_data = mesh.format.kind == AttributeKind::Integer;
+            if a.glsl_type.is_integer() != integer_data {
+                validation.error(ValidationIssue::AttributeTypeMismatch {
+                    name: name.clone(),
+                    vertex_attr: *vertex_attr,
+                    shader: a.glsl_type,
+                    mesh: mesh.format.kind,
+                });
+                continue;
+            }
+            let shader = a.glsl_type.components();
+            if shader != mesh.elements_per_data {
+                validation.warn(ValidationIssue::AttributeComponentMismatch {
+                    name: name.clone(),
+                    vertex_attr: *vertex_attr,
+                    shader,
+                    mesh: mesh.elements_per_data,
+                });
+            }
+        }
+        validation
+    }
+
+    //mp compile_validated
+    /// Compile and link the program as [Self::compile], then validate
+    /// it; if the validation has errors then fail with them
+    /// described, else return the program and the validation (which
+    /// may contain warnings)
+    pub fn compile_validated<F, G>(
+        &self,
+        gl: &G,
+        read_src: &F,
+    ) -> Result<(<G as Gl>::Program, ProgramValidation), String>
+    where
+        F: Fn(&str) -> Result<String, String>,
+        G: Gl,
+    {
+        let program = self.compile(gl, read_src)?;
+        let validation = self.validate(gl, &program);
+        if validation.is_ok() {
+            Ok((program, validation))
+        } else {
+            Err(format!("Program failed validation:\n{validation}"))
+        }
+    }
 }
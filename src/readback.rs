@@ -0,0 +1,246 @@
+//a Documentation
+
+/*!
+
+Reading back GPU buffer, texture and framebuffer contents
+
+The synchronous reads (such as [Pixels::of_texture] and
+[crate::VertexBuffer::read]) wait for the GPU to complete all the
+commands that write the data; this is simple, and suitable for tests
+and analysis tools, but stalls the pipeline.
+
+A [PendingRead] instead copies the data on the GPU in to a staging
+buffer (a pixel pack buffer for pixels) and inserts a fence; the CPU
+can poll [PendingRead::is_ready] (e.g. once per frame), and then
+[PendingRead::complete] the read without stalling.
+
+!*/
+
+//a Imports
+use crate::{BufferUsage, Gl, PixelFormat};
+
+//a Pixels
+//tp Pixels
+/// A rectangle of pixels read back from the GPU, with rows bottom-up
+#[derive(Debug, Clone, Default)]
+pub struct Pixels {
+    /// Width in pixels
+    pub width: usize,
+    /// Height in pixels
+    pub height: usize,
+    /// Format of the data
+    pub format: PixelFormat,
+    /// The pixel data, width * height * format.bytes_per_pixel() bytes
+    pub data: Vec<u8>,
+}
+
+//ip Pixels
+impl Pixels {
+    //fp new
+    /// Create zeroed pixels of a size and format
+    pub fn new(width: usize, height: usize, format: PixelFormat) -> Self {
+        let data = vec![0; width * height * format.bytes_per_pixel()];
+        Self {
+            width,
+            height,
+            format,
+            data,
+        }
+    }
+
+    //fp of_texture
+    /// Read a level of a texture, waiting for the GPU
+    pub fn of_texture<G: Gl>(
+        context: &mut G,
+        texture: &<G as Gl>::Texture,
+        level: usize,
+        format: PixelFormat,
+    ) -> Result<Self, String> {
+        let (width, height) = context.texture_size(texture, level);
+        let mut pixels = Self::new(width, height, format);
+        context.texture_read(texture, level, format, &mut pixels.data)?;
+        Ok(pixels)
+    }
+
+    //fp of_framebuffer
+    /// Read a rectangle of the bound (read) framebuffer, waiting for the GPU
+    pub fn of_framebuffer<G: Gl>(
+        context: &mut G,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        format: PixelFormat,
+    ) -> Result<Self, String> {
+        let mut pixels = Self::new(width, height, format);
+        context.read_pixels(x, y, width, height, format, &mut pixels.data)?;
+        Ok(pixels)
+    }
+
+    //ap row_byte_size
+    /// Get the byte size of a row of pixels
+    pub fn row_byte_size(&self) -> usize {
+        self.width * self.format.bytes_per_pixel()
+    }
+
+    //ap pixel
+    /// Get the bytes of the pixel at (x, y) (y from the bottom)
+    pub fn pixel(&self, x: usize, y: usize) -> &[u8] {
+        let bpp = self.format.bytes_per_pixel();
+        let start = y * self.row_byte_size() + x * bpp;
+        &self.data[start..start + bpp]
+    }
+}
+
+//a PendingRead
+//tp PendingRead
+/// An asynchronous read of GPU data in to a staging buffer, that may
+/// be completed without stalling once its fence is signalled
+pub struct PendingRead<G>
+where
+    G: Gl,
+{
+    /// The staging buffer the data is copied to
+    staging: <G as Gl>::Buffer,
+    /// Byte length of the data
+    byte_length: usize,
+    /// Size and format if the data is pixels
+    pixels: Option<(usize, usize, PixelFormat)>,
+    /// The fence after the copy, if the backend supports fences
+    fence: Option<G::Fence>,
+}
+
+//ip PendingRead
+impl<G> PendingRead<G>
+where
+    G: Gl,
+{
+    //fi of_staging
+    fn of_staging(
+        context: &mut G,
+        staging: <G as Gl>::Buffer,
+        byte_length: usize,
+        pixels: Option<(usize, usize, PixelFormat)>,
+    ) -> Self {
+        let fence = context.fence_insert();
+        Self {
+            staging,
+            byte_length,
+            pixels,
+            fence,
+        }
+    }
+
+    //fp of_buffer
+    /// Start reading a byte range of a GPU buffer
+    pub fn of_buffer(
+        context: &mut G,
+        buffer: &<G as Gl>::Buffer,
+        byte_offset: usize,
+        byte_length: usize,
+    ) -> Self {
+        let staging = context.buffer_create(byte_length, BufferUsage::Stream);
+        context.buffer_copy(buffer, byte_offset, &staging, 0, byte_length);
+        Self::of_staging(context, staging, byte_length, None)
+    }
+
+    //fp of_texture
+    /// Start reading a level of a texture
+    pub fn of_texture(
+        context: &mut G,
+        texture: &<G as Gl>::Texture,
+        level: usize,
+        format: PixelFormat,
+    ) -> Result<Self, String> {
+        let (width, height) = context.texture_size(texture, level);
+        let byte_length = width * height * format.bytes_per_pixel();
+        let staging = context.buffer_create(byte_length, BufferUsage::Stream);
+        context.texture_read_to_buffer(texture, level, format, &staging)?;
+        Ok(Self::of_staging(
+            context,
+            staging,
+            byte_length,
+            Some((width, height, format)),
+        ))
+    }
+
+    //fp of_framebuffer
+    /// Start reading a rectangle of the bound (read) framebuffer
+    pub fn of_framebuffer(
+        context: &mut G,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        format: PixelFormat,
+    ) -> Result<Self, String> {
+        let byte_length = width * height * format.bytes_per_pixel();
+        let staging = context.buffer_create(byte_length, BufferUsage::Stream);
+        context.read_pixels_to_buffer(x, y, width, height, format, &staging)?;
+        Ok(Self::of_staging(
+            context,
+            staging,
+            byte_length,
+            Some((width, height, format)),
+        ))
+    }
+
+    //ap byte_length
+    /// Get the byte length of the data being read
+    pub fn byte_length(&self) -> usize {
+        self.byte_length
+    }
+
+    //mp is_ready
+    /// Return true if the data can be read without waiting for the GPU
+    ///
+    /// If the backend does not support fences this is always true
+    pub fn is_ready(&self, context: &mut G) -> bool {
+        self.fence
+            .as_ref()
+            .map(|fence| context.fence_is_signalled(fence))
+            .unwrap_or(true)
+    }
+
+    //mp complete
+    /// Complete the read, waiting for the GPU if it is not yet ready
+    pub fn complete(self, context: &mut G) -> Result<Vec<u8>, String> {
+        if let Some(fence) = self.fence {
+            context.fence_wait(fence)?;
+        }
+        let mut data = vec![0; self.byte_length];
+        context.buffer_read_data(&self.staging, 0, &mut data)?;
+        Ok(data)
+    }
+
+    //mp complete_pixels
+    /// Complete a read of a texture or framebuffer, waiting for the
+    /// GPU if it is not yet ready
+    pub fn complete_pixels(self, context: &mut G) -> Result<Pixels, String> {
+        let Some((width, height, format)) = self.pixels else {
+            return Err("Pending read is not of pixels".into());
+        };
+        let data = self.complete(context)?;
+        Ok(Pixels {
+            width,
+            height,
+            format,
+            data,
+        })
+    }
+}
+
+//ip Debug for PendingRead
+impl<G> std::fmt::Debug for PendingRead<G>
+where
+    G: Gl,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        f.debug_struct("PendingRead")
+            .field("staging", &self.staging)
+            .field("byte_length", &self.byte_length)
+            .field("pixels", &self.pixels)
+            .field("fenced", &self.fence.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,295 @@
+//a Documentation
+
+/*!
+
+Backend-neutral reflection of a linked shader program
+
+After linking, each backend can report the active attributes,
+uniforms and uniform blocks of a program; this allows a
+[crate::ShaderProgramDesc] to be cross-checked against what the
+shader compiler actually produced.
+
+!*/
+
+//a GlslType
+//tp GlslType
+/// The GLSL type of an active attribute or uniform, as reported by
+/// the GL program introspection
+///
+/// The GL enumeration values are the same for OpenGL and WebGL2
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GlslType {
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    Int,
+    IVec2,
+    IVec3,
+    IVec4,
+    UInt,
+    UVec2,
+    UVec3,
+    UVec4,
+    Bool,
+    BVec2,
+    BVec3,
+    BVec4,
+    Mat2,
+    Mat3,
+    Mat4,
+    Sampler2D,
+    Sampler3D,
+    SamplerCube,
+    Sampler2DShadow,
+    Sampler2DArray,
+    /// Any other GL type, with its GL enumeration value
+    Other(u32),
+}
+
+//ip GlslType
+impl GlslType {
+    //fp of_gl_type
+    /// Map a GL type enumeration (e.g. GL_FLOAT_VEC4) to a [GlslType]
+    pub fn of_gl_type(gl_type: u32) -> Self {
+        use GlslType::*;
+        match gl_type {
+            0x1406 => Float,
+            0x8b50 => Vec2,
+            0x8b51 => Vec3,
+            0x8b52 => Vec4,
+            0x1404 => Int,
+            0x8b53 => IVec2,
+            0x8b54 => IVec3,
+            0x8b55 => IVec4,
+            0x1405 => UInt,
+            0x8dc6 => UVec2,
+            0x8dc7 => UVec3,
+            0x8dc8 => UVec4,
+            0x8b56 => Bool,
+            0x8b57 => BVec2,
+            0x8b58 => BVec3,
+            0x8b59 => BVec4,
+            0x8b5a => Mat2,
+            0x8b5b => Mat3,
+            0x8b5c => Mat4,
+            0x8b5e => Sampler2D,
+            0x8b5f => Sampler3D,
+            0x8b60 => SamplerCube,
+            0x8b62 => Sampler2DShadow,
+            0x8dc1 => Sampler2DArray,
+            x => Other(x),
+        }
+    }
+
+    //mp components
+    /// Number of scalar components in the type (e.g. 3 for vec3, 16 for mat4)
+    ///
+    /// Samplers and unknown types have zero components
+    pub fn components(&self) -> u32 {
+        use GlslType::*;
+        match self {
+            Float | Int | UInt | Bool => 1,
+            Vec2 | IVec2 | UVec2 | BVec2 => 2,
+            Vec3 | IVec3 | UVec3 | BVec3 => 3,
+            Vec4 | IVec4 | UVec4 | BVec4 | Mat2 => 4,
+            Mat3 => 9,
+            Mat4 => 16,
+            _ => 0,
+        }
+    }
+
+    //mp is_integer
+    /// Return true if the type is a signed or unsigned integer scalar
+    /// or vector
+    pub fn is_integer(&self) -> bool {
+        use GlslType::*;
+        matches!(
+            self,
+            Int | IVec2 | IVec3 | IVec4 | UInt | UVec2 | UVec3 | UVec4
+        )
+    }
+
+    //mp is_sampler
+    /// Return true if the type is a texture sampler
+    pub fn is_sampler(&self) -> bool {
+        use GlslType::*;
+        matches!(
+            self,
+            Sampler2D | Sampler3D | SamplerCube | Sampler2DShadow | Sampler2DArray
+        )
+    }
+}
+
+//ip Display for GlslType
+impl std::fmt::Display for GlslType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        use GlslType::*;
+        let s = match self {
+            Float => "float",
+            Vec2 => "vec2",
+            Vec3 => "vec3",
+            Vec4 => "vec4",
+            Int => "int",
+            IVec2 => "ivec2",
+            IVec3 => "ivec3",
+            IVec4 => "ivec4",
+            UInt => "uint",
+            UVec2 => "uvec2",
+            UVec3 => "uvec3",
+            UVec4 => "uvec4",
+            Bool => "bool",
+            BVec2 => "bvec2",
+            BVec3 => "bvec3",
+            BVec4 => "bvec4",
+            Mat2 => "mat2",
+            Mat3 => "mat3",
+            Mat4 => "mat4",
+            Sampler2D => "sampler2D",
+            Sampler3D => "sampler3D",
+            SamplerCube => "samplerCube",
+            Sampler2DShadow => "sampler2DShadow",
+            Sampler2DArray => "sampler2DArray",
+            Other(x) => {
+                return write!(f, "gl_type({x:#x})");
+            }
+        };
+        f.write_str(s)
+    }
+}
+
+//a ActiveVariable
+//tp ActiveVariable
+/// An active attribute or uniform of a linked program
+#[derive(Debug, Clone)]
+pub struct ActiveVariable {
+    /// The name of the variable, with any trailing '[0]' removed for arrays
+    pub name: String,
+    /// The type of the variable (of each element, for arrays)
+    pub glsl_type: GlslType,
+    /// The array size of the variable (1 if it is not an array)
+    pub array_size: usize,
+}
+
+//ip ActiveVariable
+impl ActiveVariable {
+    //fp new
+    /// Create a new [ActiveVariable] from the name, GL type and size
+    /// reported by the GL
+    pub fn new(name: &str, gl_type: u32, array_size: usize) -> Self {
+        let name = name.strip_suffix("[0]").unwrap_or(name).to_string();
+        let glsl_type = GlslType::of_gl_type(gl_type);
+        Self {
+            name,
+            glsl_type,
+            array_size,
+        }
+    }
+
+    //mp is_builtin
+    /// Return true if the variable is a GLSL builtin (such as gl_VertexID)
+    pub fn is_builtin(&self) -> bool {
+        self.name.starts_with("gl_")
+    }
+}
+
+//a ProgramReflection
+//tp ProgramReflection
+/// The active attributes, uniforms (excluding those in uniform
+/// blocks) and uniform blocks of a linked program
+#[derive(Debug, Clone, Default)]
+pub struct ProgramReflection {
+    /// Active vertex attributes
+    pub attributes: Vec<ActiveVariable>,
+    /// Active uniforms that are not members of a uniform block
+    pub uniforms: Vec<ActiveVariable>,
+    /// Names of the active uniform blocks
+    pub uniform_blocks: Vec<String>,
+}
+
+//ip ProgramReflection
+impl ProgramReflection {
+    //mp find_attribute
+    /// Find an active attribute by name
+    pub fn find_attribute(&self, name: &str) -> Option<&ActiveVariable> {
+        self.attributes.iter().find(|a| a.name == name)
+    }
+
+    //mp find_uniform
+    /// Find an active uniform by name
+    pub fn find_uniform(&self, name: &str) -> Option<&ActiveVariable> {
+        self.uniforms.iter().find(|u| u.name == name)
+    }
+
+    //mp has_uniform_block
+    /// Return true if the program has an active uniform block of the given name
+    pub fn has_uniform_block(&self, name: &str) -> bool {
+        self.uniform_blocks.iter().any(|b| b == name)
+    }
+}
+
+//a Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn of_gl_type() {
+        use GlslType::*;
+        for (gl_type, glsl_type, name) in [
+            (0x1406, Float, "float"),
+            (0x8b52, Vec4, "vec4"),
+            (0x1404, Int, "int"),
+            (0x8b54, IVec3, "ivec3"),
+            (0x1405, UInt, "uint"),
+            (0x8dc6, UVec2, "uvec2"),
+            (0x8b56, Bool, "bool"),
+            (0x8b5b, Mat3, "mat3"),
+            (0x8b5c, Mat4, "mat4"),
+            (0x8b5e, Sampler2D, "sampler2D"),
+            (0x8b60, SamplerCube, "samplerCube"),
+            (0x8dc1, Sampler2DArray, "sampler2DArray"),
+        ] {
+            assert_eq!(GlslType::of_gl_type(gl_type), glsl_type);
+            assert_eq!(glsl_type.to_string(), name);
+        }
+        assert_eq!(GlslType::of_gl_type(0x1234), Other(0x1234));
+        assert_eq!(Other(0x1234).to_string(), "gl_type(0x1234)");
+    }
+
+    #[test]
+    fn type_properties() {
+        use GlslType::*;
+        assert_eq!(Float.components(), 1);
+        assert_eq!(BVec3.components(), 3);
+        assert_eq!(Mat2.components(), 4);
+        assert_eq!(Mat4.components(), 16);
+        assert_eq!(Sampler2D.components(), 0);
+        assert!(IVec4.is_integer() && UInt.is_integer());
+        assert!(!Vec4.is_integer() && !Bool.is_integer());
+        assert!(Sampler2DShadow.is_sampler() && !Mat4.is_sampler());
+    }
+
+    #[test]
+    fn active_variables() {
+        let mut reflection = ProgramReflection::default();
+        reflection
+            .attributes
+            .push(ActiveVariable::new("gl_VertexID", 0x1404, 1));
+        reflection
+            .uniforms
+            .push(ActiveVariable::new("uBones[0]", 0x8b5c, 16));
+        reflection.uniform_blocks.push("Material".into());
+
+        let bones = reflection.find_uniform("uBones").unwrap();
+        assert_eq!(bones.glsl_type, GlslType::Mat4);
+        assert_eq!(bones.array_size, 16);
+        assert!(reflection.find_uniform("uBones[0]").is_none());
+        assert!(reflection
+            .find_attribute("gl_VertexID")
+            .unwrap()
+            .is_builtin());
+        assert!(reflection.has_uniform_block("Material"));
+        assert!(!reflection.has_uniform_block("Lights"));
+    }
+}
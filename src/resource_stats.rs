@@ -0,0 +1,309 @@
+//a Documentation
+
+/*!
+
+Accounting of the GPU resources created by a backend
+
+Each backend has a [ResourceTracker]; every buffer, texture, VAO and
+program that it creates holds a [TrackedResource] of the appropriate
+[ResourceKind] and byte size. The counts and byte sizes are added to
+the [ResourceStats] when the resource is created (or its storage
+respecified), and removed when the last reference to the resource is
+dropped (which is when the backend frees the GL object).
+
+The byte sizes are those of the data given to the GL, not of the
+driver's actual allocations; VAOs and programs are counted with a
+byte size of zero.
+
+!*/
+
+//a Imports
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+
+//a ResourceKind
+//tp ResourceKind
+/// The category of a GPU resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    /// A buffer of vertex data
+    VertexBuffer,
+    /// A buffer of indices
+    IndexBuffer,
+    /// A uniform buffer
+    UniformBuffer,
+    /// Any other buffer - buffer pool blocks, storage, indirect,
+    /// feedback and readback staging buffers
+    Buffer,
+    /// A texture
+    Texture,
+    /// A vertex array object
+    Vao,
+    /// A linked shader program
+    Program,
+}
+
+//ip ResourceKind
+impl ResourceKind {
+    /// All of the kinds, in the order used by [ResourceStats]
+    pub const ALL: [Self; 7] = [
+        Self::VertexBuffer,
+        Self::IndexBuffer,
+        Self::UniformBuffer,
+        Self::Buffer,
+        Self::Texture,
+        Self::Vao,
+        Self::Program,
+    ];
+
+    //ai index
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    //ap is_buffer
+    /// Return true if the kind is a buffer of some sort
+    pub fn is_buffer(self) -> bool {
+        matches!(
+            self,
+            Self::VertexBuffer | Self::IndexBuffer | Self::UniformBuffer | Self::Buffer
+        )
+    }
+}
+
+//ip Display for ResourceKind
+impl std::fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let name = match self {
+            Self::VertexBuffer => "vertex",
+            Self::IndexBuffer => "index",
+            Self::UniformBuffer => "uniform",
+            Self::Buffer => "buffer",
+            Self::Texture => "texture",
+            Self::Vao => "vao",
+            Self::Program => "program",
+        };
+        f.write_str(name)
+    }
+}
+
+//a ResourceUsage
+//tp ResourceUsage
+/// The number and byte size of live resources of a kind (or of all
+/// kinds), and the peaks of each since creation or the last
+/// [ResourceTracker::reset_peaks]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Number of live resources
+    pub count: usize,
+    /// Total byte size of the live resources
+    pub bytes: usize,
+    /// Peak number of live resources
+    pub peak_count: usize,
+    /// Peak total byte size of the live resources
+    pub peak_bytes: usize,
+    /// Number of resources created
+    pub created: usize,
+    /// Number of resources freed
+    pub freed: usize,
+}
+
+//ip ResourceUsage
+impl ResourceUsage {
+    //mi add
+    fn add(&mut self, bytes: usize) {
+        self.count += 1;
+        self.created += 1;
+        self.peak_count = self.peak_count.max(self.count);
+        self.resize(0, bytes);
+    }
+
+    //mi remove
+    fn remove(&mut self, bytes: usize) {
+        self.count -= 1;
+        self.freed += 1;
+        self.bytes -= bytes;
+    }
+
+    //mi resize
+    fn resize(&mut self, old_bytes: usize, new_bytes: usize) {
+        self.bytes = self.bytes - old_bytes + new_bytes;
+        self.peak_bytes = self.peak_bytes.max(self.bytes);
+    }
+
+    //mi reset_peaks
+    fn reset_peaks(&mut self) {
+        self.peak_count = self.count;
+        self.peak_bytes = self.bytes;
+    }
+}
+
+//a ResourceStats
+//tp ResourceStats
+/// A snapshot of the GPU resources of a backend, by kind and in total
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceStats {
+    /// Usage of each [ResourceKind], in the order of [ResourceKind::ALL]
+    by_kind: [ResourceUsage; 7],
+    /// Usage of all kinds; its peaks are of the sums, not the sums of peaks
+    total: ResourceUsage,
+}
+
+//ip ResourceStats
+impl ResourceStats {
+    //ap usage
+    /// Get the usage of a kind of resource
+    pub fn usage(&self, kind: ResourceKind) -> &ResourceUsage {
+        &self.by_kind[kind.index()]
+    }
+
+    //ap total
+    /// Get the usage of all resources
+    pub fn total(&self) -> &ResourceUsage {
+        &self.total
+    }
+
+    //ap buffer_bytes
+    /// Get the total byte size of all the live buffers
+    pub fn buffer_bytes(&self) -> usize {
+        ResourceKind::ALL
+            .iter()
+            .filter(|k| k.is_buffer())
+            .map(|k| self.usage(*k).bytes)
+            .sum()
+    }
+
+    //ap texture_bytes
+    /// Get the total byte size of all the live textures
+    pub fn texture_bytes(&self) -> usize {
+        self.usage(ResourceKind::Texture).bytes
+    }
+
+    //mi add
+    fn add(&mut self, kind: ResourceKind, bytes: usize) {
+        self.by_kind[kind.index()].add(bytes);
+        self.total.add(bytes);
+    }
+
+    //mi remove
+    fn remove(&mut self, kind: ResourceKind, bytes: usize) {
+        self.by_kind[kind.index()].remove(bytes);
+        self.total.remove(bytes);
+    }
+
+    //mi resize
+    fn resize(&mut self, kind: ResourceKind, old_bytes: usize, new_bytes: usize) {
+        self.by_kind[kind.index()].resize(old_bytes, new_bytes);
+        self.total.resize(old_bytes, new_bytes);
+    }
+}
+
+//ip Display for ResourceStats
+impl std::fmt::Display for ResourceStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        for kind in ResourceKind::ALL {
+            let u = self.usage(kind);
+            writeln!(
+                f,
+                "{kind:>8}: {:6} ({:10} bytes) peak {:6} ({:10} bytes)",
+                u.count, u.bytes, u.peak_count, u.peak_bytes
+            )?;
+        }
+        let u = &self.total;
+        write!(
+            f,
+            "{:>8}: {:6} ({:10} bytes) peak {:6} ({:10} bytes)",
+            "total", u.count, u.bytes, u.peak_count, u.peak_bytes
+        )
+    }
+}
+
+//a TrackedResource
+//tp TrackedResource
+/// The record of a GPU resource in a [ResourceTracker]; when dropped
+/// the resource is removed from the statistics (if the tracker still
+/// exists)
+#[derive(Debug)]
+pub struct TrackedResource {
+    /// The statistics of the tracker
+    stats: Weak<RefCell<ResourceStats>>,
+    /// The kind of the resource
+    kind: ResourceKind,
+    /// The current byte size of the resource
+    bytes: Cell<usize>,
+}
+
+//ip TrackedResource
+impl TrackedResource {
+    //ap kind
+    /// Get the kind of the resource
+    pub fn kind(&self) -> ResourceKind {
+        self.kind
+    }
+
+    //ap bytes
+    /// Get the byte size of the resource
+    pub fn bytes(&self) -> usize {
+        self.bytes.get()
+    }
+
+    //mp resize
+    /// Record that the storage of the resource has been respecified
+    /// with a new byte size
+    pub fn resize(&self, bytes: usize) {
+        if let Some(stats) = self.stats.upgrade() {
+            stats
+                .borrow_mut()
+                .resize(self.kind, self.bytes.get(), bytes);
+        }
+        self.bytes.set(bytes);
+    }
+}
+
+//ip Drop for TrackedResource
+impl Drop for TrackedResource {
+    fn drop(&mut self) {
+        if let Some(stats) = self.stats.upgrade() {
+            stats.borrow_mut().remove(self.kind, self.bytes.get());
+        }
+    }
+}
+
+//a ResourceTracker
+//tp ResourceTracker
+/// The statistics of the GPU resources created by a backend
+#[derive(Debug, Default)]
+pub struct ResourceTracker {
+    stats: Rc<RefCell<ResourceStats>>,
+}
+
+//ip ResourceTracker
+impl ResourceTracker {
+    //mp track
+    /// Record the creation of a resource of a kind and byte size; it
+    /// is recorded as freed when the returned value is dropped
+    pub fn track(&self, kind: ResourceKind, bytes: usize) -> TrackedResource {
+        self.stats.borrow_mut().add(kind, bytes);
+        TrackedResource {
+            stats: Rc::downgrade(&self.stats),
+            kind,
+            bytes: Cell::new(bytes),
+        }
+    }
+
+    //ap stats
+    /// Get a snapshot of the statistics
+    pub fn stats(&self) -> ResourceStats {
+        *self.stats.borrow()
+    }
+
+    //mp reset_peaks
+    /// Reset the peak counts and byte sizes to the current values
+    pub fn reset_peaks(&self) {
+        let mut stats = self.stats.borrow_mut();
+        stats.total.reset_peaks();
+        for usage in stats.by_kind.iter_mut() {
+            usage.reset_peaks();
+        }
+    }
+}
@@ -0,0 +1,531 @@
+//a Documentation
+
+/*!
+
+Offline checking of a [ShaderProgramDesc] using naga, without a GL
+context
+
+Each shader source is translated to the dialect of a [GlslTarget]
+(as the backend would with [crate::translate_glsl]) and then parsed
+and validated with the naga GLSL frontend, and every name in the description's
+attribute, uniform, uniform buffer and texture maps is checked to be
+in the interface of the shaders.
+
+The naga GLSL frontend only accepts '#version 440/450/460 core', so
+the version directive is replaced before parsing; the target-specific
+requirements (such as a default float precision for GLSL ES fragment
+shaders) are checked separately.
+
+The naga GLSL frontend also follows the Vulkan GLSL rules, so the
+source is rewritten (keeping its line numbers) before it is parsed:
+
+* each loose (non-block) uniform is wrapped in a uniform block of its
+  own, and is added to the interface by name
+
+* each combined sampler (such as 'sampler2D' or 'samplerCube') is
+  split in to a texture and a sampler, and every other use of its
+  name is replaced by the combined sampler constructor
+
+* every uniform block, storage block, texture and sampler is given
+  an explicit set and binding
+
+* every input and output is given an explicit location
+
+* precision statements (which naga does not need, and which may
+  name sampler types it does not have) are removed
+
+* gl_VertexID and gl_InstanceID are replaced by their Vulkan names
+
+The rewriting requires each uniform, storage, input, output and
+precision declaration to start on a line of its own (uniform and
+storage declarations may continue over following lines); combined
+samplers may only be used as the arguments of built-in functions (not
+passed to user functions, which would need the function parameters
+to be rewritten too), and arrays of combined samplers are not
+supported.
+
+!*/
+
+//a Imports
+use std::collections::HashSet;
+
+use naga::front::glsl::{Frontend, Options};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use naga::ShaderStage;
+
+use crate::{GlShaderType, GlslDialect, ProgramValidation, ShaderProgramDesc, ValidationIssue};
+
+//a GlslTarget
+//tp GlslTarget
+/// The GLSL dialect that a shader is checked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlslTarget {
+    /// Desktop OpenGL, '#version 330 core' or later
+    Desktop,
+    /// WebGL2, '#version 300 es'
+    Es300,
+}
+
+//ip GlslTarget
+impl GlslTarget {
+    //mp dialect
+    /// The dialect that a backend for the target translates sources to
+    pub fn dialect(&self) -> GlslDialect {
+        match self {
+            Self::Desktop => GlslDialect::Desktop(330),
+            Self::Es300 => GlslDialect::Es300,
+        }
+    }
+}
+
+//ip Display for GlslTarget
+impl std::fmt::Display for GlslTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Self::Desktop => write!(f, "desktop"),
+            Self::Es300 => write!(f, "300 es"),
+        }
+    }
+}
+
+//a ShaderInterface
+//tp ShaderInterface
+/// The set of names that a shader exposes: its global variables
+/// (inputs, outputs, uniforms and samplers), its entry point
+/// arguments and its named types (which include uniform blocks)
+#[derive(Debug, Default)]
+pub struct ShaderInterface {
+    names: HashSet<String>,
+}
+
+//ip ShaderInterface
+impl ShaderInterface {
+    //fp of_module
+    fn of_module(module: &naga::Module) -> Self {
+        let mut names = HashSet::new();
+        for (_, gv) in module.global_variables.iter() {
+            if let Some(name) = &gv.name {
+                names.insert(name.clone());
+            }
+        }
+        for (_, ty) in module.types.iter() {
+            if let Some(name) = &ty.name {
+                names.insert(name.clone());
+            }
+        }
+        for ep in &module.entry_points {
+            for arg in &ep.function.arguments {
+                if let Some(name) = &arg.name {
+                    names.insert(name.clone());
+                }
+            }
+        }
+        Self { names }
+    }
+
+    //mp merge
+    fn merge(&mut self, other: ShaderInterface) {
+        self.names.extend(other.names);
+    }
+
+    //mp contains
+    /// Return true if the interface has the given name
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains(name)
+    }
+}
+
+//a Functions
+//fi naga_source
+/// Replace the '#version' directive of a source with one that naga
+/// accepts, checking that it is suitable for the target
+fn naga_source(source: &str, target: GlslTarget) -> Result<String, String> {
+    let mut result = String::with_capacity(source.len() + 32);
+    result.push_str("#version 450 core\n");
+    let mut found_version = false;
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        if let Some(version) = trimmed.strip_prefix("#version") {
+            let version = version.trim();
+            let is_es = version.ends_with("es");
+            match (target, is_es) {
+                (GlslTarget::Es300, false) => {
+                    return Err(format!("'#version {version}' is not a GLSL ES version"));
+                }
+                (GlslTarget::Desktop, true) => {
+                    return Err(format!(
+                        "'#version {version}' is not a desktop GLSL version"
+                    ));
+                }
+                _ => (),
+            }
+            found_version = true;
+            // keep the line numbers the same
+            result.push('\n');
+            continue;
+        }
+        result.push_str(line);
+        result.push('\n');
+    }
+    if !found_version {
+        return Err("shader has no '#version' directive".into());
+    }
+    Ok(result)
+}
+
+//fi split_sampler_type
+/// Get the naga texture and sampler types for a GLSL combined sampler type
+fn split_sampler_type(ty: &str) -> Option<(&'static str, &'static str)> {
+    let split = match ty {
+        "sampler2D" => ("texture2D", "sampler"),
+        "sampler3D" => ("texture3D", "sampler"),
+        "samplerCube" => ("textureCube", "sampler"),
+        "sampler2DArray" => ("texture2DArray", "sampler"),
+        "sampler2DShadow" => ("texture2D", "samplerShadow"),
+        "samplerCubeShadow" => ("textureCube", "samplerShadow"),
+        "sampler2DArrayShadow" => ("texture2DArray", "samplerShadow"),
+        "isampler2D" => ("itexture2D", "sampler"),
+        "isampler3D" => ("itexture3D", "sampler"),
+        "isamplerCube" => ("itextureCube", "sampler"),
+        "isampler2DArray" => ("itexture2DArray", "sampler"),
+        "usampler2D" => ("utexture2D", "sampler"),
+        "usampler3D" => ("utexture3D", "sampler"),
+        "usamplerCube" => ("utextureCube", "sampler"),
+        "usampler2DArray" => ("utexture2DArray", "sampler"),
+        _ => {
+            return None;
+        }
+    };
+    Some(split)
+}
+
+//fi split_layout
+/// Split a declaration in to the qualifiers of its layout (if any)
+/// and the rest of the declaration
+fn split_layout(line: &str) -> (&str, &str) {
+    if let Some(layout) = line.strip_prefix("layout") {
+        if let Some(open) = layout.trim_start().strip_prefix('(') {
+            if let Some(close) = open.find(')') {
+                return (open[..close].trim(), open[close + 1..].trim_start());
+            }
+        }
+    }
+    ("", line)
+}
+
+//fi naga_prepare
+/// Rewrite a source for the naga GLSL frontend, returning the source
+/// and the names of the loose uniforms that have been wrapped in
+/// blocks
+///
+/// Arrays of combined samplers are rejected, as each use of an
+/// element would need rewriting with its index
+fn naga_prepare(source: &str) -> Result<(String, Vec<String>), String> {
+    let mut result = String::with_capacity(source.len() * 2);
+    let mut loose_uniforms = Vec::new();
+    let mut samplers = std::collections::HashMap::new();
+    let mut binding = 0;
+    let mut locations = [0, 0];
+    let mut in_comment = false;
+    let mut lines = source.lines();
+    while let Some(first) = lines.next() {
+        // Join a uniform or storage declaration that continues over
+        // following lines, keeping the line count with blank lines
+        let mut line = std::borrow::Cow::Borrowed(first);
+        let (_, decl) = split_layout(first.trim_start());
+        let keyword = decl.split_whitespace().next();
+        if !in_comment && matches!(keyword, Some("uniform" | "buffer")) {
+            while !line.contains([';', '{']) {
+                let Some(next) = lines.next() else {
+                    break;
+                };
+                line = format!("{line} {}", next.trim()).into();
+                result.push('\n');
+            }
+        }
+        let line = line.as_ref();
+        let (qualifiers, decl) = split_layout(line.trim_start());
+        let (keyword, rest) = decl.split_once(char::is_whitespace).unwrap_or((decl, ""));
+        if !in_comment && keyword == "precision" {
+            result.push('\n');
+            continue;
+        }
+        let io = {
+            let mut words = decl
+                .split_whitespace()
+                .skip_while(|w| matches!(*w, "flat" | "smooth" | "noperspective" | "centroid"));
+            match words.next() {
+                Some("in") => Some(0),
+                Some("out") => Some(1),
+                _ => None,
+            }
+        };
+        if let (false, Some(io)) = (in_comment || qualifiers.contains("location"), io) {
+            if qualifiers.is_empty() {
+                result.push_str(&format!("layout(location = {}) {decl}", locations[io]));
+            } else {
+                result.push_str(&format!(
+                    "layout({qualifiers}, location = {}) {decl}",
+                    locations[io]
+                ));
+            }
+            result.push('\n');
+            locations[io] += 1;
+            continue;
+        }
+        if in_comment || (keyword != "uniform" && keyword != "buffer") {
+            let map = |ident: &str| match ident {
+                "gl_VertexID" => Some("gl_VertexIndex"),
+                "gl_InstanceID" => Some("gl_InstanceIndex"),
+                _ => samplers.get(ident).map(|s: &String| s.as_str()),
+            };
+            result.push_str(&crate::glsl_dialect::replace_identifiers(
+                line,
+                &mut in_comment,
+                map,
+            ));
+            result.push('\n');
+            continue;
+        }
+        let is_block = keyword == "buffer" || rest.contains('{') || !rest.contains(';');
+        if is_block {
+            if qualifiers.contains("binding") {
+                result.push_str(line);
+            } else if qualifiers.is_empty() {
+                result.push_str(&format!("layout(set = 0, binding = {binding}) {decl}"));
+            } else {
+                result.push_str(&format!(
+                    "layout({qualifiers}, set = 0, binding = {binding}) {decl}"
+                ));
+            }
+            result.push('\n');
+            binding += 1;
+            continue;
+        }
+        let rest = rest.split(';').next().unwrap_or("");
+        let mut words = rest
+            .split_whitespace()
+            .filter(|w| !matches!(*w, "lowp" | "mediump" | "highp"));
+        let ty = words.next().unwrap_or("");
+        let names: String = words.collect();
+        for name in names.split(',').filter(|n| !n.is_empty()) {
+            if let Some((texture_ty, sampler_ty)) = split_sampler_type(ty) {
+                if name.contains('[') {
+                    return Err(format!(
+                        "sampler array '{name}' cannot be checked offline, as naga has no combined samplers"
+                    ));
+                }
+                result.push_str(&format!(
+                    "layout(set = 0, binding = {binding}) uniform {texture_ty} {name}; "
+                ));
+                result.push_str(&format!(
+                    "layout(set = 0, binding = {}) uniform {sampler_ty} {name}_sampler; ",
+                    binding + 1
+                ));
+                samplers.insert(name.to_string(), format!("{ty}({name}, {name}_sampler)"));
+                binding += 2;
+            } else {
+                let ident = name.split('[').next().unwrap_or(name);
+                result.push_str(&format!(
+                    "layout(set = 1, binding = {binding}) uniform mod3d_{ident}_block {{ {ty} {name}; }}; "
+                ));
+                loose_uniforms.push(ident.to_string());
+                binding += 1;
+            }
+        }
+        result.push('\n');
+    }
+    Ok((result, loose_uniforms))
+}
+
+//fi has_float_precision
+/// Return true if a source has a default float precision statement
+fn has_float_precision(source: &str) -> bool {
+    source.lines().any(|l| {
+        let mut words = l.split_whitespace();
+        words.next() == Some("precision")
+            && words.next().is_some()
+            && words.next().map(|w| w.trim_end_matches(';')) == Some("float")
+    })
+}
+
+//fp parse_shader
+/// Parse and validate a shader source with naga for a target, and
+/// return its interface
+pub fn parse_shader(
+    stage: ShaderStage,
+    source: &str,
+    target: GlslTarget,
+) -> Result<ShaderInterface, String> {
+    if target == GlslTarget::Es300 && stage == ShaderStage::Fragment && !has_float_precision(source)
+    {
+        return Err("GLSL ES fragment shader has no default float precision".into());
+    }
+    let source = naga_source(source, target)?;
+    let (source, loose_uniforms) = naga_prepare(&source)?;
+    let module = Frontend::default()
+        .parse(&Options::from(stage), &source)
+        .map_err(|e| e.emit_to_string(&source))?;
+    Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|e| e.emit_to_string(&source))?;
+    let mut interface = ShaderInterface::of_module(&module);
+    interface.names.extend(loose_uniforms);
+    Ok(interface)
+}
+
+//ip ShaderProgramDesc
+impl ShaderProgramDesc {
+    //mp check_offline
+    /// Parse and validate the shaders of the description for a
+    /// target, and check that every name in the description is in the
+    /// shader interface
+    ///
+    /// Shader parse and validation failures and missing names are
+    /// reported as errors
+    pub fn check_offline<F>(&self, read_src: &F, target: GlslTarget) -> ProgramValidation
+    where
+        F: Fn(&str) -> Result<String, String>,
+    {
+        let mut validation = ProgramValidation::default();
+        let mut interface = ShaderInterface::default();
+        let stages = match self.stages() {
+            Ok(stages) => stages,
+            Err(e) => {
+                validation.error(ValidationIssue::ShaderError(e));
+                return validation;
+            }
+        };
+        for (shader_type, path) in stages {
+            let stage = match shader_type {
+                GlShaderType::Vertex => ShaderStage::Vertex,
+                GlShaderType::Fragment => ShaderStage::Fragment,
+                GlShaderType::Compute => ShaderStage::Compute,
+                _ => {
+                    validation.warn(ValidationIssue::ShaderError(format!(
+                        "{path}: {shader_type:?} shaders cannot be checked offline"
+                    )));
+                    continue;
+                }
+            };
+            if target == GlslTarget::Es300 && stage == ShaderStage::Compute {
+                validation.warn(ValidationIssue::ShaderError(format!(
+                    "{path}: compute shaders are not supported by GLSL ES 3.00; not checked"
+                )));
+                return validation;
+            }
+            let translated = read_src(path)
+                .map(|src| crate::translate_glsl(&src, shader_type, target.dialect()));
+            match translated.and_then(|src| parse_shader(stage, &src, target)) {
+                Ok(i) => interface.merge(i),
+                Err(e) => {
+                    validation.error(ValidationIssue::ShaderError(format!(
+                        "{path} ({target}): {e}"
+                    )));
+                }
+            }
+        }
+        if !validation.is_ok() {
+            return validation;
+        }
+        let mut check = |kind, name: &str| {
+            if !interface.contains(name) {
+                validation.error(ValidationIssue::MissingName(kind, name.to_string()));
+            }
+        };
+        for (name, _) in self.attribute_map() {
+            check("attribute", name);
+        }
+        for (name, _) in self.uniform_map() {
+            check("uniform", name);
+        }
+        for name in self.uniform_buffer_map().keys() {
+            check("uniform block", name);
+        }
+        for (name, _, _) in self.texture_map() {
+            check("texture", name);
+        }
+        validation
+    }
+}
+
+//a Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prepare_keeps_lines() {
+        let src = "uniform mat4 A, B[2];\nuniform sampler2D T;\nin vec3 P;\nvoid main() {}\n";
+        let (prepared, loose) = naga_prepare(src).unwrap();
+        assert_eq!(prepared.lines().count(), src.lines().count());
+        assert_eq!(loose, ["A", "B"]);
+    }
+
+    #[test]
+    fn prepare_samplers() {
+        let src = "uniform highp sampler2D T;\nvoid f() { texture(T, vec2(0.0)); } // T\n";
+        let (prepared, _) = naga_prepare(src).unwrap();
+        assert!(prepared.contains("uniform texture2D T;"));
+        assert!(prepared.contains("uniform sampler T_sampler;"));
+        assert!(prepared.contains("texture(sampler2D(T, T_sampler), vec2(0.0)); } // T\n"));
+    }
+
+    #[test]
+    fn prepare_multi_line_declarations() {
+        let src = "uniform mat4\n    A,\n    B;\nuniform sampler2D\n    T;\nuniform Block\n{ vec4 x; };\nvoid main() {}\n";
+        let (prepared, loose) = naga_prepare(src).unwrap();
+        let lines: Vec<&str> = prepared.lines().collect();
+        assert_eq!(lines.len(), src.lines().count());
+        assert_eq!(loose, ["A", "B"]);
+        assert!(lines[2].contains("uniform mod3d_A_block { mat4 A; };"));
+        assert!(lines[2].contains("uniform mod3d_B_block { mat4 B; };"));
+        assert!(lines[4].contains("uniform texture2D T;"));
+        assert!(lines[4].contains("uniform sampler T_sampler;"));
+        assert_eq!(
+            lines[6],
+            "layout(set = 0, binding = 4) uniform Block { vec4 x; };"
+        );
+        assert_eq!(lines[7], "void main() {}");
+    }
+
+    #[test]
+    fn prepare_rejects_sampler_arrays() {
+        let src = "uniform sampler2D T[4];\nvoid main() {}\n";
+        let e = naga_prepare(src).unwrap_err();
+        assert!(e.contains("sampler array 'T[4]'"), "{e}");
+        let target = GlslTarget::Desktop;
+        let src = crate::translate_glsl(src, GlShaderType::Fragment, target.dialect());
+        let e = parse_shader(ShaderStage::Fragment, &src, target).unwrap_err();
+        assert!(e.contains("sampler array"), "{e}");
+    }
+
+    #[test]
+    fn prepare_bindings_and_locations() {
+        let src = "layout(std140) uniform Block { vec4 x; };\nlayout(location = 3) in vec3 P;\nflat out int I;\nout vec2 U;\n";
+        let (prepared, _) = naga_prepare(src).unwrap();
+        let lines: Vec<&str> = prepared.lines().collect();
+        assert_eq!(
+            lines[0],
+            "layout(std140, set = 0, binding = 0) uniform Block { vec4 x; };"
+        );
+        assert_eq!(lines[1], "layout(location = 3) in vec3 P;");
+        assert_eq!(lines[2], "layout(location = 0) flat out int I;");
+        assert_eq!(lines[3], "layout(location = 1) out vec2 U;");
+    }
+
+    #[test]
+    fn parse_model_shaders() {
+        let vs = "uniform mat4 ViewMatrix;\nattribute vec3 Position;\nvarying vec2 Uv;\nvoid main() { Uv = Position.xy; gl_Position = ViewMatrix * vec4(Position, 1.0); }\n";
+        let fs = "uniform samplerCube Environment;\nvarying vec2 Uv;\nvoid main() { gl_FragColor = texture2D(Environment, vec3(Uv, 1.0)); }\n";
+        for target in [GlslTarget::Desktop, GlslTarget::Es300] {
+            let src = crate::translate_glsl(vs, GlShaderType::Vertex, target.dialect());
+            let interface = parse_shader(ShaderStage::Vertex, &src, target).unwrap();
+            assert!(interface.contains("ViewMatrix"));
+            assert!(interface.contains("Position"));
+            let src = crate::translate_glsl(fs, GlShaderType::Fragment, target.dialect());
+            let interface = parse_shader(ShaderStage::Fragment, &src, target).unwrap();
+            assert!(interface.contains("Environment"));
+        }
+    }
+}
@@ -1,5 +1,7 @@
 //a Imports
-use crate::{Gl, GlProgram, UniformId, Vertices};
+use crate::buffer;
+use crate::{DrawElementsIndirectCommand, IndirectBuffer};
+use crate::{Gl, GlProgram, Std140, UniformId, UniformValue, Vertices};
 
 //a Shader structure
 //tp ShaderMaterialBaseData
@@ -14,7 +16,6 @@ use crate::{Gl, GlProgram, UniformId, Vertices};
 ///    A scalar parameter controlling the amount of occlusion applied. A value of `0.0` means no occlusion. A value of `1.0` means full occlusion. This value affects the final occlusion value as: `1.0 + strength * (<sampled occlusion texture value> - 1.0)`.
 
 #[derive(Default, Debug)]
-#[repr(C, packed)]
 pub struct ShaderMaterialBaseData {
     base_color: [f32; 4],
     metallic: f32,
@@ -49,13 +50,36 @@ impl ShaderMaterialBaseData {
             ..Default::default()
         }
     }
-    pub fn as_slice(&self) -> &[f32] {
-        unsafe {
-            std::slice::from_raw_parts(
-                self as *const ShaderMaterialBaseData as *const f32,
-                std::mem::size_of_val(self) / std::mem::size_of::<f32>(),
-            )
-        }
+
+    //mp uniform_floats
+    /// Get the floats of the std140 image, to set the Material
+    /// uniform as two vec4s
+    pub fn uniform_floats(&self) -> [f32; 8] {
+        let mut image = [0; Self::SIZE];
+        self.write_std140(&mut image);
+        std::array::from_fn(|i| {
+            let b = &image[i * 4..i * 4 + 4];
+            f32::from_ne_bytes([b[0], b[1], b[2], b[3]])
+        })
+    }
+}
+
+//ip Std140 for ShaderMaterialBaseData
+/// The std140 image is a vec4 base color followed by the four
+/// factors (so it may be used as a uniform block with a vec4 and
+/// four floats, or as two vec4s)
+impl Std140 for ShaderMaterialBaseData {
+    const ALIGN: usize = 16;
+    const SIZE: usize = 32;
+    fn write_std140(&self, dst: &mut [u8]) {
+        let factors = [
+            self.metallic,
+            self.roughness,
+            self.occlusion_factor,
+            self.emissive_factor,
+        ];
+        self.base_color.write_std140(&mut dst[0..16]);
+        factors.write_std140(&mut dst[16..32]);
     }
 }
 
@@ -83,6 +107,9 @@ where
     instantiable: &'a mod3d_base::Instantiable<G>,
     // vaos is 1-to-1 with instantiable::vertices, specific to this shader (class)
     vaos: Vec<G::Vao>,
+    // For each VAO, the index in the program's attributes and the
+    // default value of each attribute that the vertices do not have
+    attr_defaults: Vec<Vec<(usize, [f32; 4])>>,
     // The program NEED NOT be borrowed, if the program's uniforms
     // required for the draw are recorded during 'new_vao'
     program: &'a G::Program,
@@ -94,27 +121,35 @@ where
     G: Gl,
 {
     //fi new_vao
+    /// Create a VAO for the vertices with the program's attributes,
+    /// returning it and the attributes that the vertices lack but the
+    /// program has defaults for
+    #[allow(clippy::type_complexity)]
     fn new_vao(
         context: &mut G,
         program: &G::Program,
         vertices: &Vertices<G>,
-    ) -> Result<G::Vao, ()> {
+    ) -> Result<(G::Vao, Vec<(usize, [f32; 4])>), ()> {
         let (indices, position, attrs) = vertices.borrow();
-        let gl_vao = context.vao_create_from_indices(indices)?;
-        for (index, vertex_attr) in program.attributes() {
+        let gl_vao = match indices {
+            Some(indices) => context.vao_create_from_indices(indices)?,
+            None => context.vao_create()?,
+        };
+        let mut attr_defaults = Vec::new();
+        for (i, (index, vertex_attr)) in program.attributes().iter().enumerate() {
             if *vertex_attr == mod3d_base::VertexAttr::Position {
                 position.bind_to_vao_attr(context, index);
-            } else {
-                for (va, buffer) in attrs {
-                    if *vertex_attr == *va {
-                        buffer.bind_to_vao_attr(context, index);
-                        // crate::opengl_utils::check_errors().unwrap();
-                    }
-                }
+            } else if let Some((_, buffer)) = attrs.iter().find(|(va, _)| *vertex_attr == *va) {
+                buffer.bind_to_vao_attr(context, index);
+                // crate::opengl_utils::check_errors().unwrap();
+            } else if let Some(value) = program.attribute_default(*vertex_attr) {
+                // The attribute's vertex array is not enabled in the new VAO
+                context.attr_set_default(index, &value);
+                attr_defaults.push((i, value));
             }
         }
         context.bind_vao(None);
-        Ok(gl_vao)
+        Ok((gl_vao, attr_defaults))
     }
 
     //fp new
@@ -125,12 +160,16 @@ where
         instantiable: &'a mod3d_base::Instantiable<G>,
     ) -> Result<Self, ()> {
         let mut vaos = Vec::new();
+        let mut attr_defaults = Vec::new();
         for v in &instantiable.vertices {
-            vaos.push(Self::new_vao(context, program, v)?);
+            let (vao, defaults) = Self::new_vao(context, program, v)?;
+            vaos.push(vao);
+            attr_defaults.push(defaults);
         }
         Ok(Self {
             instantiable,
             vaos,
+            attr_defaults,
             program,
         })
     }
@@ -163,45 +202,186 @@ where
         instance model matrix
         for (i, p) in render_recipe.primitives.iter().enumerate() {
          */
+        self.set_instance_state(context, instance);
+        let primitives = &self.instantiable.render_recipe.primitives;
+        let mut i = 0;
+        while i < primitives.len() {
+            let p = &primitives[i];
+            let n = self.batch_length(i);
+            self.set_primitive_state(context, i);
+            if n == 1 {
+                context.draw_primitive(&self.vaos, p);
+            } else {
+                let batch: Vec<&mod3d_base::Primitive> = primitives[i..i + n].iter().collect();
+                context.draw_primitives(&self.vaos, &batch);
+            }
+            i += n;
+        }
+    }
+
+    //fi set_primitive_state
+    /// Set the material, mesh matrix and attribute defaults for a primitive
+    fn set_primitive_state(&self, context: &mut G, i: usize) {
+        let p = &self.instantiable.render_recipe.primitives[i];
+        let mat = p.material();
+        if mat.is_some() {
+            let mat = &self.instantiable.materials[mat.as_usize()];
+            context.program_set_uniform_floats_4(
+                self.program,
+                UniformId::Material,
+                &mat.base_data().uniform_floats(),
+            );
+            for (texture_id, ti) in mat.textures() {
+                if !ti.is_none() {
+                    let gl_texture = &self.instantiable.textures[ti.as_usize()];
+                    context.program_use_texture(self.program, *texture_id, gl_texture);
+                }
+            }
+        }
+
+        // set MeshMatrix (if different to last)
+        // Optimization using mesh uniform buffer
+        // Bind a mat4-sized range of the matrices arrays to the Matrix uniform binding point
+        let m = self.instantiable.render_recipe.matrix_for_primitives[i];
+        context.program_set_uniform_mat4(
+            self.program,
+            UniformId::MeshMatrix,
+            &self.instantiable.render_recipe.matrices[m],
+        );
+        // Attribute defaults are context state, so they must be
+        // set for each draw in case another program changed them
+        let opt_vertices_index: Option<usize> = p.vertices_index().into();
+        if let Some(vertices_index) = opt_vertices_index {
+            let attributes = self.program.attributes();
+            for (i, value) in &self.attr_defaults[vertices_index] {
+                context.attr_set_default(&attributes[*i].0, value);
+            }
+        }
+    }
+
+    //fi set_instance_state
+    /// Set the uniforms for an instance that are common to all its primitives
+    fn set_instance_state(&self, context: &mut G, instance: &mod3d_base::Instance<G>) {
         context.program_set_uniform_mat4(
             self.program,
             UniformId::ModelMatrix,
             &instance.transformation.mat4(),
         );
-        for (i, p) in self
-            .instantiable
+        context.program_set_uniform(
+            self.program,
+            UniformId::PointSize,
+            &UniformValue::Float(&[context.point_size().unwrap_or(1.0)]),
+        );
+    }
+
+    //mp indirect_commands
+    /// Create a [DrawElementsIndirectCommand] for each primitive, in
+    /// order, to draw one instance of it
+    ///
+    /// The byte offset of a primitive of indexed vertices is that of
+    /// its first index, and is converted to the `first_index` of the
+    /// command. The byte offset of a primitive of vertices without
+    /// indices is its first vertex, and it cannot be drawn with an
+    /// indexed command, so it has a command with no instances
+    pub fn indirect_commands(&self) -> Vec<DrawElementsIndirectCommand> {
+        self.instantiable
             .render_recipe
             .primitives
             .iter()
-            .enumerate()
-        {
-            let mat = p.material();
-            if mat.is_some() {
-                let mat = &self.instantiable.materials[mat.as_usize()];
-                context.program_set_uniform_floats_4(
-                    self.program,
-                    UniformId::Material,
-                    mat.base_data().as_slice(),
-                );
-                for (texture_id, ti) in mat.textures() {
-                    if !ti.is_none() {
-                        let gl_texture = &self.instantiable.textures[ti.as_usize()];
-                        context.program_use_texture(self.program, *texture_id, gl_texture);
-                    }
+            .map(|p| {
+                let opt_vertices_index: Option<usize> = p.vertices_index().into();
+                let opt_indices = opt_vertices_index.and_then(|vertices_index| {
+                    self.instantiable.vertices[vertices_index].borrow().0
+                });
+                let Some(indices) = opt_indices else {
+                    return DrawElementsIndirectCommand::default();
+                };
+                let ele_size = buffer::ele_byte_size(indices.ele_type);
+                DrawElementsIndirectCommand {
+                    count: p.index_count(),
+                    instance_count: 1,
+                    first_index: (indices.byte_offset + p.byte_offset()) / ele_size,
+                    base_vertex: 0,
+                    base_instance: 0,
                 }
-            }
+            })
+            .collect()
+    }
 
-            // set MeshMatrix (if different to last)
-            // Optimization using mesh uniform buffer
-            // Bind a mat4-sized range of the matrices arrays to the Matrix uniform binding point
-            let m = self.instantiable.render_recipe.matrix_for_primitives[i];
-            context.program_set_uniform_mat4(
-                self.program,
-                UniformId::MeshMatrix,
-                &self.instantiable.render_recipe.matrices[m],
-            );
-            context.draw_primitive(&self.vaos, p);
+    //fp gl_draw_indirect
+    /// Draw this [ShaderInstantiable] given an [mod3d_base::Instance]
+    /// data, with the draw commands of its primitives taken from an
+    /// [IndirectBuffer] (as created from [Self::indirect_commands] and
+    /// perhaps modified by a compute shader)
+    ///
+    /// Uniforms are set as for [Self::gl_draw], and primitives that
+    /// share them are drawn with a single multi-draw
+    ///
+    /// Primitives without indices cannot be drawn indirectly; they
+    /// are drawn directly (as by [Self::gl_draw]), ignoring their
+    /// commands
+    pub fn gl_draw_indirect(
+        &self,
+        context: &mut G,
+        instance: &mod3d_base::Instance<G>,
+        commands: &IndirectBuffer<G>,
+    ) -> Result<(), String> {
+        let primitives = &self.instantiable.render_recipe.primitives;
+        if commands.num_commands() < primitives.len() {
+            return Err(format!(
+                "Indirect buffer has {} commands but there are {} primitives",
+                commands.num_commands(),
+                primitives.len()
+            ));
         }
+        self.set_instance_state(context, instance);
+        let mut i = 0;
+        while i < primitives.len() {
+            let p = &primitives[i];
+            let n = self.batch_length(i);
+            let opt_vertices_index: Option<usize> = p.vertices_index().into();
+            self.set_primitive_state(context, i);
+            match opt_vertices_index {
+                Some(vertices_index) if self.instantiable.vertices[vertices_index].is_indexed() => {
+                    context.draw_indirect(
+                        &self.vaos[vertices_index],
+                        p.primitive_type(),
+                        commands.gl_buffer(),
+                        i * DrawElementsIndirectCommand::BYTE_SIZE,
+                        n,
+                    )?;
+                }
+                _ => {
+                    let batch: Vec<&mod3d_base::Primitive> = primitives[i..i + n].iter().collect();
+                    context.draw_primitives(&self.vaos, &batch);
+                }
+            }
+            i += n;
+        }
+        Ok(())
+    }
+
+    //fi batch_length
+    /// Get the number of primitives starting at an index that share
+    /// the material, mesh matrix, vertices and primitive type of the
+    /// first, and so can be drawn with a single multi-draw
+    fn batch_length(&self, start: usize) -> usize {
+        let recipe = &self.instantiable.render_recipe;
+        let key = |i: usize| {
+            let p = &recipe.primitives[i];
+            let material: Option<usize> = p.material().into();
+            let vertices_index: Option<usize> = p.vertices_index().into();
+            (
+                recipe.matrix_for_primitives[i],
+                material,
+                vertices_index,
+                p.primitive_type(),
+            )
+        };
+        let first = key(start);
+        (start..recipe.primitives.len())
+            .take_while(|i| key(*i) == first)
+            .count()
     }
 
     //zz All done
@@ -0,0 +1,139 @@
+//a Documentation
+
+/*!
+
+A built-in skybox pass, drawing a cube map as the background of a scene
+
+The skybox is a single full-screen triangle (generated from
+gl_VertexID, so it needs no vertex data) whose fragments are placed
+at the far plane; the direction through each fragment is found from
+the inverse of the view matrix of the scene, and the cube map is
+sampled in that direction.
+
+It should be drawn after the opaque geometry of the scene, with depth
+testing enabled (and the default LESS depth function), so that it
+only covers the pixels that no geometry has been drawn to; it must be
+given the same ViewMatrix (projection * view) as the scene.
+
+!*/
+
+//a Imports
+use crate::{CubeMap, Gl, GlShaderType, Mat4, TextureId, UniformId};
+
+//a Constants
+/// Vertex shader for the skybox; the near and far points of each
+/// corner are passed (homogeneous) so that the direction is correctly
+/// interpolated
+const SKYBOX_VERTEX_SHADER: &str = r#"
+uniform mat4 ViewMatrix;
+varying vec4 SkyNear;
+varying vec4 SkyFar;
+void main() {
+    vec2 xy = vec2(float((gl_VertexID & 1) << 2) - 1.0,
+                   float((gl_VertexID & 2) << 1) - 1.0);
+    mat4 inv = inverse(ViewMatrix);
+    SkyNear = inv * vec4(xy, -1.0, 1.0);
+    SkyFar = inv * vec4(xy, 1.0, 1.0);
+    gl_Position = vec4(xy, 0.999999, 1.0);
+}
+"#;
+
+/// Fragment shader for the skybox
+const SKYBOX_FRAGMENT_SHADER: &str = r#"
+uniform samplerCube Environment;
+varying vec4 SkyNear;
+varying vec4 SkyFar;
+void main() {
+    vec3 dir = SkyFar.xyz / SkyFar.w - SkyNear.xyz / SkyNear.w;
+    gl_FragColor = textureCube(Environment, normalize(dir));
+}
+"#;
+
+//a Skybox
+//tp Skybox
+/// A skybox pass, with its program, an empty VAO and the cube map
+/// that it draws
+pub struct Skybox<G>
+where
+    G: Gl,
+{
+    /// The skybox program
+    program: G::Program,
+    /// An empty VAO (the vertices are generated from gl_VertexID)
+    vao: G::Vao,
+    /// The cube map drawn
+    cube_map: CubeMap<G>,
+}
+
+//ip Skybox
+impl<G> Skybox<G>
+where
+    G: Gl,
+{
+    //fp new
+    /// Create a skybox pass drawing a cube map
+    pub fn new(context: &mut G, cube_map: CubeMap<G>) -> Result<Self, String> {
+        let program = context.compile_program(
+            &[
+                (GlShaderType::Vertex, SKYBOX_VERTEX_SHADER),
+                (GlShaderType::Fragment, SKYBOX_FRAGMENT_SHADER),
+            ],
+            &[],
+            &[("ViewMatrix", UniformId::ViewMatrix)],
+            &[],
+            &[],
+            &[("Environment", TextureId::Environment, 0)],
+            &[],
+        )?;
+        let vao = context
+            .vao_create()
+            .map_err(|_| "Failed to create skybox VAO".to_string())?;
+        context.bind_vao(None);
+        Ok(Self {
+            program,
+            vao,
+            cube_map,
+        })
+    }
+
+    //ap cube_map
+    /// Borrow the cube map drawn by the skybox
+    pub fn cube_map(&self) -> &CubeMap<G> {
+        &self.cube_map
+    }
+
+    //mp set_cube_map
+    /// Replace the cube map drawn by the skybox, returning the old one
+    pub fn set_cube_map(&mut self, cube_map: CubeMap<G>) -> CubeMap<G> {
+        std::mem::replace(&mut self.cube_map, cube_map)
+    }
+
+    //mp draw
+    /// Draw the skybox with the view matrix (projection * view) of the
+    /// scene; this should be after the opaque geometry is drawn
+    ///
+    /// This leaves the skybox program in use
+    pub fn draw(&self, context: &mut G, view_matrix: &Mat4) {
+        context.use_program(Some(&self.program));
+        context.program_set_uniform_mat4(&self.program, UniformId::ViewMatrix, view_matrix);
+        context.program_use_texture(
+            &self.program,
+            TextureId::Environment,
+            self.cube_map.texture(),
+        );
+        context.draw_vertices(Some(&self.vao), mod3d_base::PrimitiveType::Triangles, 0, 3);
+        context.bind_vao(None);
+    }
+}
+
+//ip Debug for Skybox
+impl<G> std::fmt::Debug for Skybox<G>
+where
+    G: Gl,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        f.debug_struct("Skybox")
+            .field("cube_map", &self.cube_map)
+            .finish()
+    }
+}
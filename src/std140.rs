@@ -0,0 +1,345 @@
+//a Documentation
+
+/*!
+
+The std140 layout of uniform block contents
+
+A type that implements [Std140] knows its std140 base alignment and
+size, and can write its std140 byte image; this is the layout that
+the GPU uses for a uniform block declared with 'layout(std140)'.
+
+Implementations are provided for the GLSL scalars, vectors (as
+arrays of 2 to 4 scalars) and matrices (`[f32; 9]` for mat3 and
+`[f32; 16]` for mat4, both column-major, and `[[f32; 2]; 2]` for
+mat2), and for arrays of any [Std140] type using [Std140Array].
+Structures can derive [Std140] with the `derive` feature; structures
+deriving it must not be `#[repr(packed)]`.
+
+!*/
+
+//a Imports
+use std::ops::Range;
+
+//a Std140
+//tt Std140
+/// A type with a std140 layout
+pub trait Std140 {
+    /// The std140 base alignment of the type in bytes
+    const ALIGN: usize;
+
+    /// The size of the std140 image of the type in bytes
+    const SIZE: usize;
+
+    /// Write the std140 byte image of the value; `dst` must be
+    /// exactly [Self::SIZE] bytes
+    fn write_std140(&self, dst: &mut [u8]);
+
+    /// Get the byte range of a named field within the std140 image,
+    /// if the type is a structure with that field
+    fn field_range(_name: &str) -> Option<Range<usize>> {
+        None
+    }
+
+    /// Get the std140 byte image of the value
+    fn to_std140_bytes(&self) -> Vec<u8> {
+        let mut data = vec![0; Self::SIZE];
+        self.write_std140(&mut data);
+        data
+    }
+}
+
+//a Layout functions
+//fp round_up
+/// Round an offset up to a multiple of an alignment
+pub const fn round_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+//fp struct_align
+/// The std140 alignment of a structure given the alignments of its
+/// members: the largest, rounded up to that of a vec4
+pub const fn struct_align(member_aligns: &[usize]) -> usize {
+    let mut align = 16;
+    let mut i = 0;
+    while i < member_aligns.len() {
+        if member_aligns[i] > align {
+            align = member_aligns[i];
+        }
+        i += 1;
+    }
+    round_up(align, 16)
+}
+
+//fp struct_size
+/// The std140 size of a structure given the (alignment, size) of its
+/// members, in order
+pub const fn struct_size(members: &[(usize, usize)]) -> usize {
+    let mut offset = 0;
+    let mut align = 16;
+    let mut i = 0;
+    while i < members.len() {
+        offset = round_up(offset, members[i].0) + members[i].1;
+        if members[i].0 > align {
+            align = members[i].0;
+        }
+        i += 1;
+    }
+    round_up(offset, round_up(align, 16))
+}
+
+//a Scalar and vector implementations
+macro_rules! std140_scalar {
+    ($t:ty) => {
+        impl Std140 for $t {
+            const ALIGN: usize = 4;
+            const SIZE: usize = 4;
+            fn write_std140(&self, dst: &mut [u8]) {
+                dst.copy_from_slice(&self.to_ne_bytes());
+            }
+        }
+        impl Std140 for [$t; 2] {
+            const ALIGN: usize = 8;
+            const SIZE: usize = 8;
+            fn write_std140(&self, dst: &mut [u8]) {
+                for (i, v) in self.iter().enumerate() {
+                    v.write_std140(&mut dst[i * 4..i * 4 + 4]);
+                }
+            }
+        }
+        impl Std140 for [$t; 3] {
+            const ALIGN: usize = 16;
+            const SIZE: usize = 12;
+            fn write_std140(&self, dst: &mut [u8]) {
+                for (i, v) in self.iter().enumerate() {
+                    v.write_std140(&mut dst[i * 4..i * 4 + 4]);
+                }
+            }
+        }
+        impl Std140 for [$t; 4] {
+            const ALIGN: usize = 16;
+            const SIZE: usize = 16;
+            fn write_std140(&self, dst: &mut [u8]) {
+                for (i, v) in self.iter().enumerate() {
+                    v.write_std140(&mut dst[i * 4..i * 4 + 4]);
+                }
+            }
+        }
+    };
+}
+
+std140_scalar!(f32);
+std140_scalar!(i32);
+std140_scalar!(u32);
+
+//ip Std140 for bool
+/// A GLSL bool is 4 bytes in a uniform block
+impl Std140 for bool {
+    const ALIGN: usize = 4;
+    const SIZE: usize = 4;
+    fn write_std140(&self, dst: &mut [u8]) {
+        (*self as u32).write_std140(dst);
+    }
+}
+
+//a Matrix implementations
+//ip Std140 for [[f32; 2]; 2]
+/// mat2: two columns, each padded to a vec4
+impl Std140 for [[f32; 2]; 2] {
+    const ALIGN: usize = 16;
+    const SIZE: usize = 32;
+    fn write_std140(&self, dst: &mut [u8]) {
+        dst.fill(0);
+        for (c, column) in self.iter().enumerate() {
+            column.write_std140(&mut dst[c * 16..c * 16 + 8]);
+        }
+    }
+}
+
+//ip Std140 for [f32; 9]
+/// mat3 (column-major): three columns, each padded to a vec4
+impl Std140 for [f32; 9] {
+    const ALIGN: usize = 16;
+    const SIZE: usize = 48;
+    fn write_std140(&self, dst: &mut [u8]) {
+        dst.fill(0);
+        for c in 0..3 {
+            let column = [self[c * 3], self[c * 3 + 1], self[c * 3 + 2]];
+            column.write_std140(&mut dst[c * 16..c * 16 + 12]);
+        }
+    }
+}
+
+//ip Std140 for [f32; 16]
+/// mat4 (column-major)
+impl Std140 for [f32; 16] {
+    const ALIGN: usize = 16;
+    const SIZE: usize = 64;
+    fn write_std140(&self, dst: &mut [u8]) {
+        for (i, v) in self.iter().enumerate() {
+            v.write_std140(&mut dst[i * 4..i * 4 + 4]);
+        }
+    }
+}
+
+//a Std140Array
+//tp Std140Array
+/// An array of N std140 values; each element is padded to a
+/// multiple of 16 bytes
+#[derive(Debug, Clone, Copy)]
+pub struct Std140Array<T: Std140, const N: usize>(pub [T; N]);
+
+//ip Std140Array
+impl<T: Std140, const N: usize> Std140Array<T, N> {
+    /// The byte stride between elements
+    pub const STRIDE: usize = round_up(T::SIZE, 16);
+}
+
+//ip Std140 for Std140Array
+impl<T: Std140, const N: usize> Std140 for Std140Array<T, N> {
+    const ALIGN: usize = round_up(T::ALIGN, 16);
+    const SIZE: usize = Self::STRIDE * N;
+    fn write_std140(&self, dst: &mut [u8]) {
+        dst.fill(0);
+        for (i, v) in self.0.iter().enumerate() {
+            let start = i * Self::STRIDE;
+            v.write_std140(&mut dst[start..start + T::SIZE]);
+        }
+    }
+}
+
+//a Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //fi floats
+    /// Get the floats of a std140 image
+    fn floats(data: &[u8]) -> Vec<f32> {
+        data.chunks_exact(4)
+            .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn scalars_and_vectors() {
+        assert_eq!((f32::ALIGN, f32::SIZE), (4, 4));
+        assert_eq!((bool::ALIGN, bool::SIZE), (4, 4));
+        assert_eq!((<[f32; 2]>::ALIGN, <[f32; 2]>::SIZE), (8, 8));
+        assert_eq!((<[i32; 3]>::ALIGN, <[i32; 3]>::SIZE), (16, 12));
+        assert_eq!((<[u32; 4]>::ALIGN, <[u32; 4]>::SIZE), (16, 16));
+        assert_eq!(true.to_std140_bytes(), 1_u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn matrices() {
+        assert_eq!((<[[f32; 2]; 2]>::ALIGN, <[[f32; 2]; 2]>::SIZE), (16, 32));
+        assert_eq!((<[f32; 9]>::ALIGN, <[f32; 9]>::SIZE), (16, 48));
+        assert_eq!((<[f32; 16]>::ALIGN, <[f32; 16]>::SIZE), (16, 64));
+        let mat3: [f32; 9] = std::array::from_fn(|i| i as f32 + 1.0);
+        assert_eq!(
+            floats(&mat3.to_std140_bytes()),
+            [1., 2., 3., 0., 4., 5., 6., 0., 7., 8., 9., 0.]
+        );
+        let mat2 = [[1.0, 2.0], [3.0, 4.0]];
+        assert_eq!(
+            floats(&mat2.to_std140_bytes()),
+            [1., 2., 0., 0., 3., 4., 0., 0.]
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        // Scalar and vec3 elements are padded to a vec4
+        assert_eq!(Std140Array::<f32, 4>::STRIDE, 16);
+        assert_eq!(<Std140Array<f32, 4>>::SIZE, 64);
+        assert_eq!(<Std140Array<f32, 4>>::ALIGN, 16);
+        assert_eq!(<Std140Array<[f32; 3], 2>>::SIZE, 32);
+        // mat4 elements are already a multiple of a vec4
+        assert_eq!(Std140Array::<[f32; 16], 3>::STRIDE, 64);
+        assert_eq!(<Std140Array<[f32; 16], 3>>::SIZE, 192);
+        let array = Std140Array([1.0_f32, 2.0, 3.0]);
+        assert_eq!(
+            floats(&array.to_std140_bytes()),
+            [1., 0., 0., 0., 2., 0., 0., 0., 3., 0., 0., 0.]
+        );
+    }
+
+    #[test]
+    fn structure_layout() {
+        // A float packs after a vec3, in the last four bytes of its vec4
+        assert_eq!(struct_size(&[(16, 12), (4, 4)]), 16);
+        // A vec3 after a float starts at the next vec4
+        assert_eq!(struct_size(&[(4, 4), (16, 12)]), 32);
+        // A vec2 is aligned to 8 bytes, and the structure to a vec4
+        assert_eq!(struct_size(&[(4, 4), (8, 8)]), 16);
+        assert_eq!(struct_align(&[4, 8]), 16);
+        // A nested structure (of size 16) starts at the next vec4
+        assert_eq!(struct_size(&[(4, 4), (16, 16), (4, 4)]), 48);
+        assert_eq!(round_up(17, 16), 32);
+        assert_eq!(round_up(32, 16), 32);
+    }
+
+    #[cfg(feature = "derive")]
+    mod derive {
+        use crate::{Std140, Std140Array};
+
+        #[derive(crate::Std140)]
+        struct Light {
+            position: [f32; 3],
+            intensity: f32,
+        }
+
+        #[derive(crate::Std140)]
+        struct Inner {
+            scale: f32,
+            offset: [f32; 2],
+        }
+
+        #[derive(crate::Std140)]
+        struct Outer {
+            first: f32,
+            inner: Inner,
+            last: f32,
+            matrices: Std140Array<[f32; 16], 2>,
+        }
+
+        #[test]
+        fn vec3_then_float() {
+            assert_eq!((Light::ALIGN, Light::SIZE), (16, 16));
+            assert_eq!(Light::field_range("position"), Some(0..12));
+            assert_eq!(Light::field_range("intensity"), Some(12..16));
+            assert_eq!(Light::field_range("color"), None);
+            let light = Light {
+                position: [1.0, 2.0, 3.0],
+                intensity: 4.0,
+            };
+            assert_eq!(super::floats(&light.to_std140_bytes()), [1., 2., 3., 4.]);
+        }
+
+        #[test]
+        fn nested() {
+            assert_eq!((Inner::ALIGN, Inner::SIZE), (16, 16));
+            assert_eq!(Inner::field_range("offset"), Some(8..16));
+            assert_eq!((Outer::ALIGN, Outer::SIZE), (16, 176));
+            assert_eq!(Outer::field_range("inner"), Some(16..32));
+            assert_eq!(Outer::field_range("last"), Some(32..36));
+            assert_eq!(Outer::field_range("matrices"), Some(48..176));
+            let outer = Outer {
+                first: 1.0,
+                inner: Inner {
+                    scale: 2.0,
+                    offset: [3.0, 4.0],
+                },
+                last: 5.0,
+                matrices: Std140Array([[6.0; 16], [7.0; 16]]),
+            };
+            let data = super::floats(&outer.to_std140_bytes());
+            assert_eq!(data.len(), 44);
+            assert_eq!(data[..10], [1., 0., 0., 0., 2., 0., 3., 4., 5., 0.]);
+            assert_eq!(data[12], 6.0);
+            assert_eq!(data[27], 6.0);
+            assert_eq!(data[28], 7.0);
+            assert_eq!(data[43], 7.0);
+        }
+    }
+}
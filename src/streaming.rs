@@ -0,0 +1,185 @@
+//a Documentation
+
+/*!
+
+A ring buffer for streaming per-frame uniform data
+
+A [StreamingBuffer] is a single dynamic [UniformBuffer] divided in
+to a number of frame regions (usually two or three). Each frame
+allocates ranges from its own region, at offsets aligned as the GL
+requires for binding a range to a uniform block; at the end of the
+frame a fence is inserted, and when the region is next used the
+fence is waited on, so that data is never overwritten while the GPU
+may still be reading it.
+
+As the fences guarantee that the GPU has finished with a region, data
+is written (with OpenGL) through an unsynchronized mapping of its
+range, so the driver does not stall. WebGL2 cannot map buffers, so
+the buffer is instead orphaned at the start of each frame and the
+data copied in to the new storage.
+
+!*/
+
+//a Imports
+use crate::{BufferUsage, Gl, Std140, UniformBuffer};
+
+//a StreamingBuffer
+//tp StreamingBuffer
+/// A uniform buffer divided in to per-frame regions, from which
+/// ranges of uniform data are allocated each frame
+pub struct StreamingBuffer<G>
+where
+    G: Gl,
+{
+    /// The uniform buffer containing all the frame regions
+    uniform_buffer: UniformBuffer<G>,
+    /// Byte size of each frame region
+    frame_byte_size: usize,
+    /// Alignment required for the byte offset of a bound range
+    alignment: usize,
+    /// The fence inserted at the end of the last use of each region
+    fences: Vec<Option<G::Fence>>,
+    /// The region used by the current frame
+    frame: usize,
+    /// Byte offset within the current region of the next allocation
+    offset: usize,
+    /// True if data is written through a mapping; otherwise the
+    /// buffer is orphaned at the start of each frame
+    mapped: bool,
+}
+
+//ip StreamingBuffer
+impl<G> StreamingBuffer<G>
+where
+    G: Gl,
+{
+    //fp new
+    /// Create a streaming buffer with a number of frame regions each
+    /// of (at least) a byte size
+    pub fn new(context: &mut G, frame_byte_size: usize, num_frames: usize) -> Result<Self, String> {
+        if num_frames == 0 {
+            return Err("A streaming buffer must have at least one frame region".into());
+        }
+        let alignment = context.uniform_buffer_offset_alignment();
+        let frame_byte_size = frame_byte_size.div_ceil(alignment) * alignment;
+        let data = vec![0_u8; frame_byte_size * num_frames];
+        let uniform_buffer = UniformBuffer::of_data(context, &data, true)
+            .map_err(|_| "Failed to create streaming uniform buffer".to_string())?;
+        let fences = (0..num_frames).map(|_| None).collect();
+        let mapped = context.buffer_can_map();
+        Ok(Self {
+            uniform_buffer,
+            frame_byte_size,
+            alignment,
+            fences,
+            frame: 0,
+            offset: 0,
+            mapped,
+        })
+    }
+
+    //ap uniform_buffer
+    /// Borrow the underlying [UniformBuffer]
+    pub fn uniform_buffer(&self) -> &UniformBuffer<G> {
+        &self.uniform_buffer
+    }
+
+    //ap frame_byte_size
+    /// Get the byte size of each frame region
+    pub fn frame_byte_size(&self) -> usize {
+        self.frame_byte_size
+    }
+
+    //mp begin_frame
+    /// Move to the next frame region, waiting for the GPU to have
+    /// finished with it if required (or, if the buffer cannot be
+    /// mapped, orphaning the buffer)
+    ///
+    /// This fails if the wait for the GPU fails
+    pub fn begin_frame(&mut self, context: &mut G) -> Result<(), String> {
+        self.frame = (self.frame + 1) % self.fences.len();
+        self.offset = 0;
+        if let Some(fence) = self.fences[self.frame].take() {
+            context.fence_wait(fence)?;
+        }
+        if !self.mapped {
+            context.buffer_orphan(
+                self.uniform_buffer.gl_buffer(),
+                self.uniform_buffer.byte_length(),
+                BufferUsage::Dynamic,
+            );
+        }
+        Ok(())
+    }
+
+    //mp end_frame
+    /// Mark the end of the commands using the current frame region
+    pub fn end_frame(&mut self, context: &mut G) {
+        self.fences[self.frame] = context.fence_insert();
+    }
+
+    //mp allocate_bytes
+    /// Copy data in to the current frame region, returning the
+    /// (byte offset, byte length) of the range in the buffer
+    ///
+    /// This fails if the frame region does not have space for the data
+    pub fn allocate_bytes(
+        &mut self,
+        context: &mut G,
+        data: &[u8],
+    ) -> Result<(usize, usize), String> {
+        let start = self.offset.div_ceil(self.alignment) * self.alignment;
+        if start + data.len() > self.frame_byte_size {
+            return Err(format!(
+                "Streaming buffer frame region of {} bytes has no space for {} bytes at {start}",
+                self.frame_byte_size,
+                data.len()
+            ));
+        }
+        let byte_offset = self.frame * self.frame_byte_size + start;
+        if self.mapped {
+            context.buffer_write_unsynchronized(
+                self.uniform_buffer.gl_buffer(),
+                data,
+                byte_offset,
+            )?;
+        } else {
+            context.uniform_buffer_update_data(&self.uniform_buffer, data, byte_offset as u32);
+        }
+        self.offset = start + data.len();
+        Ok((byte_offset, data.len()))
+    }
+
+    //mp allocate
+    /// Copy the std140 image of a value in to the current frame
+    /// region, returning the (byte offset, byte length) of the range
+    pub fn allocate<T: Std140>(
+        &mut self,
+        context: &mut G,
+        value: &T,
+    ) -> Result<(usize, usize), String> {
+        self.allocate_bytes(context, &value.to_std140_bytes())
+    }
+
+    //mp bind_range
+    /// Bind a range returned by an allocation to a uniform block binding point
+    pub fn bind_range(&self, context: &mut G, gl_uindex: u32, range: (usize, usize)) {
+        context.uniform_index_of_range(&self.uniform_buffer, gl_uindex, range.0, range.1);
+    }
+}
+
+//ip Debug for StreamingBuffer
+impl<G> std::fmt::Debug for StreamingBuffer<G>
+where
+    G: Gl,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        f.debug_struct("StreamingBuffer")
+            .field("frame_byte_size", &self.frame_byte_size)
+            .field("num_frames", &self.fences.len())
+            .field("frame", &self.frame)
+            .field("offset", &self.offset)
+            .field("mapped", &self.mapped)
+            .finish()
+    }
+}
@@ -1,7 +1,8 @@
 //a Imports
-use crate::{BufferView, UniformBuffer};
-use crate::{Mat4, TextureId, UniformId};
-use crate::{Material, Vertices};
+use crate::{AttributeFormat, BufferView, PrimitiveRestart, StorageBuffer, UniformBuffer};
+use crate::{BufferUsage, GlslDialect, Mat4, MemoryBarrier, PixelFormat, TextureId};
+use crate::{Material, PoolAllocation, ProgramReflection, UniformStats, UniformValue, Vertices};
+use crate::{TextureKind, UniformId};
 
 //tp GlShader
 pub trait GlShader: Sized {
@@ -30,12 +31,40 @@ pub trait GlProgram: Sized {
     /// Attempt to retrieve the uniform and sampler from a [UniformId] - return None
     /// if the shader program does not have that uniform
     fn texture_uniform(&self, texture_id: TextureId) -> Option<(Self::GlUniformId<'_>, u32)>;
+
+    /// Record a value for a uniform in the program's shadow copy of
+    /// its uniform values, returning true if it differs from the
+    /// value last uploaded (and hence must be uploaded)
+    fn uniform_changed(&self, uniform_id: UniformId, value: &UniformValue) -> bool;
+
+    /// Forget the program's shadow copy of its uniform values, so that
+    /// the next value set for each uniform is uploaded
+    fn invalidate_uniform_cache(&self);
+
+    /// Get the counters of uniform uploads made and skipped for the program
+    fn uniform_stats(&self) -> UniformStats;
+
+    /// Set the default values of vertex attributes, used when a mesh
+    /// does not have the attribute
+    fn set_attribute_defaults(&mut self, defaults: &[(mod3d_base::VertexAttr, [f32; 4])]);
+
+    /// Get the default value of a vertex attribute, if it has one
+    fn attribute_default(&self, vertex_attr: mod3d_base::VertexAttr) -> Option<[f32; 4]>;
 }
 
 //tt GlShaderType
+/// The stage of a shader
+///
+/// Geometry, tessellation and compute shaders are only supported by
+/// the OpenGL backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GlShaderType {
     Vertex,
     Fragment,
+    Geometry,
+    TessControl,
+    TessEvaluation,
+    Compute,
 }
 
 //tt GlBuffer
@@ -45,7 +74,21 @@ pub enum GlShaderType {
 /// A single GlBuffer will be cloned for different
 /// mod3d_base::BufferAccessor of the same BufferData (by the
 /// [VertexBuffer] type)
-pub trait GlBuffer: Default + Clone + std::fmt::Debug + mod3d_base::BufferClient {}
+pub trait GlBuffer: Default + Clone + std::fmt::Debug + mod3d_base::BufferClient {
+    //ap byte_offset
+    /// The byte offset of the data in the GPU buffer; this is zero
+    /// unless the buffer is an allocation from a [crate::BufferPool]
+    fn byte_offset(&self) -> u32;
+
+    //fp of_pool_allocation
+    /// Create a buffer for an allocation within this (pool block) buffer
+    fn of_pool_allocation(&self, allocation: PoolAllocation) -> Self;
+
+    //ap is_pool_allocation
+    /// Return true if the buffer is an allocation from a
+    /// [crate::BufferPool], and so shares its GPU buffer
+    fn is_pool_allocation(&self) -> bool;
+}
 
 //tt GlVao
 /// The GlVao correlates to an OpenGl VAO buffer for a ShaderInstantiable mesh + GlProgram
@@ -74,30 +117,88 @@ pub trait Gl:
     type Buffer: GlBuffer;
     type Vao: GlVao;
     type Texture;
+    /// A GPU fence, that is signalled when the GPU has completed the
+    /// commands issued before it
+    type Fence;
 
     //fp link_program
     /// Create a program from a list of compiled shaders
+    ///
+    /// Any feedback varyings are captured (interleaved) in to the
+    /// buffer of [Gl::transform_feedback_begin]
+    #[allow(clippy::too_many_arguments)]
     fn link_program(
         &self,
         srcs: &[&Self::Shader],
         named_attrs: &[(&str, mod3d_base::VertexAttr)],
         named_uniforms: &[(&str, UniformId)],
         named_uniform_buffers: &[(&str, usize)],
+        named_storage_buffers: &[(&str, usize)],
         named_textures: &[(&str, crate::TextureId, usize)],
+        feedback_varyings: &[&str],
     ) -> Result<Self::Program, String>;
 
+    //fp glsl_dialect
+    /// The GLSL dialect that shader sources are translated to by
+    /// [Gl::compile_shader]
+    fn glsl_dialect(&self) -> GlslDialect;
+
     //fp compile_shader
-    /// Compile a shader
+    /// Compile a shader from a dialect-neutral source (see
+    /// [crate::translate_glsl])
     fn compile_shader(
         &self,
         shader_type: GlShaderType,
         source: &str,
     ) -> Result<Self::Shader, String>;
 
+    //fp compile_program
+    /// Compile shaders from their sources and link them into a program
+    ///
+    /// A backend may override this to avoid the compilation (e.g. by
+    /// using a cached program binary)
+    #[allow(clippy::too_many_arguments)]
+    fn compile_program(
+        &self,
+        srcs: &[(GlShaderType, &str)],
+        named_attrs: &[(&str, mod3d_base::VertexAttr)],
+        named_uniforms: &[(&str, UniformId)],
+        named_uniform_buffers: &[(&str, usize)],
+        named_storage_buffers: &[(&str, usize)],
+        named_textures: &[(&str, crate::TextureId, usize)],
+        feedback_varyings: &[&str],
+    ) -> Result<Self::Program, String> {
+        let mut shaders = Vec::new();
+        for (shader_type, source) in srcs {
+            shaders.push(self.compile_shader(*shader_type, source)?);
+        }
+        let shaders: Vec<&Self::Shader> = shaders.iter().collect();
+        self.link_program(
+            &shaders,
+            named_attrs,
+            named_uniforms,
+            named_uniform_buffers,
+            named_storage_buffers,
+            named_textures,
+            feedback_varyings,
+        )
+    }
+
+    //fp program_reflection
+    /// Get the active attributes, uniforms and uniform blocks of a
+    /// linked program
+    fn program_reflection(&self, program: &Self::Program) -> ProgramReflection;
+
     //fp use_program
     /// Use the program
     fn use_program(&self, program: Option<&Self::Program>);
 
+    //mp share_index_buffers
+    /// Return true if indices are drawn directly from the GPU copy of
+    /// the [mod3d_base::BufferData] that contains them, rather than
+    /// from a separate copy in their own buffer
+    fn share_index_buffers(&self) -> bool;
+
     //mp init_buffer_of_indices
     /// Create the OpenGL ELEMENT_ARRAY_BUFFER buffer using STATIC_DRAW - this copies the data in to OpenGL
     fn init_buffer_of_indices(
@@ -106,6 +207,85 @@ pub trait Gl:
         view: &mod3d_base::BufferAccessor<Self>,
     );
 
+    //mp buffer_create
+    /// Create a GPU buffer of a byte length and usage, with
+    /// uninitialized contents, for vertex data
+    fn buffer_create(&mut self, byte_length: usize, usage: BufferUsage) -> <Self as Gl>::Buffer;
+
+    //mp buffer_data_create
+    /// Create (or, if it already exists, respecify) the GPU copy of a
+    /// [mod3d_base::BufferData] with a usage; the data client is not
+    /// recreated by a later `init_buffer_data_client`, so this may be
+    /// used to make vertex data dynamic before the vertices are created
+    fn buffer_data_create(
+        &mut self,
+        buffer: &mut <Self as Gl>::Buffer,
+        data: &mod3d_base::BufferData<Self>,
+        usage: BufferUsage,
+    );
+
+    //mp buffer_update_data
+    /// Update (a portion of) the GPU copy of vertex data, starting at a
+    /// byte offset; the buffer must be large enough
+    fn buffer_update_data(
+        &mut self,
+        buffer: &<Self as Gl>::Buffer,
+        data: &[u8],
+        byte_offset: usize,
+    );
+
+    //mp buffer_read_data
+    /// Read (a portion of) the GPU copy of vertex data, starting at a
+    /// byte offset, in to a slice; this waits for the GPU to finish
+    /// writing the buffer
+    fn buffer_read_data(
+        &mut self,
+        buffer: &<Self as Gl>::Buffer,
+        byte_offset: usize,
+        data: &mut [u8],
+    ) -> Result<(), String>;
+
+    //mp buffer_copy
+    /// Copy a byte range of one GPU buffer to another, on the GPU
+    fn buffer_copy(
+        &mut self,
+        src: &<Self as Gl>::Buffer,
+        src_byte_offset: usize,
+        dst: &<Self as Gl>::Buffer,
+        dst_byte_offset: usize,
+        byte_length: usize,
+    );
+
+    //ap buffer_can_map
+    /// Return true if [Gl::buffer_write_unsynchronized] is supported;
+    /// WebGL2 cannot map buffers
+    fn buffer_can_map(&self) -> bool;
+
+    //mp buffer_write_unsynchronized
+    /// Write data to a byte range of a buffer through a mapping of the
+    /// range, without waiting for the GPU to finish with the buffer
+    ///
+    /// The caller must ensure (e.g. with fences) that the GPU is not
+    /// using the range; this fails if buffers cannot be mapped
+    fn buffer_write_unsynchronized(
+        &mut self,
+        buffer: &<Self as Gl>::Buffer,
+        data: &[u8],
+        byte_offset: usize,
+    ) -> Result<(), String>;
+
+    //mp buffer_orphan
+    /// Replace the GPU storage of vertex data with new (uninitialized)
+    /// storage of a byte length and usage, so that the GPU need not
+    /// wait for draws using the old contents; this also resizes the
+    /// buffer, keeping it bound to any VAOs that use it
+    fn buffer_orphan(
+        &mut self,
+        buffer: &<Self as Gl>::Buffer,
+        byte_length: usize,
+        usage: BufferUsage,
+    );
+
     //mp uniform_buffer_create
     /// Create a uniform buffer (a GlBuffer in the GPU bound to GlUniformBuffer)
     ///
@@ -135,6 +315,159 @@ pub trait Gl:
         _byte_length: usize,
     );
 
+    //mp uniform_buffer_offset_alignment
+    /// The alignment required for the byte offset of a range of a
+    /// uniform buffer bound with [Gl::uniform_index_of_range]
+    fn uniform_buffer_offset_alignment(&self) -> usize;
+
+    //mp storage_buffer_create
+    /// Create a shader storage buffer containing some data, or an
+    /// error if the backend does not support storage buffers
+    fn storage_buffer_create(
+        &mut self,
+        data: &[u8],
+        usage: BufferUsage,
+    ) -> Result<StorageBuffer<Self>, String>;
+
+    //mp storage_index_of_range
+    /// Set the GPU's shader storage buffer binding point N to a range
+    /// of a StorageBuffer (a byte length of zero is the whole buffer)
+    fn storage_index_of_range(
+        &mut self,
+        storage_buffer: &StorageBuffer<Self>,
+        gl_index: u32,
+        byte_offset: usize,
+        byte_length: usize,
+    );
+
+    //mp fence_insert
+    /// Insert a fence after the commands issued so far, if the
+    /// backend supports fences
+    fn fence_insert(&mut self) -> Option<Self::Fence>;
+
+    //mp fence_wait
+    /// Wait for the GPU to complete the commands before a fence, and
+    /// delete the fence
+    ///
+    /// This fails if the wait fails or (for OpenGL) if the GPU does
+    /// not complete the commands within a few seconds
+    fn fence_wait(&mut self, fence: Self::Fence) -> Result<(), String>;
+
+    //mp fence_is_signalled
+    /// Return true if the GPU has completed the commands before a
+    /// fence, without waiting
+    fn fence_is_signalled(&mut self, fence: &Self::Fence) -> bool;
+
+    //mp texture_create
+    /// Create a texture of a kind from texture data, optionally
+    /// generating mipmaps for it
+    ///
+    /// The data must have a depth of zero for a
+    /// [TextureKind::Texture2D], and a non-zero depth (the number of
+    /// layers, or the depth of the volume) otherwise
+    fn texture_create(
+        &mut self,
+        texture: &mod3d_base::Texture<Self>,
+        kind: TextureKind,
+        mipmaps: bool,
+    ) -> Result<<Self as Gl>::Texture, String>;
+
+    //mp texture_create_cube
+    /// Create a cube map from the data of six square faces of a size,
+    /// in the order +X, -X, +Y, -Y, +Z, -Z, optionally generating
+    /// mipmaps for it
+    ///
+    /// Each face has a number of channels (1 to 4) of an element type
+    fn texture_create_cube(
+        &mut self,
+        size: usize,
+        channels: usize,
+        ele_type: mod3d_base::BufferElementType,
+        faces: [&[u8]; 6],
+        mipmaps: bool,
+    ) -> Result<<Self as Gl>::Texture, String>;
+
+    //mp texture_create_target
+    /// Create a [TextureKind::Texture2D] or [TextureKind::CubeMap] (of
+    /// square faces) of a size, with a number of mipmap levels of
+    /// uninitialized storage in a pixel format, to be rendered to with
+    /// [Gl::render_to_texture]
+    ///
+    /// [PixelFormat::RgbaF32] targets are stored as 16-bit floats
+    fn texture_create_target(
+        &mut self,
+        kind: TextureKind,
+        size: usize,
+        format: PixelFormat,
+        levels: usize,
+    ) -> Result<<Self as Gl>::Texture, String>;
+
+    //mp render_to_texture
+    /// Direct rendering to a level of a texture (and a face of a cube
+    /// map, in the order +X, -X, +Y, -Y, +Z, -Z), setting the viewport
+    /// to the size of the level; with None, rendering is directed back
+    /// to the default framebuffer and its viewport is restored
+    ///
+    /// The render target has no depth buffer
+    fn render_to_texture(
+        &mut self,
+        target: Option<(&<Self as Gl>::Texture, usize, usize)>,
+    ) -> Result<(), String>;
+
+    //mp texture_size
+    /// Get the width and height of a level of a texture
+    fn texture_size(&mut self, texture: &<Self as Gl>::Texture, level: usize) -> (usize, usize);
+
+    //mp texture_read
+    /// Read a level of a 2D texture, converted to a pixel format, in
+    /// to a slice (that must be large enough); rows are bottom-up
+    fn texture_read(
+        &mut self,
+        texture: &<Self as Gl>::Texture,
+        level: usize,
+        format: PixelFormat,
+        data: &mut [u8],
+    ) -> Result<(), String>;
+
+    //mp texture_read_to_buffer
+    /// Read a level of a 2D texture, converted to a pixel format, in to
+    /// the start of a GPU buffer (as a pixel pack buffer), without
+    /// waiting for the GPU
+    fn texture_read_to_buffer(
+        &mut self,
+        texture: &<Self as Gl>::Texture,
+        level: usize,
+        format: PixelFormat,
+        buffer: &<Self as Gl>::Buffer,
+    ) -> Result<(), String>;
+
+    //mp read_pixels
+    /// Read a rectangle of pixels of the bound (read) framebuffer, in
+    /// a pixel format, in to a slice (that must be large enough)
+    fn read_pixels(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        format: PixelFormat,
+        data: &mut [u8],
+    ) -> Result<(), String>;
+
+    //mp read_pixels_to_buffer
+    /// Read a rectangle of pixels of the bound (read) framebuffer, in
+    /// a pixel format, in to the start of a GPU buffer (as a pixel
+    /// pack buffer), without waiting for the GPU
+    fn read_pixels_to_buffer(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        format: PixelFormat,
+        buffer: &<Self as Gl>::Buffer,
+    ) -> Result<(), String>;
+
     //fp vao_create_from_indices
     /// Create a VAO, add the indices as its element array buffer, and
     /// leave it bound
@@ -143,20 +476,39 @@ pub trait Gl:
         indices: &crate::IndexBuffer<Self>,
     ) -> Result<Self::Vao, ()>;
 
+    //fp vao_create
+    /// Create a VAO with no element array buffer (for non-indexed
+    /// vertices), and leave it bound
+    fn vao_create(&mut self) -> Result<Self::Vao, ()>;
+
     //fp buffer_bind_to_vao_attr
     /// With the currently bound VAO add this view of the specified
     /// buffer as an attribute of the program, if the program has that
-    /// attribute
+    /// attribute; integer data is presented as the format requires
+    #[allow(clippy::too_many_arguments)]
     fn buffer_bind_to_vao_attr(
         &mut self,
         buffer: &<Self as Gl>::Buffer,
         attr_id: &<<Self as Gl>::Program as GlProgram>::GlAttrId,
         count: u32,
         ele_type: mod3d_base::BufferElementType,
+        format: AttributeFormat,
         byte_offset: u32,
         stride: u32,
     );
 
+    //fp attr_set_default
+    /// Set the constant value of an attribute, which is used when its
+    /// vertex array is not enabled in the bound VAO
+    ///
+    /// The constant value is GL context state (not VAO state), so it
+    /// must be set again if another program's defaults change it
+    fn attr_set_default(
+        &mut self,
+        attr_id: &<<Self as Gl>::Program as GlProgram>::GlAttrId,
+        value: &[f32; 4],
+    );
+
     //mp program_set_uniform_mat4
     fn program_set_uniform_mat4(
         &mut self,
@@ -173,6 +525,17 @@ pub trait Gl:
         floats: &[f32],
     );
 
+    //mp program_set_uniform
+    /// Set a uniform of the program (which must be 'used') to a value
+    /// of any of the GLSL non-opaque types, if the program has that
+    /// uniform
+    fn program_set_uniform(
+        &mut self,
+        program: &Self::Program,
+        id: crate::UniformId,
+        value: &UniformValue,
+    );
+
     //mp program_bind_uniform_index
     fn program_bind_uniform_index(
         &mut self,
@@ -181,9 +544,20 @@ pub trait Gl:
         gl_uindex: u32,
     ) -> Result<(), ()>;
 
+    //mp program_bind_storage_index
+    /// Bind a program's storage block (by its storage buffer id) to a
+    /// shader storage buffer binding point
+    fn program_bind_storage_index(
+        &mut self,
+        program: &<Self as Gl>::Program,
+        storage_buffer_id: usize,
+        gl_index: u32,
+    ) -> Result<(), ()>;
+
     //mp program_use_texture
     /// Activate the required texture unit and set the program's
-    /// uniform to that unit, and bind the Gl texture to the unit
+    /// uniform to that unit, and bind the Gl texture to the unit (at
+    /// the target for the kind of the texture)
     ///
     /// The texture unit and uniform are specified by the program, and
     /// can be gathered from program and texture_id
@@ -196,8 +570,134 @@ pub trait Gl:
 
     //fp draw_primitive
     /// Draw the specified primitive using its VAO index into the vaos slice
+    ///
+    /// If the VAO has no indices then the primitive is drawn from the
+    /// vertex arrays, with its byte offset as the first vertex; a
+    /// primitive whose first vertex does not fit in an i32 is not drawn
     fn draw_primitive(&mut self, vaos: &[Self::Vao], primitive: &mod3d_base::Primitive);
 
+    //fp draw_primitives
+    /// Draw a run of primitives that share a VAO (that of the first
+    /// primitive) and primitive type
+    ///
+    /// If possible this is a single multi-draw call
+    /// (glMultiDrawElements or WEBGL_multi_draw); otherwise each
+    /// primitive is drawn in turn
+    fn draw_primitives(&mut self, vaos: &[Self::Vao], primitives: &[&mod3d_base::Primitive]);
+
+    //fp draw_vertices
+    /// Draw a range of vertices of a VAO (or of no VAO, for programs
+    /// that use only gl_VertexID) as a type of primitive, without
+    /// using any indices
+    fn draw_vertices(
+        &mut self,
+        vao: Option<&Self::Vao>,
+        primitive_type: mod3d_base::PrimitiveType,
+        first: usize,
+        count: usize,
+    );
+
+    //mp draw_indirect
+    /// Draw a number of [crate::DrawElementsIndirectCommand] from a
+    /// byte offset in a buffer, with a VAO (that must have indices)
+    /// and primitive type
+    ///
+    /// This is not supported by WebGL2, nor by OpenGL before 4.0 (or
+    /// 4.3 for more than one command); it returns an error if so
+    fn draw_indirect(
+        &mut self,
+        vao: &Self::Vao,
+        primitive_type: mod3d_base::PrimitiveType,
+        buffer: &<Self as Gl>::Buffer,
+        byte_offset: usize,
+        draw_count: usize,
+    ) -> Result<(), String>;
+
+    //mp transform_feedback_begin
+    /// Start capturing the feedback varyings of the program in use in
+    /// to a byte range of a buffer, for draws of primitives of a type;
+    /// if discard is true then the primitives are not rasterized
+    ///
+    /// OpenGL captures strips, fans and loops as separate lines or
+    /// triangles. WebGL2 requires draws to be of exactly the captured
+    /// type, so only points, lines and triangles may be captured
+    /// (others return an error), and the draws must not be indexed
+    fn transform_feedback_begin(
+        &mut self,
+        buffer: &<Self as Gl>::Buffer,
+        byte_offset: usize,
+        byte_length: usize,
+        primitive_type: mod3d_base::PrimitiveType,
+        discard: bool,
+    ) -> Result<(), String>;
+
+    //mp transform_feedback_end
+    /// Stop capturing feedback varyings, and re-enable rasterization
+    fn transform_feedback_end(&mut self);
+
+    //mp set_primitive_restart
+    /// Set the primitive restart of indexed primitives
+    ///
+    /// WebGL2 always uses [PrimitiveRestart::FixedIndex];
+    /// [PrimitiveRestart::Disabled] is a no-op there, and
+    /// [PrimitiveRestart::Index] returns an error
+    fn set_primitive_restart(&mut self, restart: PrimitiveRestart) -> Result<(), String>;
+
+    //mp set_point_size
+    /// Set the size in pixels of points drawn, or None if programs
+    /// set it with gl_PointSize
+    ///
+    /// The initial size is `Some(1.0)`, which matches the initial
+    /// OpenGL state (a point size of 1.0 with PROGRAM_POINT_SIZE
+    /// disabled)
+    ///
+    /// Programs with a [UniformId::PointSize] uniform have it set to
+    /// the size (or 1.0 if None) when drawn; WebGL only supports point
+    /// sizes set by programs, so WebGL programs that draw points should
+    /// use this uniform
+    fn set_point_size(&mut self, point_size: Option<f32>);
+
+    //ap point_size
+    /// Get the size in pixels of points drawn, if fixed
+    fn point_size(&self) -> Option<f32>;
+
+    //mp set_attribute_format
+    /// Set the format of integer data for a vertex attribute, or None
+    /// to use that of [AttributeFormat::of_vertex_attr]
+    ///
+    /// This only affects vertex data whose clients are created after
+    /// it is set
+    fn set_attribute_format(
+        &mut self,
+        vertex_attr: mod3d_base::VertexAttr,
+        format: Option<AttributeFormat>,
+    );
+
+    //ap attribute_format
+    /// Get the format of data of an element type for a vertex
+    /// attribute
+    fn attribute_format(
+        &self,
+        vertex_attr: mod3d_base::VertexAttr,
+        ele_type: mod3d_base::BufferElementType,
+    ) -> AttributeFormat;
+
     //fp bind_vao
     fn bind_vao(&mut self, vao: Option<&Self::Vao>);
+
+    //fp dispatch_compute
+    /// Use a compute program and dispatch a grid of work groups
+    ///
+    /// This fails if the backend does not support compute shaders
+    fn dispatch_compute(
+        &mut self,
+        program: &Self::Program,
+        num_groups: (u32, u32, u32),
+    ) -> Result<(), String>;
+
+    //fp memory_barrier
+    /// Order memory transactions issued by shaders (e.g. compute
+    /// shader writes) before those that follow, for the specified
+    /// kinds of access
+    fn memory_barrier(&mut self, barrier: MemoryBarrier);
 }
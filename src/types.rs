@@ -15,6 +15,9 @@ pub enum UniformId {
     /// The Material data uniform - once per model, and it may have
     /// many forms, but it must start with ShaderMaterialBaseData
     Material,
+    /// The point size uniform (a float), for programs that set
+    /// gl_PointSize - set from [crate::Gl::point_size] (or 1.0) for each draw
+    PointSize,
     /// Texure uniform - dependent on the program.
     Texture(TextureId),
     /// User uniform - dependent on the program.
@@ -34,12 +37,109 @@ impl std::str::FromStr for UniformId {
             "BoneScale" => BoneScale,
             "BoneMatrices" => BoneMatrices,
             "Material" => Material,
-            _ => Err(format!("Cannot interpret {s} as a UniformID"))?,
+            "PointSize" => PointSize,
+            _ => {
+                if let Some(n) = s.strip_prefix("User").and_then(|n| n.parse().ok()) {
+                    User(n)
+                } else if let Some(n) = s.strip_prefix("Buffer").and_then(|n| n.parse().ok()) {
+                    Buffer(n)
+                } else {
+                    Err(format!("Cannot interpret {s} as a UniformID"))?
+                }
+            }
         };
         Ok(v)
     }
 }
 
+//tp UniformValue
+/// A value for a uniform of any of the GLSL non-opaque types, for
+/// use with [crate::Gl::program_set_uniform]
+///
+/// Each variant is named for the GLSL type of the uniform, and holds
+/// a slice of data for one or more (for an array uniform) values of
+/// that type; the slice length must be a multiple of the number of
+/// components of the type. Matrices are column-major, unless the
+/// transpose flag is set (in which case they are row-major).
+#[derive(Debug, Clone, Copy)]
+pub enum UniformValue<'a> {
+    /// A float - one f32 per value
+    Float(&'a [f32]),
+    /// A vec2 - two f32 per value
+    Vec2(&'a [f32]),
+    /// A vec3 - three f32 per value
+    Vec3(&'a [f32]),
+    /// A vec4 - four f32 per value
+    Vec4(&'a [f32]),
+    /// An int (or bool) - one i32 per value
+    Int(&'a [i32]),
+    /// An ivec2 - two i32 per value
+    IVec2(&'a [i32]),
+    /// An ivec3 - three i32 per value
+    IVec3(&'a [i32]),
+    /// An ivec4 - four i32 per value
+    IVec4(&'a [i32]),
+    /// A uint - one u32 per value
+    UInt(&'a [u32]),
+    /// A uvec2 - two u32 per value
+    UVec2(&'a [u32]),
+    /// A uvec3 - three u32 per value
+    UVec3(&'a [u32]),
+    /// A uvec4 - four u32 per value
+    UVec4(&'a [u32]),
+    /// A mat2 - four f32 per value, and the transpose flag
+    Mat2(&'a [f32], bool),
+    /// A mat3 - nine f32 per value, and the transpose flag
+    Mat3(&'a [f32], bool),
+    /// A mat4 - sixteen f32 per value, and the transpose flag
+    Mat4(&'a [f32], bool),
+}
+
+//ip UniformValue
+impl UniformValue<'_> {
+    //mp glsl_type
+    /// The GLSL type of (each element of) the uniform
+    pub fn glsl_type(&self) -> crate::GlslType {
+        use crate::GlslType as T;
+        use UniformValue::*;
+        match self {
+            Float(_) => T::Float,
+            Vec2(_) => T::Vec2,
+            Vec3(_) => T::Vec3,
+            Vec4(_) => T::Vec4,
+            Int(_) => T::Int,
+            IVec2(_) => T::IVec2,
+            IVec3(_) => T::IVec3,
+            IVec4(_) => T::IVec4,
+            UInt(_) => T::UInt,
+            UVec2(_) => T::UVec2,
+            UVec3(_) => T::UVec3,
+            UVec4(_) => T::UVec4,
+            Mat2(..) => T::Mat2,
+            Mat3(..) => T::Mat3,
+            Mat4(..) => T::Mat4,
+        }
+    }
+
+    //mp data_len
+    /// The number of scalars in the data
+    pub fn data_len(&self) -> usize {
+        use UniformValue::*;
+        match self {
+            Float(d) | Vec2(d) | Vec3(d) | Vec4(d) => d.len(),
+            Int(d) | IVec2(d) | IVec3(d) | IVec4(d) => d.len(),
+            UInt(d) | UVec2(d) | UVec3(d) | UVec4(d) => d.len(),
+            Mat2(d, _) | Mat3(d, _) | Mat4(d, _) => d.len(),
+        }
+    }
+
+    //mp count
+    /// The number of values (array elements) in the data
+    pub fn count(&self) -> usize {
+        self.data_len() / (self.glsl_type().components() as usize)
+    }
+}
+
 //tp TextureId
 /// An enumeration of texures - that this crate particularly cares about
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
@@ -61,6 +161,18 @@ pub enum TextureId {
     MetallicRoughness,
     /// User 0
     User0,
+    /// An environment cube map (e.g. for a [crate::Skybox])
+    Environment,
+    /// The diffuse irradiance cube map of an environment, for
+    /// image-based lighting (see [crate::IblTextures])
+    Irradiance,
+    /// The prefiltered specular cube map of an environment, with
+    /// increasing roughness in each mipmap level, for image-based
+    /// lighting
+    Prefiltered,
+    /// The split-sum BRDF lookup texture for image-based lighting,
+    /// indexed by (N.V, roughness) and giving a scale and bias to F0
+    BrdfLut,
 }
 
 impl std::str::FromStr for TextureId {
@@ -74,6 +186,10 @@ impl std::str::FromStr for TextureId {
             "Emission" => Emission,
             "MetallicRoughness" => MetallicRoughness,
             "User0" => User0,
+            "Environment" => Environment,
+            "Irradiance" => Irradiance,
+            "Prefiltered" => Prefiltered,
+            "BrdfLut" => BrdfLut,
             _ => Err(format!("Cannot interpret {s} as a TextureId"))?,
         };
         Ok(v)
@@ -94,3 +210,214 @@ impl TextureId {
         }
     }
 }
+
+//tp MemoryBarrier
+/// A set of memory access kinds that a [crate::Gl::memory_barrier]
+/// orders shader writes before
+///
+/// These may be combined with '|'
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MemoryBarrier(u32);
+
+//ip MemoryBarrier
+impl MemoryBarrier {
+    /// Vertex attribute fetches from buffers
+    pub const VERTEX_ATTRIB_ARRAY: Self = Self(1 << 0);
+    /// Index fetches from element array buffers
+    pub const ELEMENT_ARRAY: Self = Self(1 << 1);
+    /// Uniform buffer reads
+    pub const UNIFORM: Self = Self(1 << 2);
+    /// Texture fetches
+    pub const TEXTURE_FETCH: Self = Self(1 << 3);
+    /// Shader image loads and stores
+    pub const SHADER_IMAGE_ACCESS: Self = Self(1 << 4);
+    /// Indirect draw and dispatch command reads
+    pub const COMMAND: Self = Self(1 << 5);
+    /// Buffer reads and writes by the GL (e.g. readback, sub-data updates)
+    pub const BUFFER_UPDATE: Self = Self(1 << 6);
+    /// Shader storage buffer accesses
+    pub const SHADER_STORAGE: Self = Self(1 << 7);
+    /// All kinds of access
+    pub const ALL: Self = Self(0xff);
+
+    //mp contains
+    /// Return true if all of the accesses in `other` are in this set
+    pub fn contains(&self, other: Self) -> bool {
+        (self.0 & other.0) == other.0
+    }
+}
+
+//ip BitOr for MemoryBarrier
+impl std::ops::BitOr for MemoryBarrier {
+    type Output = Self;
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+//tp BufferUsage
+/// The usage hint for the GPU copy of a buffer's data
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum BufferUsage {
+    /// Set once and drawn many times (STATIC_DRAW)
+    #[default]
+    Static,
+    /// Updated repeatedly and drawn many times (DYNAMIC_DRAW)
+    Dynamic,
+    /// Updated (or respecified) for each time it is drawn (STREAM_DRAW)
+    Stream,
+}
+
+//tp PrimitiveRestart
+/// Whether indexed strip (and loop and fan) primitives restart at a
+/// special index value
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PrimitiveRestart {
+    /// No index value restarts a primitive
+    #[default]
+    Disabled,
+    /// The maximum value of the index type (0xff, 0xffff or
+    /// 0xffffffff) restarts a primitive; this is always the case
+    /// for WebGL2
+    FixedIndex,
+    /// The given index value restarts a primitive (OpenGL only)
+    Index(u32),
+}
+
+//tp PixelFormat
+/// The format of pixels read back from a texture or framebuffer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    /// Four bytes per pixel (RGBA, UNSIGNED_BYTE)
+    #[default]
+    Rgba8,
+    /// Four 32-bit floats per pixel (RGBA, FLOAT), for float
+    /// textures and render targets
+    RgbaF32,
+}
+
+//ip PixelFormat
+impl PixelFormat {
+    //ap bytes_per_pixel
+    /// Get the number of bytes of each pixel
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            Self::Rgba8 => 4,
+            Self::RgbaF32 => 16,
+        }
+    }
+}
+
+//tp TextureKind
+/// The kind of a texture, and hence the GL target it is bound to
+///
+/// The depth of the texture data is the number of layers of an
+/// array texture, or the depth of a 3D texture; a 2D texture has a
+/// depth of zero. Cube maps are created from six square faces (see
+/// [crate::CubeMap])
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TextureKind {
+    /// A 2D texture (TEXTURE_2D), for a sampler2D
+    #[default]
+    Texture2D,
+    /// An array of 2D textures (TEXTURE_2D_ARRAY), for a sampler2DArray
+    Texture2DArray,
+    /// A volume texture (TEXTURE_3D), for a sampler3D
+    Texture3D,
+    /// A cube map (TEXTURE_CUBE_MAP), for a samplerCube
+    CubeMap,
+}
+
+//ip TextureKind
+impl TextureKind {
+    //ap has_depth
+    /// Return true if textures of the kind have a depth
+    pub fn has_depth(&self) -> bool {
+        matches!(self, Self::Texture2DArray | Self::Texture3D)
+    }
+
+    //mp check_depth
+    /// Check that texture data, which has a non-zero depth or not, is
+    /// valid for the kind
+    pub fn check_depth(&self, has_depth: bool) -> Result<(), String> {
+        if *self == Self::CubeMap {
+            Err("A cube map must be created from six faces".into())
+        } else if has_depth == self.has_depth() {
+            Ok(())
+        } else if has_depth {
+            Err(format!("Texture data with a depth cannot be a {self:?}"))
+        } else {
+            Err(format!("A {self:?} requires texture data with a depth"))
+        }
+    }
+}
+
+//tp AttributeKind
+/// How the elements of vertex attribute data are presented to a
+/// shader attribute; this only affects integer element types
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum AttributeKind {
+    /// Integer elements are converted directly to floats
+    #[default]
+    Float,
+    /// Integer elements are normalized to 0.0 to 1.0 if unsigned, or
+    /// -1.0 to 1.0 if signed
+    Normalized,
+    /// Integer elements are passed unconverted to an integer
+    /// attribute (int, ivecN, uint or uvecN)
+    Integer,
+}
+
+//tp AttributeFormat
+/// The interpretation of integer vertex attribute data: its
+/// signedness and how it is presented to the shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AttributeFormat {
+    /// How the elements are presented to the shader
+    pub kind: AttributeKind,
+    /// True if integer elements are signed
+    pub signed: bool,
+}
+
+//ip Default for AttributeFormat
+impl Default for AttributeFormat {
+    fn default() -> Self {
+        Self {
+            kind: AttributeKind::Float,
+            signed: true,
+        }
+    }
+}
+
+//ip AttributeFormat
+impl AttributeFormat {
+    //fp of_vertex_attr
+    /// Get the format of data of an element type for a vertex
+    /// attribute, as glTF (with KHR_mesh_quantization) defines it
+    ///
+    /// Float data is always presented as floats. For integer data,
+    /// joints are unsigned integers; weights, colors and texture
+    /// coordinates are unsigned normalized; normals and tangents are
+    /// signed normalized; other attributes (such as positions) are
+    /// converted to floats and assumed signed. A
+    /// [mod3d_base::BufferElementType] does not record signedness, so
+    /// meshes with other data should override the format with
+    /// [crate::Gl::set_attribute_format]
+    pub fn of_vertex_attr(
+        vertex_attr: mod3d_base::VertexAttr,
+        ele_type: mod3d_base::BufferElementType,
+    ) -> Self {
+        use mod3d_base::BufferElementType::{Float16, Float32};
+        use mod3d_base::VertexAttr::*;
+        if matches!(ele_type, Float16 | Float32) {
+            return Self::default();
+        }
+        let (kind, signed) = match vertex_attr {
+            Joints => (AttributeKind::Integer, false),
+            Weights | Color | TexCoords0 | TexCoords1 => (AttributeKind::Normalized, false),
+            Normal | Tangent => (AttributeKind::Normalized, true),
+            _ => (AttributeKind::Float, true),
+        };
+        Self { kind, signed }
+    }
+}
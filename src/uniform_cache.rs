@@ -0,0 +1,98 @@
+//a Imports
+use crate::{GlslType, UniformId, UniformValue};
+
+//a UniformStats
+//tp UniformStats
+/// Counters of the uniform uploads made for a program, and of those
+/// skipped because the value was unchanged
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UniformStats {
+    /// Number of uniform values uploaded to the GL
+    pub uploads: usize,
+    /// Number of uniform uploads skipped as the value was unchanged
+    pub skipped: usize,
+}
+
+//a UniformCache
+//tp UniformCache
+/// A shadow copy of the uniform values last uploaded to a program
+///
+/// The values are stored as the bit patterns of their scalars, so
+/// that comparison is exact (and NaN compares equal to itself)
+#[derive(Debug, Default)]
+pub struct UniformCache {
+    /// The type, transpose flag and scalar bits of each uniform set
+    values: Vec<(UniformId, GlslType, bool, Vec<u32>)>,
+    /// Upload counters
+    stats: UniformStats,
+}
+
+//fi update_bits
+/// Update the stored bits from data; return true if they changed
+fn update_bits<T: Copy, F: Fn(T) -> u32>(stored: &mut Vec<u32>, data: &[T], f: F) -> bool {
+    if stored.len() == data.len() && stored.iter().zip(data).all(|(s, d)| *s == f(*d)) {
+        false
+    } else {
+        stored.clear();
+        stored.extend(data.iter().map(|d| f(*d)));
+        true
+    }
+}
+
+//ip UniformCache
+impl UniformCache {
+    //mp update
+    /// Record a value for a uniform, returning true if it differs
+    /// from the value last recorded (and hence must be uploaded)
+    pub fn update(&mut self, uniform_id: UniformId, value: &UniformValue) -> bool {
+        use UniformValue::*;
+        let glsl_type = value.glsl_type();
+        let transpose = matches!(value, Mat2(_, true) | Mat3(_, true) | Mat4(_, true));
+        let index = {
+            if let Some(i) = self.values.iter().position(|(u, ..)| *u == uniform_id) {
+                i
+            } else {
+                self.values
+                    .push((uniform_id, glsl_type, transpose, Vec::new()));
+                self.values.len() - 1
+            }
+        };
+        let entry = &mut self.values[index];
+        let type_changed = entry.1 != glsl_type || entry.2 != transpose;
+        entry.1 = glsl_type;
+        entry.2 = transpose;
+        let stored = &mut entry.3;
+        let bits_changed = match value {
+            Float(d) | Vec2(d) | Vec3(d) | Vec4(d) => update_bits(stored, d, f32::to_bits),
+            Mat2(d, _) | Mat3(d, _) | Mat4(d, _) => update_bits(stored, d, f32::to_bits),
+            Int(d) | IVec2(d) | IVec3(d) | IVec4(d) => update_bits(stored, d, |i: i32| i as u32),
+            UInt(d) | UVec2(d) | UVec3(d) | UVec4(d) => update_bits(stored, d, |u: u32| u),
+        };
+        if type_changed || bits_changed {
+            self.stats.uploads += 1;
+            true
+        } else {
+            self.stats.skipped += 1;
+            false
+        }
+    }
+
+    //mp invalidate
+    /// Forget all recorded values, so that the next value for every
+    /// uniform is uploaded (e.g. after setting uniforms directly with the GL)
+    pub fn invalidate(&mut self) {
+        self.values.clear();
+    }
+
+    //ap stats
+    /// Get the upload counters
+    pub fn stats(&self) -> UniformStats {
+        self.stats
+    }
+
+    //mp reset_stats
+    /// Reset the upload counters
+    pub fn reset_stats(&mut self) {
+        self.stats = UniformStats::default();
+    }
+}
@@ -0,0 +1,368 @@
+//a Documentation
+
+/*!
+
+Validation of a [crate::ShaderProgramDesc] against the reflection of
+the program that it produced, and against the vertices that it will
+be used to draw
+
+!*/
+
+//a Imports
+use crate::{AttributeKind, GlslType, TextureId, UniformId};
+
+//a ValidationIssue
+//tp ValidationIssue
+/// A single problem found when validating a program
+#[derive(Debug, Clone)]
+pub enum ValidationIssue {
+    /// An active attribute of the program that the description does not map
+    UnmappedAttribute(String),
+    /// An active uniform of the program that the description does not map
+    UnmappedUniform(String),
+    /// An active uniform block of the program that the description does not map
+    UnmappedUniformBlock(String),
+    /// A name in the description that the shader interface does not
+    /// have; the first element is the kind of name (e.g. "attribute")
+    MissingName(&'static str, String),
+    /// A shader source that failed to parse or validate
+    ShaderError(String),
+    /// An attribute whose type in the shader cannot be set from the
+    /// data of the mesh's vertex buffer (integer data for a float
+    /// attribute, or float data for an integer attribute)
+    AttributeTypeMismatch {
+        name: String,
+        vertex_attr: mod3d_base::VertexAttr,
+        shader: GlslType,
+        mesh: AttributeKind,
+    },
+    /// An attribute whose component count in the shader differs from
+    /// that of the mesh's vertex buffer
+    AttributeComponentMismatch {
+        name: String,
+        vertex_attr: mod3d_base::VertexAttr,
+        shader: u32,
+        mesh: u32,
+    },
+    /// An attribute of the program that the mesh does not have; the
+    /// default value from the description is used if there is one
+    MissingAttribute {
+        name: String,
+        vertex_attr: mod3d_base::VertexAttr,
+        default: Option<[f32; 4]>,
+    },
+    /// A uniform whose type in the shader is not one that the
+    /// [UniformId] it is mapped to can be set with
+    UniformTypeMismatch {
+        name: String,
+        uniform_id: UniformId,
+        expected: &'static [GlslType],
+        found: GlslType,
+    },
+}
+
+//ip Display for ValidationIssue
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        use ValidationIssue::*;
+        match self {
+            UnmappedAttribute(name) => write!(f, "active attribute '{name}' is not mapped"),
+            UnmappedUniform(name) => write!(f, "active uniform '{name}' is not mapped"),
+            UnmappedUniformBlock(name) => {
+                write!(f, "active uniform block '{name}' is not mapped")
+            }
+            MissingName(kind, name) => {
+                write!(f, "{kind} '{name}' is not in the shader interface")
+            }
+            ShaderError(e) => write!(f, "{e}"),
+            AttributeTypeMismatch {
+                name,
+                vertex_attr,
+                shader,
+                mesh,
+            } => write!(
+                f,
+                "attribute '{name}' ({vertex_attr:?}) is a {shader} in the shader but the mesh has {mesh:?} data"
+            ),
+            AttributeComponentMismatch {
+                name,
+                vertex_attr,
+                shader,
+                mesh,
+            } => write!(
+                f,
+                "attribute '{name}' ({vertex_attr:?}) has {shader} components in the shader but {mesh} in the mesh"
+            ),
+            MissingAttribute {
+                name,
+                vertex_attr,
+                default: Some(d),
+            } => write!(
+                f,
+                "attribute '{name}' ({vertex_attr:?}) is not in the mesh; using default {d:?}"
+            ),
+            MissingAttribute {
+                name,
+                vertex_attr,
+                default: None,
+            } => write!(
+                f,
+                "attribute '{name}' ({vertex_attr:?}) is not in the mesh and has no default"
+            ),
+            UniformTypeMismatch {
+                name,
+                uniform_id,
+                expected,
+                found,
+            } => {
+                write!(f, "uniform '{name}' ({uniform_id:?}) is a {found}, expected ")?;
+                for (i, e) in expected.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " or ")?;
+                    }
+                    write!(f, "{e}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+//a ProgramValidation
+//tp ProgramValidation
+/// The result of validating a program: warnings are legal but
+/// probably unintended, errors will lead to GL errors or incorrect
+/// rendering
+#[derive(Debug, Clone, Default)]
+pub struct ProgramValidation {
+    /// Issues that are legal but probably unintended
+    pub warnings: Vec<ValidationIssue>,
+    /// Issues that will cause GL errors or incorrect rendering
+    pub errors: Vec<ValidationIssue>,
+}
+
+//ip ProgramValidation
+impl ProgramValidation {
+    //mp warn
+    /// Add a warning
+    pub fn warn(&mut self, issue: ValidationIssue) {
+        self.warnings.push(issue);
+    }
+
+    //mp error
+    /// Add an error
+    pub fn error(&mut self, issue: ValidationIssue) {
+        self.errors.push(issue);
+    }
+
+    //mp merge
+    /// Merge another validation result into this one
+    pub fn merge(&mut self, other: ProgramValidation) {
+        self.warnings.extend(other.warnings);
+        self.errors.extend(other.errors);
+    }
+
+    //mp is_ok
+    /// Return true if there are no errors (there may be warnings)
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    //mp is_clean
+    /// Return true if there are neither errors nor warnings
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty() && self.warnings.is_empty()
+    }
+}
+
+//ip Display for ProgramValidation
+impl std::fmt::Display for ProgramValidation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        for e in &self.errors {
+            writeln!(f, "error: {e}")?;
+        }
+        for w in &self.warnings {
+            writeln!(f, "warning: {w}")?;
+        }
+        Ok(())
+    }
+}
+
+//a Expected types
+//fp expected_uniform_types
+/// The GLSL types that a uniform mapped to a [UniformId] may have
+/// for the [crate::Gl] setters to be valid; None if any type is
+/// permitted
+pub fn expected_uniform_types(uniform_id: UniformId) -> Option<&'static [GlslType]> {
+    use UniformId::*;
+    match uniform_id {
+        ViewMatrix | ModelMatrix | MeshMatrix | BoneMatrices => Some(&[GlslType::Mat4]),
+        BoneScale | PointSize => Some(&[GlslType::Float]),
+        Material => Some(&[GlslType::Vec4]),
+        Texture(_) => Some(&[
+            GlslType::Sampler2D,
+            GlslType::Sampler3D,
+            GlslType::SamplerCube,
+            GlslType::Sampler2DShadow,
+            GlslType::Sampler2DArray,
+        ]),
+        User(_) | Buffer(_) => None,
+    }
+}
+
+//fp check_uniform_type
+/// Check the type of a uniform against that expected for a [UniformId]
+pub(crate) fn check_uniform_type(
+    validation: &mut ProgramValidation,
+    name: &str,
+    uniform_id: UniformId,
+    found: GlslType,
+) {
+    if let Some(expected) = expected_uniform_types(uniform_id) {
+        if !expected.contains(&found) {
+            validation.error(ValidationIssue::UniformTypeMismatch {
+                name: name.to_string(),
+                uniform_id,
+                expected,
+                found,
+            });
+        }
+    }
+}
+
+//fp check_texture_type
+/// Check the type of a texture sampler uniform
+pub(crate) fn check_texture_type(
+    validation: &mut ProgramValidation,
+    name: &str,
+    texture_id: TextureId,
+    found: GlslType,
+) {
+    check_uniform_type(validation, name, UniformId::Texture(texture_id), found)
+}
+
+//a Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expected_types() {
+        assert_eq!(
+            expected_uniform_types(UniformId::ViewMatrix),
+            Some(&[GlslType::Mat4][..])
+        );
+        assert_eq!(
+            expected_uniform_types(UniformId::PointSize),
+            Some(&[GlslType::Float][..])
+        );
+        assert_eq!(
+            expected_uniform_types(UniformId::Material),
+            Some(&[GlslType::Vec4][..])
+        );
+        let textures = expected_uniform_types(UniformId::Texture(TextureId::BaseColor)).unwrap();
+        assert!(textures.iter().all(|t| t.is_sampler()));
+        assert!(textures.contains(&GlslType::SamplerCube));
+        assert_eq!(expected_uniform_types(UniformId::User(0)), None);
+        assert_eq!(expected_uniform_types(UniformId::Buffer(1)), None);
+    }
+
+    #[test]
+    fn uniform_type_check() {
+        let mut validation = ProgramValidation::default();
+        check_uniform_type(
+            &mut validation,
+            "uModel",
+            UniformId::ModelMatrix,
+            GlslType::Mat4,
+        );
+        check_uniform_type(
+            &mut validation,
+            "uUser",
+            UniformId::User(3),
+            GlslType::IVec2,
+        );
+        check_texture_type(
+            &mut validation,
+            "uEnv",
+            TextureId::BaseColor,
+            GlslType::SamplerCube,
+        );
+        assert!(validation.is_clean());
+
+        check_uniform_type(
+            &mut validation,
+            "uView",
+            UniformId::ViewMatrix,
+            GlslType::Mat3,
+        );
+        check_texture_type(
+            &mut validation,
+            "uBase",
+            TextureId::BaseColor,
+            GlslType::Vec4,
+        );
+        assert!(!validation.is_ok());
+        assert!(validation.warnings.is_empty());
+        assert_eq!(validation.errors.len(), 2);
+        let ValidationIssue::UniformTypeMismatch {
+            name,
+            uniform_id,
+            expected,
+            found,
+        } = &validation.errors[0]
+        else {
+            panic!("Expected a uniform type mismatch");
+        };
+        assert_eq!(name, "uView");
+        assert_eq!(*uniform_id, UniformId::ViewMatrix);
+        assert_eq!(*expected, &[GlslType::Mat4][..]);
+        assert_eq!(*found, GlslType::Mat3);
+    }
+
+    #[test]
+    fn display() {
+        use mod3d_base::VertexAttr;
+        let mut validation = ProgramValidation::default();
+        validation.warn(ValidationIssue::UnmappedUniform("uTime".into()));
+        validation.warn(ValidationIssue::MissingAttribute {
+            name: "aColor".into(),
+            vertex_attr: VertexAttr::Color,
+            default: None,
+        });
+        validation.error(ValidationIssue::MissingName("uniform", "uFog".into()));
+        assert!(!validation.is_ok());
+
+        let mut other = ProgramValidation::default();
+        other.error(ValidationIssue::AttributeTypeMismatch {
+            name: "aJoints".into(),
+            vertex_attr: VertexAttr::Joints,
+            shader: GlslType::UVec4,
+            mesh: AttributeKind::Normalized,
+        });
+        other.warn(ValidationIssue::AttributeComponentMismatch {
+            name: "aPosition".into(),
+            vertex_attr: VertexAttr::Position,
+            shader: 4,
+            mesh: 3,
+        });
+        other.error(ValidationIssue::UniformTypeMismatch {
+            name: "uBase".into(),
+            uniform_id: UniformId::Texture(TextureId::BaseColor),
+            expected: &[GlslType::Sampler2D, GlslType::SamplerCube],
+            found: GlslType::Vec4,
+        });
+        validation.merge(other);
+
+        assert_eq!(
+            validation.to_string(),
+            "error: uniform 'uFog' is not in the shader interface\n\
+             error: attribute 'aJoints' (Joints) is a uvec4 in the shader but the mesh has Normalized data\n\
+             error: uniform 'uBase' (Texture(BaseColor)) is a vec4, expected sampler2D or samplerCube\n\
+             warning: active uniform 'uTime' is not mapped\n\
+             warning: attribute 'aColor' (Color) is not in the mesh and has no default\n\
+             warning: attribute 'aPosition' (Position) has 4 components in the shader but 3 in the mesh\n"
+        );
+        assert_eq!(ProgramValidation::default().to_string(), "");
+    }
+}
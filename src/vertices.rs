@@ -1,7 +1,18 @@
 //a Imports
 use std::rc::Rc;
 
-use crate::{Gl, IndexBuffer, VertexBuffer};
+use crate::{Gl, GlProgram, IndexBuffer, VertexBuffer};
+
+//a Functions
+//fp first_vertex
+/// Get the first vertex of a primitive of non-indexed vertices, which
+/// is its byte offset
+///
+/// This is None if it does not fit in the i32 that glDrawArrays (and
+/// glMultiDrawArrays) requires
+pub(crate) fn first_vertex(primitive: &mod3d_base::Primitive) -> Option<i32> {
+    i32::try_from(primitive.byte_offset()).ok()
+}
 
 //a Vertices
 //tp Vertices
@@ -10,12 +21,17 @@ use crate::{Gl, IndexBuffer, VertexBuffer};
 /// This is part of the [RenderContext], and so has a different
 /// lifetime to the model3d objects and vertices. It is created by
 /// invoking create_client on a [mod3d_rs::Object]
+///
+/// If the indices of the [mod3d_base::Vertices] are empty then the
+/// vertices are non-indexed (e.g. a point cloud), and primitives are
+/// drawn directly from the vertex arrays; the byte offset of such a
+/// primitive is then the index of its first vertex
 #[derive(Debug)]
 pub struct Vertices<G>
 where
     G: Gl,
 {
-    indices: Rc<IndexBuffer<G>>,
+    indices: Option<Rc<IndexBuffer<G>>>,
     position: Rc<VertexBuffer<G>>,
     attrs: Rc<Vec<(mod3d_base::VertexAttr, VertexBuffer<G>)>>,
 }
@@ -48,9 +64,9 @@ where
         let indices = vertices
             .borrow_indices()
             .borrow_client()
-            .as_index_buffer()
-            .clone()
-            .into();
+            .index_buffer()
+            .filter(|indices| indices.count > 0)
+            .map(|indices| indices.clone().into());
         let position = vertices
             .borrow_position()
             .borrow_client()
@@ -68,16 +84,40 @@ where
             attrs,
         }
     }
+    //ap is_indexed
+    /// Return true if the vertices have indices
+    pub fn is_indexed(&self) -> bool {
+        self.indices.is_some()
+    }
+
     //fp borrow
-    /// Borrow the indices, positions, and the array of other attributes
+    /// Borrow the indices (if any), positions, and the array of other attributes
     pub fn borrow(
         &self,
     ) -> (
-        &IndexBuffer<G>,
+        Option<&IndexBuffer<G>>,
         &VertexBuffer<G>,
         &Vec<(mod3d_base::VertexAttr, VertexBuffer<G>)>,
     ) {
-        (&self.indices, &self.position, &self.attrs)
+        (self.indices.as_deref(), &self.position, &self.attrs)
+    }
+
+    //mp missing_attributes
+    /// Get the attributes that a program has but these vertices do
+    /// not, and the default value (if any) the program uses for each
+    pub fn missing_attributes(
+        &self,
+        program: &<G as Gl>::Program,
+    ) -> Vec<(mod3d_base::VertexAttr, Option<[f32; 4]>)> {
+        program
+            .attributes()
+            .iter()
+            .map(|(_, va)| *va)
+            .filter(|va| {
+                *va != mod3d_base::VertexAttr::Position && !self.attrs.iter().any(|(a, _)| a == va)
+            })
+            .map(|va| (va, program.attribute_default(va)))
+            .collect()
     }
 }
 
@@ -87,7 +127,11 @@ where
     G: Gl,
 {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-        writeln!(fmt, "ind:{}", self.indices)?;
+        if let Some(indices) = &self.indices {
+            writeln!(fmt, "ind:{indices}")?;
+        } else {
+            writeln!(fmt, "ind:none")?;
+        }
         writeln!(fmt, "pos:{}", self.position)
     }
 }
@@ -99,7 +143,7 @@ where
 {
     /// Create a none
     fn default() -> Self {
-        let indices = IndexBuffer::default().into();
+        let indices = None;
         let position = VertexBuffer::default().into();
         let attrs = Vec::new().into();
         Self {
@@ -1,8 +1,11 @@
 //a Imports
 use crate::console_log;
 use crate::webgl_log::log_gl_vao;
-use crate::{Gl, GlProgram, GlShaderType, Mat4, UniformBuffer};
-use web_sys::WebGl2RenderingContext;
+use crate::{BufferPool, GlBuffer, ProgramReflection, StorageBuffer, UniformBuffer, UniformValue};
+use crate::{Gl, GlProgram, GlShaderType, GlslDialect, Mat4, MemoryBarrier, PrimitiveRestart};
+use crate::{ResourceKind, ResourceStats, ResourceTracker, TextureKind};
+use wasm_bindgen::JsCast;
+use web_sys::{WebGl2RenderingContext, WebGlFramebuffer, WebglMultiDraw};
 
 mod shader;
 pub use shader::Shader;
@@ -11,6 +14,8 @@ pub use program::Program;
 
 mod buffer;
 
+mod fence;
+
 mod texture;
 
 mod vao;
@@ -21,16 +26,162 @@ use vao::Vao;
 #[derive(Debug)]
 pub struct Model3DWebGL {
     context: WebGl2RenderingContext,
+    /// Optional pool from which the buffers of vertex data are allocated
+    buffer_pool: Option<BufferPool<Self>>,
+    /// The WEBGL_multi_draw extension, if supported
+    multi_draw: Option<WebglMultiDraw>,
+    /// Size of points drawn, set as the PointSize uniform of programs
+    point_size: Option<f32>,
+    /// Statistics of the buffers, textures, VAOs and programs created
+    resources: ResourceTracker,
+    /// Kind of texture created for model textures with a non-zero depth
+    depth_texture_kind: TextureKind,
+    /// If true, mipmaps are generated for model textures
+    texture_mipmaps: bool,
+    /// Formats of integer vertex data that override those of
+    /// [crate::AttributeFormat::of_vertex_attr]
+    attribute_formats: Vec<(mod3d_base::VertexAttr, crate::AttributeFormat)>,
+    /// The framebuffer used by [Gl::render_to_texture]
+    render_target: Option<WebGlFramebuffer>,
 }
 
 //ip Model3DWebGL
 impl Model3DWebGL {
     pub fn new(context: WebGl2RenderingContext) -> Self {
-        Self { context }
+        let multi_draw = context
+            .get_extension("WEBGL_multi_draw")
+            .ok()
+            .flatten()
+            .map(|extension| extension.unchecked_into::<WebglMultiDraw>());
+        Self {
+            context,
+            buffer_pool: None,
+            multi_draw,
+            point_size: Some(1.0),
+            resources: ResourceTracker::default(),
+            depth_texture_kind: TextureKind::Texture2DArray,
+            texture_mipmaps: false,
+            attribute_formats: Vec::new(),
+            render_target: None,
+        }
+    }
+
+    //mp set_texture_options
+    /// Set the kind of texture created for model textures whose data
+    /// has a non-zero depth ([TextureKind::Texture2DArray] or
+    /// [TextureKind::Texture3D]), and whether mipmaps are generated
+    /// for model textures
+    ///
+    /// This only affects textures whose clients are created after it
+    /// is set; other textures may be created with [Gl::texture_create]
+    pub fn set_texture_options(&mut self, depth_texture_kind: TextureKind, mipmaps: bool) {
+        self.depth_texture_kind = depth_texture_kind;
+        self.texture_mipmaps = mipmaps;
+    }
+
+    //ap stats
+    /// Get the statistics of the live GPU resources (buffers,
+    /// textures, VAOs and programs) created by this context, and
+    /// their peaks
+    pub fn stats(&self) -> ResourceStats {
+        self.resources.stats()
+    }
+
+    //mp reset_stats_peaks
+    /// Reset the peaks of the resource statistics to their current
+    /// values (e.g. before loading a model)
+    pub fn reset_stats_peaks(&self) {
+        self.resources.reset_peaks();
+    }
+
+    //mi track_buffer
+    /// Record the byte length of the storage of a buffer in the
+    /// resource statistics, as a new buffer or a respecification
+    fn track_buffer(&self, buffer: &mut buffer::Buffer, kind: ResourceKind, byte_length: usize) {
+        if let Some(tracked) = buffer.tracked() {
+            tracked.resize(byte_length);
+        } else {
+            buffer.set_tracked(self.resources.track(kind, byte_length));
+        }
+    }
+
+    //mp set_buffer_pool
+    /// Set a pool from which the buffers of vertex data are allocated
+    /// (rather than each having its own WebGL buffer), or None
+    ///
+    /// This only affects buffer data whose clients are created after
+    /// it is set
+    pub fn set_buffer_pool(&mut self, buffer_pool: Option<BufferPool<Self>>) {
+        self.buffer_pool = buffer_pool;
     }
+
     pub fn context(&self) -> &WebGl2RenderingContext {
         &self.context
     }
+
+    //fi gl_pixel_format
+    /// Get the WebGL format and type to read pixels as
+    fn gl_pixel_format(format: crate::PixelFormat) -> (u32, u32) {
+        match format {
+            crate::PixelFormat::Rgba8 => (
+                WebGl2RenderingContext::RGBA,
+                WebGl2RenderingContext::UNSIGNED_BYTE,
+            ),
+            crate::PixelFormat::RgbaF32 => {
+                (WebGl2RenderingContext::RGBA, WebGl2RenderingContext::FLOAT)
+            }
+        }
+    }
+
+    //fi texture_read_framebuffer
+    /// Create a framebuffer with a level of a texture as its color
+    /// attachment, and bind it as the read framebuffer
+    ///
+    /// Only 2D textures may be read
+    fn texture_read_framebuffer(
+        &self,
+        texture: &texture::Texture,
+        level: usize,
+    ) -> Result<WebGlFramebuffer, String> {
+        if texture.kind() != TextureKind::Texture2D {
+            return Err(format!("Cannot read a {:?}", texture.kind()));
+        }
+        let framebuffer = self
+            .create_framebuffer()
+            .ok_or_else(|| String::from("Unable to create framebuffer"))?;
+        self.bind_framebuffer(WebGl2RenderingContext::READ_FRAMEBUFFER, Some(&framebuffer));
+        self.framebuffer_texture_2d(
+            WebGl2RenderingContext::READ_FRAMEBUFFER,
+            WebGl2RenderingContext::COLOR_ATTACHMENT0,
+            WebGl2RenderingContext::TEXTURE_2D,
+            texture.gl_texture(),
+            level as i32,
+        );
+        Ok(framebuffer)
+    }
+
+    //fi end_texture_read
+    /// Delete a framebuffer from [Self::texture_read_framebuffer],
+    /// leaving the default read framebuffer bound
+    fn end_texture_read(&self, framebuffer: WebGlFramebuffer) {
+        self.bind_framebuffer(WebGl2RenderingContext::READ_FRAMEBUFFER, None);
+        self.delete_framebuffer(Some(&framebuffer));
+    }
+
+    //fi gl_primitive_type
+    /// Get the WebGL primitive type to draw a type of primitive as
+    fn gl_primitive_type(primitive_type: mod3d_base::PrimitiveType) -> u32 {
+        use mod3d_base::PrimitiveType::*;
+        match primitive_type {
+            Points => WebGl2RenderingContext::POINTS,
+            Lines => WebGl2RenderingContext::LINES,
+            LineLoop => WebGl2RenderingContext::LINE_LOOP,
+            LineStrip => WebGl2RenderingContext::LINE_STRIP,
+            Triangles => WebGl2RenderingContext::TRIANGLES,
+            TriangleFan => WebGl2RenderingContext::TRIANGLE_FAN,
+            TriangleStrip => WebGl2RenderingContext::TRIANGLE_STRIP,
+        }
+    }
 }
 
 //ip Deref for Model3DWebGL
@@ -49,18 +200,28 @@ impl Gl for Model3DWebGL {
     type Buffer = buffer::Buffer;
     type Vao = vao::Vao;
     type Texture = texture::Texture;
+    type Fence = fence::Fence;
 
     //fp link_program
     /// Create a program from a list of compiled shaders
+    #[allow(clippy::too_many_arguments)]
     fn link_program(
         &self,
         srcs: &[&Self::Shader],
         named_attrs: &[(&str, mod3d_base::VertexAttr)],
         named_uniforms: &[(&str, crate::UniformId)],
         named_uniform_buffers: &[(&str, usize)],
+        named_storage_buffers: &[(&str, usize)],
         named_textures: &[(&str, crate::TextureId, usize)],
+        feedback_varyings: &[&str],
     ) -> Result<Self::Program, String> {
-        let mut program = Program::link_program(&self.context, srcs)?;
+        if let Some((name, _)) = named_storage_buffers.first() {
+            return Err(format!(
+                "WebGL2 does not support shader storage blocks (such as {name})"
+            ));
+        }
+        let mut program = Program::link_program(&self.context, srcs, feedback_varyings)?;
+        program.set_tracked(self.resources.track(ResourceKind::Program, 0));
         for (name, attr) in named_attrs {
             program.add_attr_name(self, name, *attr)?;
         }
@@ -83,7 +244,18 @@ impl Gl for Model3DWebGL {
         shader_type: GlShaderType,
         source: &str,
     ) -> Result<Self::Shader, String> {
-        Shader::compile(&self.context, source, shader_type)
+        let source = crate::translate_glsl(source, shader_type, self.glsl_dialect());
+        Shader::compile(&self.context, &source, shader_type)
+    }
+
+    //fp glsl_dialect
+    fn glsl_dialect(&self) -> GlslDialect {
+        GlslDialect::Es300
+    }
+
+    //fp program_reflection
+    fn program_reflection(&self, program: &Self::Program) -> ProgramReflection {
+        program.reflection(&self.context)
     }
 
     //fp use_program
@@ -96,6 +268,13 @@ impl Gl for Model3DWebGL {
         }
     }
 
+    //mp share_index_buffers
+    /// WebGL does not permit a buffer to be used for both vertex
+    /// attributes and indices, so indices always have their own buffer
+    fn share_index_buffers(&self) -> bool {
+        false
+    }
+
     //mp init_buffer_of_indices
     fn init_buffer_of_indices(
         &mut self,
@@ -103,11 +282,368 @@ impl Gl for Model3DWebGL {
         view: &mod3d_base::BufferAccessor<Self>,
     ) {
         buffer.of_indices(view, self);
+        let byte_length = view.data.as_slice().len() - view.byte_offset as usize;
+        self.track_buffer(buffer, ResourceKind::IndexBuffer, byte_length);
+    }
+
+    //mp buffer_create
+    fn buffer_create(
+        &mut self,
+        byte_length: usize,
+        usage: crate::BufferUsage,
+    ) -> <Self as Gl>::Buffer {
+        let mut buffer = <Self as Gl>::Buffer::default();
+        buffer.create(self, byte_length, usage);
+        self.track_buffer(&mut buffer, ResourceKind::Buffer, byte_length);
+        buffer
+    }
+
+    //mp buffer_data_create
+    fn buffer_data_create(
+        &mut self,
+        buffer: &mut <Self as Gl>::Buffer,
+        data: &mod3d_base::BufferData<Self>,
+        usage: crate::BufferUsage,
+    ) {
+        buffer.of_data_with_usage(data, usage, self);
+        self.track_buffer(buffer, ResourceKind::VertexBuffer, data.as_slice().len());
+    }
+
+    //mp buffer_update_data
+    fn buffer_update_data(
+        &mut self,
+        buffer: &<Self as Gl>::Buffer,
+        data: &[u8],
+        byte_offset: usize,
+    ) {
+        buffer.update_data(self, data, byte_offset);
+    }
+
+    //mp buffer_read_data
+    /// This blocks until the GPU has finished writing the buffer
+    fn buffer_read_data(
+        &mut self,
+        buffer: &<Self as Gl>::Buffer,
+        byte_offset: usize,
+        data: &mut [u8],
+    ) -> Result<(), String> {
+        buffer.read_data(self, byte_offset, data);
+        Ok(())
+    }
+
+    //mp buffer_copy
+    fn buffer_copy(
+        &mut self,
+        src: &<Self as Gl>::Buffer,
+        src_byte_offset: usize,
+        dst: &<Self as Gl>::Buffer,
+        dst_byte_offset: usize,
+        byte_length: usize,
+    ) {
+        let src_byte_offset = src.byte_offset() as usize + src_byte_offset;
+        let dst_byte_offset = dst.byte_offset() as usize + dst_byte_offset;
+        src.bind_to_context_buffer(self, WebGl2RenderingContext::COPY_READ_BUFFER);
+        dst.bind_to_context_buffer(self, WebGl2RenderingContext::COPY_WRITE_BUFFER);
+        self.copy_buffer_sub_data_with_i32_and_i32_and_i32(
+            WebGl2RenderingContext::COPY_READ_BUFFER,
+            WebGl2RenderingContext::COPY_WRITE_BUFFER,
+            src_byte_offset as i32,
+            dst_byte_offset as i32,
+            byte_length as i32,
+        );
+        self.bind_buffer(WebGl2RenderingContext::COPY_READ_BUFFER, None);
+        self.bind_buffer(WebGl2RenderingContext::COPY_WRITE_BUFFER, None);
+    }
+
+    //ap buffer_can_map
+    /// WebGL2 cannot map buffers
+    fn buffer_can_map(&self) -> bool {
+        false
+    }
+
+    //mp buffer_write_unsynchronized
+    fn buffer_write_unsynchronized(
+        &mut self,
+        _buffer: &<Self as Gl>::Buffer,
+        _data: &[u8],
+        _byte_offset: usize,
+    ) -> Result<(), String> {
+        Err("WebGL2 cannot map buffers".into())
+    }
+
+    //mp buffer_orphan
+    fn buffer_orphan(
+        &mut self,
+        buffer: &<Self as Gl>::Buffer,
+        byte_length: usize,
+        usage: crate::BufferUsage,
+    ) {
+        buffer.orphan(self, byte_length, usage);
+        if let Some(tracked) = buffer.tracked() {
+            tracked.resize(byte_length);
+        }
+    }
+
+    //mp uniform_buffer_offset_alignment
+    fn uniform_buffer_offset_alignment(&self) -> usize {
+        self.context
+            .get_parameter(WebGl2RenderingContext::UNIFORM_BUFFER_OFFSET_ALIGNMENT)
+            .ok()
+            .and_then(|v| v.as_f64())
+            .map(|a| a as usize)
+            .unwrap_or(256)
+            .max(1)
+    }
+
+    //mp fence_insert
+    fn fence_insert(&mut self) -> Option<Self::Fence> {
+        fence::Fence::insert(&self.context)
+    }
+
+    //mp fence_wait
+    fn fence_wait(&mut self, fence: Self::Fence) -> Result<(), String> {
+        fence.wait(&self.context)
+    }
+
+    //mp fence_is_signalled
+    fn fence_is_signalled(&mut self, fence: &Self::Fence) -> bool {
+        fence.is_signalled(&self.context)
+    }
+
+    //mp texture_create
+    fn texture_create(
+        &mut self,
+        texture: &mod3d_base::Texture<Self>,
+        kind: TextureKind,
+        mipmaps: bool,
+    ) -> Result<<Self as Gl>::Texture, String> {
+        let mut client = texture::Texture::of_texture(texture, kind, mipmaps, self)?;
+        let byte_size = texture.data().len();
+        client.set_tracked(self.resources.track(ResourceKind::Texture, byte_size));
+        Ok(client)
+    }
+
+    //mp texture_create_cube
+    fn texture_create_cube(
+        &mut self,
+        size: usize,
+        channels: usize,
+        ele_type: mod3d_base::BufferElementType,
+        faces: [&[u8]; 6],
+        mipmaps: bool,
+    ) -> Result<<Self as Gl>::Texture, String> {
+        let mut client =
+            texture::Texture::of_cube_faces(size, channels, ele_type, faces, mipmaps, self)?;
+        let byte_size =
+            6 * size * size * channels * crate::buffer::ele_byte_size(ele_type) as usize;
+        client.set_tracked(self.resources.track(ResourceKind::Texture, byte_size));
+        Ok(client)
+    }
+
+    //mp texture_create_target
+    fn texture_create_target(
+        &mut self,
+        kind: TextureKind,
+        size: usize,
+        format: crate::PixelFormat,
+        levels: usize,
+    ) -> Result<<Self as Gl>::Texture, String> {
+        let mut client = texture::Texture::of_target(kind, size, format, levels, self)?;
+        let faces = if kind == TextureKind::CubeMap { 6 } else { 1 };
+        let texel_bytes = if format == crate::PixelFormat::Rgba8 {
+            4
+        } else {
+            8
+        };
+        let byte_size = (0..levels)
+            .map(|l| faces * (size >> l) * (size >> l) * texel_bytes)
+            .sum();
+        client.set_tracked(self.resources.track(ResourceKind::Texture, byte_size));
+        Ok(client)
+    }
+
+    //mp render_to_texture
+    /// When rendering returns to the default framebuffer the viewport
+    /// is set to the whole drawing buffer
+    fn render_to_texture(
+        &mut self,
+        target: Option<(&<Self as Gl>::Texture, usize, usize)>,
+    ) -> Result<(), String> {
+        let Some((texture, level, face)) = target else {
+            if let Some(framebuffer) = self.render_target.take() {
+                self.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+                self.delete_framebuffer(Some(&framebuffer));
+                self.viewport(
+                    0,
+                    0,
+                    self.drawing_buffer_width(),
+                    self.drawing_buffer_height(),
+                );
+            }
+            return Ok(());
+        };
+        let tex_target = match texture.kind() {
+            TextureKind::Texture2D if face == 0 => WebGl2RenderingContext::TEXTURE_2D,
+            TextureKind::CubeMap if face < 6 => {
+                WebGl2RenderingContext::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32
+            }
+            kind => {
+                return Err(format!("Cannot render to face {face} of a {kind:?}"));
+            }
+        };
+        let (width, height) = texture.size(level);
+        if self.render_target.is_none() {
+            let framebuffer = self
+                .create_framebuffer()
+                .ok_or_else(|| String::from("Unable to create framebuffer"))?;
+            self.render_target = Some(framebuffer);
+        }
+        self.bind_framebuffer(
+            WebGl2RenderingContext::FRAMEBUFFER,
+            self.render_target.as_ref(),
+        );
+        self.framebuffer_texture_2d(
+            WebGl2RenderingContext::FRAMEBUFFER,
+            WebGl2RenderingContext::COLOR_ATTACHMENT0,
+            tex_target,
+            texture.gl_texture(),
+            level as i32,
+        );
+        self.viewport(0, 0, width as i32, height as i32);
+        let status = self.check_framebuffer_status(WebGl2RenderingContext::FRAMEBUFFER);
+        if status != WebGl2RenderingContext::FRAMEBUFFER_COMPLETE {
+            return Err(format!(
+                "Render target framebuffer is incomplete ({status:#x})"
+            ));
+        }
+        Ok(())
+    }
+
+    //mp texture_size
+    fn texture_size(&mut self, texture: &<Self as Gl>::Texture, level: usize) -> (usize, usize) {
+        texture.size(level)
+    }
+
+    //mp texture_read
+    /// WebGL cannot read textures directly, so the texture level is
+    /// attached to a temporary framebuffer which is read
+    fn texture_read(
+        &mut self,
+        texture: &<Self as Gl>::Texture,
+        level: usize,
+        format: crate::PixelFormat,
+        data: &mut [u8],
+    ) -> Result<(), String> {
+        let (width, height) = texture.size(level);
+        let framebuffer = self.texture_read_framebuffer(texture, level)?;
+        let result = self.read_pixels(0, 0, width, height, format, data);
+        self.end_texture_read(framebuffer);
+        result
+    }
+
+    //mp texture_read_to_buffer
+    fn texture_read_to_buffer(
+        &mut self,
+        texture: &<Self as Gl>::Texture,
+        level: usize,
+        format: crate::PixelFormat,
+        buffer: &<Self as Gl>::Buffer,
+    ) -> Result<(), String> {
+        let (width, height) = texture.size(level);
+        let framebuffer = self.texture_read_framebuffer(texture, level)?;
+        let result = self.read_pixels_to_buffer(0, 0, width, height, format, buffer);
+        self.end_texture_read(framebuffer);
+        result
+    }
+
+    //mp read_pixels
+    fn read_pixels(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        format: crate::PixelFormat,
+        data: &mut [u8],
+    ) -> Result<(), String> {
+        let byte_length = width * height * format.bytes_per_pixel();
+        if data.len() < byte_length {
+            return Err(format!(
+                "Buffer of {} bytes too small for {width}x{height} pixels",
+                data.len()
+            ));
+        }
+        let (gl_format, gl_type) = Self::gl_pixel_format(format);
+        self.pixel_storei(WebGl2RenderingContext::PACK_ALIGNMENT, 1);
+        let result = match format {
+            crate::PixelFormat::Rgba8 => self.context.read_pixels_with_opt_u8_array(
+                x as i32,
+                y as i32,
+                width as i32,
+                height as i32,
+                gl_format,
+                gl_type,
+                Some(&mut data[0..byte_length]),
+            ),
+            crate::PixelFormat::RgbaF32 => {
+                // Float pixels must be read in to a Float32Array
+                let floats = js_sys::Float32Array::new_with_length((byte_length / 4) as u32);
+                let result = self.context.read_pixels_with_opt_array_buffer_view(
+                    x as i32,
+                    y as i32,
+                    width as i32,
+                    height as i32,
+                    gl_format,
+                    gl_type,
+                    Some(&floats),
+                );
+                for (d, f) in data.chunks_exact_mut(4).zip(floats.to_vec()) {
+                    d.copy_from_slice(&f.to_ne_bytes());
+                }
+                result
+            }
+        };
+        result.map_err(|e| format!("Read pixels failed: {e:?}"))
+    }
+
+    //mp read_pixels_to_buffer
+    fn read_pixels_to_buffer(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        format: crate::PixelFormat,
+        buffer: &<Self as Gl>::Buffer,
+    ) -> Result<(), String> {
+        let (gl_format, gl_type) = Self::gl_pixel_format(format);
+        self.pixel_storei(WebGl2RenderingContext::PACK_ALIGNMENT, 1);
+        buffer.bind_to_context_buffer(self, WebGl2RenderingContext::PIXEL_PACK_BUFFER);
+        let result = self.context.read_pixels_with_i32(
+            x as i32,
+            y as i32,
+            width as i32,
+            height as i32,
+            gl_format,
+            gl_type,
+            buffer.byte_offset() as i32,
+        );
+        self.bind_buffer(WebGl2RenderingContext::PIXEL_PACK_BUFFER, None);
+        result.map_err(|e| format!("Read pixels failed: {e:?}"))
     }
 
     //mp vao_create_from_indices
     fn vao_create_from_indices(&mut self, indices: &crate::IndexBuffer<Self>) -> Result<Vao, ()> {
-        Vao::create_from_indices(self, indices)
+        let mut vao = Vao::create_from_indices(self, indices)?;
+        vao.set_tracked(self.resources.track(ResourceKind::Vao, 0));
+        Ok(vao)
+    }
+
+    //mp vao_create
+    fn vao_create(&mut self) -> Result<Vao, ()> {
+        let mut vao = Vao::create(self)?;
+        vao.set_tracked(self.resources.track(ResourceKind::Vao, 0));
+        Ok(vao)
     }
 
     //mp buffer_bind_to_vao_attr
@@ -117,16 +653,25 @@ impl Gl for Model3DWebGL {
         attr_id: &<Program as GlProgram>::GlAttrId,
         count: u32,
         ele_type: mod3d_base::BufferElementType,
+        format: crate::AttributeFormat,
         byte_offset: u32,
         stride: u32,
     ) {
-        buffer.bind_to_vao_attr(self, *attr_id, count, ele_type, byte_offset, stride);
+        buffer.bind_to_vao_attr(self, *attr_id, count, ele_type, format, byte_offset, stride);
+    }
+
+    //mp attr_set_default
+    fn attr_set_default(&mut self, attr_id: &<Program as GlProgram>::GlAttrId, value: &[f32; 4]) {
+        self.vertex_attrib4fv_with_f32_array(*attr_id, value);
     }
 
     //mp program_set_uniform_mat4
     fn program_set_uniform_mat4(&mut self, program: &Program, id: crate::UniformId, mat4: &Mat4) {
         console_log!("program_set_uniform_mat4: {:?} {:?}", id, mat4);
         if let Some(u) = program.uniform(id) {
+            if !program.uniform_changed(id, &UniformValue::Mat4(mat4, false)) {
+                return;
+            }
             self.context
                 .uniform_matrix4fv_with_f32_array(Some(u), false, mat4);
         }
@@ -141,10 +686,48 @@ impl Gl for Model3DWebGL {
     ) {
         console_log!("webgl: set uniform [vec4] {id:?} {floats:?}");
         if let Some(u) = program.uniform(id) {
+            if !program.uniform_changed(id, &UniformValue::Vec4(floats)) {
+                return;
+            }
             self.context.uniform4fv_with_f32_array(Some(u), floats);
         }
     }
 
+    //mp program_set_uniform
+    fn program_set_uniform(
+        &mut self,
+        program: &Self::Program,
+        id: crate::UniformId,
+        value: &UniformValue,
+    ) {
+        let Some(u) = program.uniform(id) else {
+            return;
+        };
+        if !program.uniform_changed(id, value) {
+            return;
+        }
+        let u = Some(u);
+        let c = &self.context;
+        use UniformValue::*;
+        match value {
+            Float(d) => c.uniform1fv_with_f32_array(u, d),
+            Vec2(d) => c.uniform2fv_with_f32_array(u, d),
+            Vec3(d) => c.uniform3fv_with_f32_array(u, d),
+            Vec4(d) => c.uniform4fv_with_f32_array(u, d),
+            Int(d) => c.uniform1iv_with_i32_array(u, d),
+            IVec2(d) => c.uniform2iv_with_i32_array(u, d),
+            IVec3(d) => c.uniform3iv_with_i32_array(u, d),
+            IVec4(d) => c.uniform4iv_with_i32_array(u, d),
+            UInt(d) => c.uniform1uiv_with_u32_array(u, d),
+            UVec2(d) => c.uniform2uiv_with_u32_array(u, d),
+            UVec3(d) => c.uniform3uiv_with_u32_array(u, d),
+            UVec4(d) => c.uniform4uiv_with_u32_array(u, d),
+            Mat2(d, t) => c.uniform_matrix2fv_with_f32_array(u, *t, d),
+            Mat3(d, t) => c.uniform_matrix3fv_with_f32_array(u, *t, d),
+            Mat4(d, t) => c.uniform_matrix4fv_with_f32_array(u, *t, d),
+        }
+    }
+
     //mp program_bind_uniform_index
     fn program_bind_uniform_index(
         &mut self,
@@ -161,6 +744,17 @@ impl Gl for Model3DWebGL {
         }
     }
 
+    //mp program_bind_storage_index
+    /// WebGL2 does not support storage buffers
+    fn program_bind_storage_index(
+        &mut self,
+        _program: &<Self as Gl>::Program,
+        _storage_buffer_id: usize,
+        _gl_index: u32,
+    ) -> Result<(), ()> {
+        Err(())
+    }
+
     //mp program_use_texture
     /// Requires the program to be 'used'
     fn program_use_texture(
@@ -174,7 +768,7 @@ impl Gl for Model3DWebGL {
             self.context
                 .active_texture(WebGl2RenderingContext::TEXTURE0 + unit);
             self.context
-                .bind_texture(WebGl2RenderingContext::TEXTURE_2D, gl_texture.gl_texture());
+                .bind_texture(gl_texture.target(), gl_texture.gl_texture());
             self.context.uniform1i(Some(u), unit as i32);
         }
     }
@@ -182,34 +776,203 @@ impl Gl for Model3DWebGL {
     //mp draw_primitive
     fn draw_primitive(&mut self, vaos: &[Vao], primitive: &mod3d_base::Primitive) {
         console_log!("webgl: draw_primitive {primitive:?}");
-        use mod3d_base::PrimitiveType::*;
-        let gl_type = match primitive.primitive_type() {
-            Points => WebGl2RenderingContext::POINTS,
-            Lines => WebGl2RenderingContext::LINES,
-            LineLoop => WebGl2RenderingContext::LINE_LOOP,
-            LineStrip => WebGl2RenderingContext::LINE_STRIP,
-            Triangles => WebGl2RenderingContext::TRIANGLES,
-            TriangleFan => WebGl2RenderingContext::TRIANGLE_FAN,
-            TriangleStrip => WebGl2RenderingContext::TRIANGLE_STRIP,
-        };
+        let gl_type = Self::gl_primitive_type(primitive.primitive_type());
         let opt_vertices_index: Option<usize> = primitive.vertices_index().into();
         if let Some(vertices_index) = opt_vertices_index {
-            let index_type = vaos[vertices_index].bind_vao(self);
-            self.draw_elements_with_i32(
+            let vao = &vaos[vertices_index];
+            let index_type = vao.bind_vao(self);
+            if vao.indexed() {
+                let byte_offset = vao.index_byte_offset() + primitive.byte_offset() as usize;
+                self.draw_elements_with_i32(
+                    gl_type,
+                    primitive.index_count() as i32,
+                    index_type,
+                    byte_offset as i32,
+                );
+            } else if let Some(first) = crate::vertices::first_vertex(primitive) {
+                self.draw_arrays(gl_type, first, primitive.index_count() as i32);
+            }
+        } else if let Some(first) = crate::vertices::first_vertex(primitive) {
+            self.draw_arrays(gl_type, first, primitive.index_count() as i32);
+        }
+    }
+
+    //mp draw_primitives
+    /// This uses the WEBGL_multi_draw extension if it is available
+    fn draw_primitives(&mut self, vaos: &[Vao], primitives: &[&mod3d_base::Primitive]) {
+        let opt_vertices_index: Option<usize> =
+            primitives.first().and_then(|p| p.vertices_index().into());
+        let (Some(vertices_index), Some(multi_draw)) = (opt_vertices_index, &self.multi_draw)
+        else {
+            for p in primitives {
+                self.draw_primitive(vaos, p);
+            }
+            return;
+        };
+        let gl_type = Self::gl_primitive_type(primitives[0].primitive_type());
+        let vao = &vaos[vertices_index];
+        let index_type = vao.bind_vao(self);
+        let mut counts: Vec<i32> = primitives.iter().map(|p| p.index_count() as i32).collect();
+        if vao.indexed() {
+            let mut offsets: Vec<i32> = primitives
+                .iter()
+                .map(|p| (vao.index_byte_offset() + p.byte_offset() as usize) as i32)
+                .collect();
+            multi_draw.multi_draw_elements_webgl_with_i32_array_and_i32_array(
                 gl_type,
-                primitive.index_count() as i32,
+                &mut counts,
+                0,
                 index_type,
-                primitive.byte_offset() as i32,
+                &mut offsets,
+                0,
+                primitives.len() as i32,
             );
         } else {
-            self.draw_arrays(
+            let firsts: Option<Vec<i32>> = primitives
+                .iter()
+                .map(|p| crate::vertices::first_vertex(p))
+                .collect();
+            let Some(mut firsts) = firsts else {
+                for p in primitives {
+                    self.draw_primitive(vaos, p);
+                }
+                return;
+            };
+            multi_draw.multi_draw_arrays_webgl_with_i32_array_and_i32_array(
                 gl_type,
-                primitive.byte_offset() as i32,
-                primitive.index_count() as i32,
+                &mut firsts,
+                0,
+                &mut counts,
+                0,
+                primitives.len() as i32,
             );
         }
     }
 
+    //mp draw_vertices
+    fn draw_vertices(
+        &mut self,
+        vao: Option<&Vao>,
+        primitive_type: mod3d_base::PrimitiveType,
+        first: usize,
+        count: usize,
+    ) {
+        self.bind_vao(vao);
+        let gl_type = Self::gl_primitive_type(primitive_type);
+        self.draw_arrays(gl_type, first as i32, count as i32);
+    }
+
+    //mp draw_indirect
+    /// WebGL2 does not support indirect drawing
+    fn draw_indirect(
+        &mut self,
+        _vao: &Vao,
+        _primitive_type: mod3d_base::PrimitiveType,
+        _buffer: &<Self as Gl>::Buffer,
+        _byte_offset: usize,
+        _draw_count: usize,
+    ) -> Result<(), String> {
+        Err("WebGL2 does not support indirect drawing".into())
+    }
+
+    //mp transform_feedback_begin
+    /// WebGL2 requires the mode of each draw to be the same as that
+    /// captured, and does not permit indexed draws while capturing;
+    /// strips, fans and loops cannot be captured
+    fn transform_feedback_begin(
+        &mut self,
+        buffer: &<Self as Gl>::Buffer,
+        byte_offset: usize,
+        byte_length: usize,
+        primitive_type: mod3d_base::PrimitiveType,
+        discard: bool,
+    ) -> Result<(), String> {
+        use mod3d_base::PrimitiveType::*;
+        let gl_type = match primitive_type {
+            Points => WebGl2RenderingContext::POINTS,
+            Lines => WebGl2RenderingContext::LINES,
+            Triangles => WebGl2RenderingContext::TRIANGLES,
+            _ => {
+                return Err(format!(
+                    "WebGL2 transform feedback cannot capture {primitive_type:?}"
+                ));
+            }
+        };
+        let byte_offset = buffer.byte_offset() as usize + byte_offset;
+        buffer.bind_buffer_range(
+            self,
+            WebGl2RenderingContext::TRANSFORM_FEEDBACK_BUFFER,
+            0,
+            byte_offset as i32,
+            byte_length as i32,
+        );
+        if discard {
+            self.enable(WebGl2RenderingContext::RASTERIZER_DISCARD);
+        }
+        self.begin_transform_feedback(gl_type);
+        Ok(())
+    }
+
+    //mp transform_feedback_end
+    fn transform_feedback_end(&mut self) {
+        self.end_transform_feedback();
+        self.disable(WebGl2RenderingContext::RASTERIZER_DISCARD);
+        // WebGL does not permit a buffer to be bound for transform
+        // feedback and for another use at the same time
+        self.bind_buffer_base(WebGl2RenderingContext::TRANSFORM_FEEDBACK_BUFFER, 0, None);
+    }
+
+    //mp set_primitive_restart
+    /// WebGL2 always restarts at the fixed index and it cannot be
+    /// turned off; [PrimitiveRestart::Disabled] is accepted as a no-op,
+    /// as meshes that do not use the maximum index are unaffected
+    fn set_primitive_restart(&mut self, restart: PrimitiveRestart) -> Result<(), String> {
+        if let PrimitiveRestart::Index(_) = restart {
+            Err(format!(
+                "WebGL2 only supports primitive restart at a fixed index, not {restart:?}"
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    //mp set_point_size
+    /// WebGL has no fixed point size; it is only recorded, for the
+    /// PointSize uniform
+    fn set_point_size(&mut self, point_size: Option<f32>) {
+        self.point_size = point_size;
+    }
+
+    //ap point_size
+    fn point_size(&self) -> Option<f32> {
+        self.point_size
+    }
+
+    //mp set_attribute_format
+    fn set_attribute_format(
+        &mut self,
+        vertex_attr: mod3d_base::VertexAttr,
+        format: Option<crate::AttributeFormat>,
+    ) {
+        self.attribute_formats.retain(|(va, _)| *va != vertex_attr);
+        if let Some(format) = format {
+            self.attribute_formats.push((vertex_attr, format));
+        }
+    }
+
+    //ap attribute_format
+    fn attribute_format(
+        &self,
+        vertex_attr: mod3d_base::VertexAttr,
+        ele_type: mod3d_base::BufferElementType,
+    ) -> crate::AttributeFormat {
+        self.attribute_formats
+            .iter()
+            .find(|(va, _)| *va == vertex_attr)
+            .map(|(_, format)| *format)
+            .unwrap_or_else(|| crate::AttributeFormat::of_vertex_attr(vertex_attr, ele_type))
+    }
+
     //mp bind_vao
     fn bind_vao(&mut self, vao: Option<&Self::Vao>) {
         if let Some(vao) = vao {
@@ -220,6 +983,20 @@ impl Gl for Model3DWebGL {
         }
     }
 
+    //mp dispatch_compute
+    /// WebGL2 does not support compute shaders
+    fn dispatch_compute(
+        &mut self,
+        _program: &Self::Program,
+        _num_groups: (u32, u32, u32),
+    ) -> Result<(), String> {
+        Err("Compute shaders are not supported by WebGL2".into())
+    }
+
+    //mp memory_barrier
+    /// WebGL2 has no shader writes to order, so this does nothing
+    fn memory_barrier(&mut self, _barrier: MemoryBarrier) {}
+
     //mp uniform_buffer_create
     fn uniform_buffer_create<F: Sized>(
         &mut self,
@@ -229,6 +1006,7 @@ impl Gl for Model3DWebGL {
         let byte_length = std::mem::size_of_val(data);
         let mut gl = buffer::Buffer::default();
         gl.uniform_buffer(self, data, is_dynamic)?;
+        self.track_buffer(&mut gl, ResourceKind::UniformBuffer, byte_length);
         Ok(UniformBuffer::new(gl, byte_length))
     }
 
@@ -261,6 +1039,27 @@ impl Gl for Model3DWebGL {
             byte_length as i32,
         );
     }
+
+    //mp storage_buffer_create
+    /// WebGL2 does not support storage buffers
+    fn storage_buffer_create(
+        &mut self,
+        _data: &[u8],
+        _usage: crate::BufferUsage,
+    ) -> Result<StorageBuffer<Self>, String> {
+        Err("WebGL2 does not support shader storage buffers".into())
+    }
+
+    //mp storage_index_of_range
+    /// WebGL2 does not support storage buffers, so none can exist to bind
+    fn storage_index_of_range(
+        &mut self,
+        _storage_buffer: &StorageBuffer<Self>,
+        _gl_index: u32,
+        _byte_offset: usize,
+        _byte_length: usize,
+    ) {
+    }
 }
 
 //ip mod3d_base::Renderable for Model3DWebGL
@@ -282,7 +1081,20 @@ impl mod3d_base::Renderable for Model3DWebGL {
         buffer_data: &mod3d_base::BufferData<Self>,
     ) {
         if client.is_none() {
-            client.of_data(buffer_data, self)
+            if let Some(mut buffer_pool) = self.buffer_pool.take() {
+                if let Some(buffer) = buffer_pool.allocate(self, buffer_data.as_slice()) {
+                    *client = buffer;
+                }
+                self.buffer_pool = Some(buffer_pool);
+            }
+        }
+        if client.is_none() {
+            client.of_data(buffer_data, self);
+            self.track_buffer(
+                client,
+                ResourceKind::VertexBuffer,
+                buffer_data.as_slice().len(),
+            );
         }
     }
 
@@ -303,8 +1115,21 @@ impl mod3d_base::Renderable for Model3DWebGL {
     }
 
     //mp create_texture_client
+    /// Create the client texture; if this fails the error is logged
+    /// and an empty texture is used, which samples as black
     fn create_texture_client(&mut self, texture: &mod3d_base::Texture<Self>) -> Self::Texture {
-        Self::Texture::of_texture(texture, self) // , self)
+        let kind = {
+            if texture.dims().2 == 0 {
+                TextureKind::Texture2D
+            } else {
+                self.depth_texture_kind
+            }
+        };
+        self.texture_create(texture, kind, self.texture_mipmaps)
+            .unwrap_or_else(|e| {
+                console_log!("Failed to create texture client: {e}");
+                texture::Texture::default()
+            })
     }
 
     //mp init_material_client
@@ -7,9 +7,38 @@ use mod3d_base::{BufferClient, BufferData};
 use crate::webgl_log::log_gl_buffer;
 
 use super::{Model3DWebGL, Program};
-use crate::GlProgram;
+use crate::TrackedResource;
+use crate::{AttributeFormat, AttributeKind, BufferUsage, GlProgram, PoolAllocation};
 use web_sys::{WebGl2RenderingContext, WebGlBuffer};
 
+//a Functions
+//fi gl_usage
+/// Get the WebGL usage hint for a [BufferUsage]
+fn gl_usage(usage: BufferUsage) -> u32 {
+    match usage {
+        BufferUsage::Static => WebGl2RenderingContext::STATIC_DRAW,
+        BufferUsage::Dynamic => WebGl2RenderingContext::DYNAMIC_DRAW,
+        BufferUsage::Stream => WebGl2RenderingContext::STREAM_DRAW,
+    }
+}
+
+//fi gl_ele_type
+/// Get the WebGL type for vertex attribute elements, and whether it
+/// is an integer type
+fn gl_ele_type(ele_type: mod3d_base::BufferElementType, signed: bool) -> (u32, bool) {
+    use mod3d_base::BufferElementType::*;
+    match (ele_type, signed) {
+        (Float32, _) => (WebGl2RenderingContext::FLOAT, false),
+        (Float16, _) => (WebGl2RenderingContext::HALF_FLOAT, false),
+        (Int8, true) => (WebGl2RenderingContext::BYTE, true),
+        (Int8, false) => (WebGl2RenderingContext::UNSIGNED_BYTE, true),
+        (Int16, true) => (WebGl2RenderingContext::SHORT, true),
+        (Int16, false) => (WebGl2RenderingContext::UNSIGNED_SHORT, true),
+        (Int32, true) => (WebGl2RenderingContext::INT, true),
+        (Int32, false) => (WebGl2RenderingContext::UNSIGNED_INT, true),
+    }
+}
+
 //a Buffer
 //tp Buffer
 /// A simple structure provides a reference-counted OpenGl buffer;
@@ -26,17 +55,33 @@ use web_sys::{WebGl2RenderingContext, WebGlBuffer};
 /// the buffer in this case must be an OpenGL ELEMENT_ARRAY_BUFFER;
 /// this could perhaps be optimized to reduce the number of OpenGL
 /// buffers with much more code.
+///
+/// A buffer allocated from a [crate::BufferPool] shares the WebGL
+/// buffer of a pool block, and its data starts at a byte offset
 #[derive(Debug, Clone)]
 pub struct Buffer {
     /// The WebGL Buffer if this has been set
     gl: Rc<RefCell<Option<WebGlBuffer>>>,
+    /// Byte offset of the data in the WebGL buffer
+    byte_offset: u32,
+    /// The pool allocation, if the buffer is from a pool; this is
+    /// released when the last clone is dropped
+    allocation: Option<Rc<PoolAllocation>>,
+    /// The record of the WebGL buffer in the resource statistics,
+    /// shared by all clones
+    tracked: Option<Rc<TrackedResource>>,
 }
 
 //ip Default for Buffer
 impl Default for Buffer {
     fn default() -> Self {
         let gl = Rc::new(RefCell::new(None));
-        Self { gl }
+        Self {
+            gl,
+            byte_offset: 0,
+            allocation: None,
+            tracked: None,
+        }
     }
 }
 
@@ -52,6 +97,18 @@ impl BufferClient for Buffer {}
 
 //ip Buffer
 impl Buffer {
+    //ap tracked
+    /// Get the record of the buffer in the resource statistics, if any
+    pub fn tracked(&self) -> Option<&TrackedResource> {
+        self.tracked.as_deref()
+    }
+
+    //mp set_tracked
+    /// Set the record of the buffer in the resource statistics
+    pub fn set_tracked(&mut self, tracked: TrackedResource) {
+        self.tracked = Some(Rc::new(tracked));
+    }
+
     //ap is_none
     pub fn is_none(&self) -> bool {
         self.gl.borrow().is_none()
@@ -61,23 +118,38 @@ impl Buffer {
     /// Create the OpenGL ARRAY_BUFFER buffer using STATIC_DRAW - this copies the data in to OpenGL
     pub fn of_data(&mut self, data: &BufferData<Model3DWebGL>, render_context: &Model3DWebGL) {
         assert!(self.is_none());
-        let gl = render_context.create_buffer().unwrap();
-        render_context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&gl));
-        // render_context.buffer_data_with_u8_array(
-        //    WebGl2RenderingContext::ARRAY_BUFFER,
-        //    data.as_slice(),
-        //    WebGl2RenderingContext::STATIC_DRAW,
-        // );
+        self.of_data_with_usage(data, BufferUsage::Static, render_context);
+    }
+
+    //mp of_data_with_usage
+    /// Create the WebGL ARRAY_BUFFER buffer with a usage - this
+    /// copies the data in to WebGL
+    ///
+    /// If the buffer already exists then its storage is respecified
+    /// with the data, keeping the same WebGL buffer
+    pub fn of_data_with_usage(
+        &mut self,
+        data: &BufferData<Model3DWebGL>,
+        usage: BufferUsage,
+        render_context: &Model3DWebGL,
+    ) {
+        assert!(self.allocation.is_none());
+        if self.is_none() {
+            *self.gl.borrow_mut() = Some(render_context.create_buffer().unwrap());
+        }
+        render_context.bind_buffer(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            self.gl.borrow().as_ref(),
+        );
         unsafe {
             let buf_view = js_sys::Uint8Array::view(data.as_slice());
             render_context.buffer_data_with_array_buffer_view(
                 WebGl2RenderingContext::ARRAY_BUFFER,
                 &buf_view,
-                WebGl2RenderingContext::STATIC_DRAW,
+                gl_usage(usage),
             );
         }
         render_context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, None);
-        *self.gl.borrow_mut() = Some(gl);
         log_gl_buffer(
             render_context,
             self.gl.borrow().as_ref(),
@@ -89,6 +161,91 @@ impl Buffer {
         );
     }
 
+    //mp create
+    /// Create a WebGL ARRAY_BUFFER buffer of a byte length and usage,
+    /// with zeroed contents
+    pub fn create(
+        &mut self,
+        render_context: &Model3DWebGL,
+        byte_length: usize,
+        usage: BufferUsage,
+    ) {
+        assert!(self.is_none());
+        *self.gl.borrow_mut() = Some(render_context.create_buffer().unwrap());
+        self.orphan(render_context, byte_length, usage);
+    }
+
+    //mp of_pool_allocation
+    /// Create a buffer for an allocation within this (pool block) buffer
+    pub fn of_pool_allocation(&self, allocation: PoolAllocation) -> Self {
+        Self {
+            gl: self.gl.clone(),
+            byte_offset: allocation.byte_offset() as u32,
+            allocation: Some(Rc::new(allocation)),
+            tracked: self.tracked.clone(),
+        }
+    }
+
+    //mp update_data
+    /// Update (a portion of) the buffer data starting at a byte
+    /// offset (relative to the start of the data)
+    ///
+    /// The buffer is bound to COPY_WRITE_BUFFER, as WebGL2 does not
+    /// permit index data to be bound to ARRAY_BUFFER
+    pub fn update_data(&self, render_context: &Model3DWebGL, data: &[u8], byte_offset: usize) {
+        assert!(!self.is_none());
+        let byte_offset = self.byte_offset as usize + byte_offset;
+        render_context.bind_buffer(
+            WebGl2RenderingContext::COPY_WRITE_BUFFER,
+            self.gl.borrow().as_ref(),
+        );
+        render_context.buffer_sub_data_with_i32_and_u8_array(
+            WebGl2RenderingContext::COPY_WRITE_BUFFER,
+            byte_offset as i32,
+            data,
+        );
+        render_context.bind_buffer(WebGl2RenderingContext::COPY_WRITE_BUFFER, None);
+    }
+
+    //mp read_data
+    /// Read (a portion of) the buffer data starting at a byte offset
+    /// (relative to the start of the data) in to a slice
+    pub fn read_data(&self, render_context: &Model3DWebGL, byte_offset: usize, data: &mut [u8]) {
+        assert!(!self.is_none());
+        let byte_offset = self.byte_offset as usize + byte_offset;
+        render_context.bind_buffer(
+            WebGl2RenderingContext::COPY_READ_BUFFER,
+            self.gl.borrow().as_ref(),
+        );
+        render_context.get_buffer_sub_data_with_i32_and_u8_array(
+            WebGl2RenderingContext::COPY_READ_BUFFER,
+            byte_offset as i32,
+            data,
+        );
+        render_context.bind_buffer(WebGl2RenderingContext::COPY_READ_BUFFER, None);
+    }
+
+    //mp orphan
+    /// Replace the buffer storage with new zeroed storage of a byte
+    /// length and usage; it is bound to COPY_WRITE_BUFFER, so this may
+    /// be used for vertex and index data
+    ///
+    /// This must not be used for a buffer allocated from a pool
+    pub fn orphan(&self, render_context: &Model3DWebGL, byte_length: usize, usage: BufferUsage) {
+        assert!(!self.is_none());
+        assert!(self.allocation.is_none());
+        render_context.bind_buffer(
+            WebGl2RenderingContext::COPY_WRITE_BUFFER,
+            self.gl.borrow().as_ref(),
+        );
+        render_context.buffer_data_with_i32(
+            WebGl2RenderingContext::COPY_WRITE_BUFFER,
+            byte_length as i32,
+            gl_usage(usage),
+        );
+        render_context.bind_buffer(WebGl2RenderingContext::COPY_WRITE_BUFFER, None);
+    }
+
     //mp of_indices
     /// Create the OpenGL ELEMENT_ARRAY_BUFFER buffer using STATIC_DRAW - this copies the data in to OpenGL
     pub fn of_indices(
@@ -137,30 +294,37 @@ impl Buffer {
 
     //fp bind_to_vao_attr
     /// Bind the buffer as a vertex attribute to the current VAO
+    ///
+    /// Integer data uses vertexAttribIPointer if the format is
+    /// [AttributeKind::Integer], and is normalized if it is
+    /// [AttributeKind::Normalized]
+    ///
+    /// A count of 9 or 16 (mat3 or mat4) is bound as one column per
+    /// attribute location, starting at attr_id
+    #[allow(clippy::too_many_arguments)]
     pub fn bind_to_vao_attr(
         &self,
         render_context: &Model3DWebGL,
         attr_id: <Program as GlProgram>::GlAttrId,
         count: u32,
         ele_type: mod3d_base::BufferElementType,
+        format: AttributeFormat,
         byte_offset: u32,
         stride: u32,
     ) {
-        let ele_type = {
-            use mod3d_base::BufferElementType::*;
-            match ele_type {
-                Float32 => WebGl2RenderingContext::FLOAT,
-                Float16 => WebGl2RenderingContext::HALF_FLOAT,
-                Int8 => WebGl2RenderingContext::BYTE,
-                Int16 => WebGl2RenderingContext::SHORT,
-                Int32 => WebGl2RenderingContext::INT,
-            }
+        let (columns, rows) = crate::buffer::attribute_columns(count);
+        let column_size = rows * crate::buffer::ele_byte_size(ele_type);
+        // A stride of 0 would be tightly packed columns, not matrices
+        let stride = if stride == 0 && columns > 1 {
+            columns * column_size
+        } else {
+            stride
         };
+        let (ele_type, is_integer) = gl_ele_type(ele_type, format.signed);
         crate::console_log!("bind_to_vao_attr");
         let gl_buffer_ref = self.gl.borrow();
         let gl_buffer = gl_buffer_ref.as_ref();
         render_context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, gl_buffer);
-        render_context.enable_vertex_attrib_array(attr_id);
         log_gl_buffer(
             render_context,
             self.gl.borrow().as_ref(),
@@ -169,14 +333,29 @@ impl Buffer {
             0,
             0, // Do not output the buffer data
         );
-        render_context.vertex_attrib_pointer_with_i32(
-            attr_id,
-            count as i32,
-            ele_type,
-            false,
-            stride as i32,
-            byte_offset as i32,
-        );
+        for c in 0..columns {
+            let attr_id = attr_id + c;
+            let byte_offset = byte_offset + c * column_size;
+            render_context.enable_vertex_attrib_array(attr_id);
+            if is_integer && format.kind == AttributeKind::Integer {
+                render_context.vertex_attrib_i_pointer_with_i32(
+                    attr_id,
+                    rows as i32,
+                    ele_type,
+                    stride as i32,
+                    byte_offset as i32,
+                );
+            } else {
+                render_context.vertex_attrib_pointer_with_i32(
+                    attr_id,
+                    rows as i32,
+                    ele_type,
+                    is_integer && format.kind == AttributeKind::Normalized,
+                    stride as i32,
+                    byte_offset as i32,
+                );
+            }
+        }
     }
 
     //mp bind_buffer_range
@@ -210,7 +389,8 @@ impl Buffer {
     }
 
     //mp uniform_buffer
-    /// Create the OpenGL
+    /// Create the WebGL UNIFORM_BUFFER buffer, using DYNAMIC_DRAW if
+    /// it is dynamic and STATIC_DRAW otherwise
     pub fn uniform_buffer<F: Sized>(
         &mut self,
         render_context: &Model3DWebGL,
@@ -234,7 +414,11 @@ impl Buffer {
             render_context.buffer_data_with_array_buffer_view(
                 WebGl2RenderingContext::UNIFORM_BUFFER,
                 &buf_view,
-                WebGl2RenderingContext::STATIC_DRAW,
+                if is_dynamic {
+                    WebGl2RenderingContext::DYNAMIC_DRAW
+                } else {
+                    WebGl2RenderingContext::STATIC_DRAW
+                },
             );
         }
         render_context.bind_buffer(WebGl2RenderingContext::UNIFORM_BUFFER, None);
@@ -283,4 +467,14 @@ impl Buffer {
 }
 
 //ip GlBuffer for Buffer
-impl crate::GlBuffer for Buffer {}
+impl crate::GlBuffer for Buffer {
+    fn byte_offset(&self) -> u32 {
+        self.byte_offset
+    }
+    fn of_pool_allocation(&self, allocation: PoolAllocation) -> Self {
+        self.of_pool_allocation(allocation)
+    }
+    fn is_pool_allocation(&self) -> bool {
+        self.allocation.is_some()
+    }
+}
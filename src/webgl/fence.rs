@@ -0,0 +1,51 @@
+//a Imports
+use web_sys::{WebGl2RenderingContext, WebGlSync};
+
+//a Fence
+//tp Fence
+/// A WebGL2 sync object for the completion of GPU commands
+///
+/// WebGL does not permit a client wait to block, so waiting on a
+/// fence only flushes the commands; the browser orders buffer
+/// updates after the GPU reads that precede them, so this is safe
+#[derive(Debug)]
+pub struct Fence {
+    sync: WebGlSync,
+}
+
+//ip Fence
+impl Fence {
+    //fp insert
+    /// Insert a fence after the commands issued so far
+    pub fn insert(context: &WebGl2RenderingContext) -> Option<Self> {
+        context
+            .fence_sync(WebGl2RenderingContext::SYNC_GPU_COMMANDS_COMPLETE, 0)
+            .map(|sync| Self { sync })
+    }
+
+    //mp is_signalled
+    /// Return true if the GPU has completed the commands before the
+    /// fence, without waiting
+    pub fn is_signalled(&self, context: &WebGl2RenderingContext) -> bool {
+        context
+            .get_sync_parameter(&self.sync, WebGl2RenderingContext::SYNC_STATUS)
+            .as_f64()
+            == Some(WebGl2RenderingContext::SIGNALED as f64)
+    }
+
+    //mp wait
+    /// Flush the commands before the fence, and delete it
+    pub fn wait(self, context: &WebGl2RenderingContext) -> Result<(), String> {
+        let result = context.client_wait_sync_with_u32(
+            &self.sync,
+            WebGl2RenderingContext::SYNC_FLUSH_COMMANDS_BIT,
+            0,
+        );
+        context.delete_sync(Some(&self.sync));
+        if result == WebGl2RenderingContext::WAIT_FAILED {
+            Err("Wait on a fence failed".into())
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -8,10 +8,13 @@ The attributes and uniforms can be accessed in a simple common manner
 !*/
 
 //a Imports
+use std::cell::RefCell;
+
 use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation};
 
 use super::Shader;
-use crate::{GlProgram, GlShader, TextureId, UniformId};
+use crate::{ActiveVariable, GlProgram, GlShader, ProgramReflection, TextureId, UniformId};
+use crate::{TrackedResource, UniformCache, UniformStats, UniformValue};
 
 //a Program
 //tp Program
@@ -27,6 +30,12 @@ pub struct Program {
     uniform_buffers: Vec<(u32, usize)>,
     /// texture map from TextureId to uniform location and unit
     textures: Vec<(WebGlUniformLocation, TextureId, u32)>,
+    /// shadow copy of the uniform values last uploaded
+    uniform_cache: RefCell<UniformCache>,
+    /// default values of vertex attributes that a mesh may lack
+    attribute_defaults: Vec<(mod3d_base::VertexAttr, [f32; 4])>,
+    /// The record of the program in the resource statistics
+    tracked: Option<TrackedResource>,
 }
 
 //ip Program
@@ -36,11 +45,68 @@ impl Program {
         &self.program
     }
 
+    //mp reflection
+    /// Get the active attributes, uniforms and uniform blocks of the
+    /// program; uniforms that are members of uniform blocks are not
+    /// included
+    pub fn reflection(&self, context: &WebGl2RenderingContext) -> ProgramReflection {
+        let mut reflection = ProgramReflection::default();
+        let get_count = |pname| {
+            context
+                .get_program_parameter(&self.program, pname)
+                .as_f64()
+                .unwrap_or(0.) as u32
+        };
+
+        for i in 0..get_count(WebGl2RenderingContext::ACTIVE_ATTRIBUTES) {
+            if let Some(info) = context.get_active_attrib(&self.program, i) {
+                reflection.attributes.push(ActiveVariable::new(
+                    &info.name(),
+                    info.type_(),
+                    info.size() as usize,
+                ));
+            }
+        }
+
+        let num_uniforms = get_count(WebGl2RenderingContext::ACTIVE_UNIFORMS);
+        let indices = js_sys::Array::new();
+        for i in 0..num_uniforms {
+            indices.push(&i.into());
+        }
+        let block_indices = js_sys::Array::from(&context.get_active_uniforms(
+            &self.program,
+            &indices,
+            WebGl2RenderingContext::UNIFORM_BLOCK_INDEX,
+        ));
+        for i in 0..num_uniforms {
+            let block_index = block_indices.get(i).as_f64().unwrap_or(-1.);
+            if block_index >= 0. {
+                continue;
+            }
+            if let Some(info) = context.get_active_uniform(&self.program, i) {
+                reflection.uniforms.push(ActiveVariable::new(
+                    &info.name(),
+                    info.type_(),
+                    info.size() as usize,
+                ));
+            }
+        }
+
+        for i in 0..get_count(WebGl2RenderingContext::ACTIVE_UNIFORM_BLOCKS) {
+            if let Some(name) = context.get_active_uniform_block_name(&self.program, i) {
+                reflection.uniform_blocks.push(name);
+            }
+        }
+        reflection
+    }
+
     //fp link_program
-    /// Compile a program from a slice of kind/source pairs
+    /// Compile a program from a slice of kind/source pairs, capturing
+    /// the feedback varyings (if any) interleaved
     pub fn link_program(
         context: &WebGl2RenderingContext,
         shaders: &[&Shader],
+        feedback_varyings: &[&str],
     ) -> Result<Program, String> {
         let program = context
             .create_program()
@@ -49,6 +115,17 @@ impl Program {
         for shader in shaders {
             context.attach_shader(&program, shader.id());
         }
+        if !feedback_varyings.is_empty() {
+            let varyings: js_sys::Array = feedback_varyings
+                .iter()
+                .map(|v| wasm_bindgen::JsValue::from_str(v))
+                .collect();
+            context.transform_feedback_varyings(
+                &program,
+                &varyings,
+                WebGl2RenderingContext::INTERLEAVED_ATTRIBS,
+            );
+        }
         context.link_program(&program);
 
         if !context
@@ -65,15 +142,26 @@ impl Program {
         let uniforms = Vec::new();
         let uniform_buffers = Vec::new();
         let textures = Vec::new();
+        let uniform_cache = RefCell::new(UniformCache::default());
+        let attribute_defaults = Vec::new();
         Ok(Program {
             program,
             attributes,
             textures,
             uniforms,
             uniform_buffers,
+            uniform_cache,
+            attribute_defaults,
+            tracked: None,
         })
     }
 
+    //mp set_tracked
+    /// Set the record of the program in the resource statistics
+    pub fn set_tracked(&mut self, tracked: TrackedResource) {
+        self.tracked = Some(tracked);
+    }
+
     //mp add_attr_name
     /// Add an attribute to the [Program] from its name (that should be in the shader source)
     pub fn add_attr_name(
@@ -186,4 +274,22 @@ impl GlProgram for Program {
         }
         None
     }
+    fn uniform_changed(&self, uniform_id: UniformId, value: &UniformValue) -> bool {
+        self.uniform_cache.borrow_mut().update(uniform_id, value)
+    }
+    fn invalidate_uniform_cache(&self) {
+        self.uniform_cache.borrow_mut().invalidate();
+    }
+    fn uniform_stats(&self) -> UniformStats {
+        self.uniform_cache.borrow().stats()
+    }
+    fn set_attribute_defaults(&mut self, defaults: &[(mod3d_base::VertexAttr, [f32; 4])]) {
+        self.attribute_defaults = defaults.to_vec();
+    }
+    fn attribute_default(&self, vertex_attr: mod3d_base::VertexAttr) -> Option<[f32; 4]> {
+        self.attribute_defaults
+            .iter()
+            .find(|(va, _)| *va == vertex_attr)
+            .map(|(_, d)| *d)
+    }
 }
@@ -22,6 +22,14 @@ impl Shader {
         let (shader_type, shader_kind) = match shader_type {
             GlShaderType::Fragment => (WebGl2RenderingContext::FRAGMENT_SHADER, "fragment"),
             GlShaderType::Vertex => (WebGl2RenderingContext::VERTEX_SHADER, "vertex"),
+            GlShaderType::Geometry
+            | GlShaderType::TessControl
+            | GlShaderType::TessEvaluation
+            | GlShaderType::Compute => {
+                return Err(format!(
+                    "{shader_type:?} shaders are not supported by WebGL2"
+                ));
+            }
         };
         let shader = context
             .create_shader(shader_type)
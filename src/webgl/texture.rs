@@ -4,8 +4,85 @@ use std::rc::Rc;
 use mod3d_base::TextureClient;
 
 use super::Model3DWebGL;
+use crate::{PixelFormat, TextureKind, TrackedResource};
 use web_sys::{WebGl2RenderingContext, WebGlTexture};
 
+//a Functions
+//fi gl_target
+/// Get the WebGL target for a kind of texture
+fn gl_target(kind: TextureKind) -> u32 {
+    match kind {
+        TextureKind::Texture2D => WebGl2RenderingContext::TEXTURE_2D,
+        TextureKind::Texture2DArray => WebGl2RenderingContext::TEXTURE_2D_ARRAY,
+        TextureKind::Texture3D => WebGl2RenderingContext::TEXTURE_3D,
+        TextureKind::CubeMap => WebGl2RenderingContext::TEXTURE_CUBE_MAP,
+    }
+}
+
+//fi gl_data_format
+/// Get the WebGL format and type of texture data with a number of
+/// channels of an element type
+fn gl_data_format(channels: usize, ele_type: mod3d_base::BufferElementType) -> (u32, u32) {
+    let data_format = {
+        match channels {
+            1 => WebGl2RenderingContext::RED,
+            2 => WebGl2RenderingContext::RG,
+            3 => WebGl2RenderingContext::RGB,
+            4 => WebGl2RenderingContext::RGBA,
+            _ => WebGl2RenderingContext::RGBA,
+        }
+    };
+    let data_type = {
+        match ele_type {
+            mod3d_base::BufferElementType::Int8 => WebGl2RenderingContext::UNSIGNED_BYTE,
+            mod3d_base::BufferElementType::Int16 => WebGl2RenderingContext::UNSIGNED_SHORT,
+            mod3d_base::BufferElementType::Float16 => WebGl2RenderingContext::HALF_FLOAT,
+            mod3d_base::BufferElementType::Float32 => WebGl2RenderingContext::FLOAT,
+            _ => WebGl2RenderingContext::UNSIGNED_BYTE,
+        }
+    };
+    (data_format, data_type)
+}
+
+//fi gl_internal_format
+/// Get the WebGL sized internal format for texture data with a number
+/// of channels of an element type
+///
+/// 8-bit integer data is unsigned normalized; float data is stored as
+/// 16-bit floats, which (unlike 32-bit floats) WebGL2 can filter.
+/// WebGL2 has no 16-bit normalized formats
+fn gl_internal_format(
+    channels: usize,
+    ele_type: mod3d_base::BufferElementType,
+) -> Result<u32, String> {
+    use mod3d_base::BufferElementType::*;
+    let formats = match ele_type {
+        Int8 => [
+            WebGl2RenderingContext::R8,
+            WebGl2RenderingContext::RG8,
+            WebGl2RenderingContext::RGB8,
+            WebGl2RenderingContext::RGBA8,
+        ],
+        Float16 | Float32 => [
+            WebGl2RenderingContext::R16F,
+            WebGl2RenderingContext::RG16F,
+            WebGl2RenderingContext::RGB16F,
+            WebGl2RenderingContext::RGBA16F,
+        ],
+        _ => {
+            return Err(format!(
+                "WebGL2 textures of {ele_type:?} data are not supported"
+            ));
+        }
+    };
+    if !(1..=4).contains(&channels) {
+        return Err(format!(
+            "Textures must have 1 to 4 channels, not {channels}"
+        ));
+    }
+    Ok(formats[channels - 1])
+}
+
 //a Texture
 //tp Texture
 /// A simple structure provides a reference-counted OpenGl texture;
@@ -16,13 +93,25 @@ use web_sys::{WebGl2RenderingContext, WebGlTexture};
 pub struct Texture {
     /// The OpenGL Texture
     gl: Rc<Option<WebGlTexture>>,
+    /// Width and height of level 0 (WebGL cannot query them)
+    size: (usize, usize),
+    /// The kind of the texture
+    kind: TextureKind,
+    /// The record of the texture in the resource statistics, shared
+    /// by all clones
+    tracked: Option<Rc<TrackedResource>>,
 }
 
 //ip Default for Texture
 impl Default for Texture {
     fn default() -> Self {
         let gl = Rc::new(None);
-        Self { gl }
+        Self {
+            gl,
+            size: (0, 0),
+            kind: TextureKind::default(),
+            tracked: None,
+        }
     }
 }
 
@@ -37,79 +126,309 @@ impl Texture {
         (self.gl.as_ref()).as_ref()
     }
 
+    //ap size
+    /// Get the width and height of a level of the texture
+    pub fn size(&self, level: usize) -> (usize, usize) {
+        ((self.size.0 >> level).max(1), (self.size.1 >> level).max(1))
+    }
+
+    //mp set_tracked
+    /// Set the record of the texture in the resource statistics
+    pub fn set_tracked(&mut self, tracked: TrackedResource) {
+        self.tracked = Some(Rc::new(tracked));
+    }
+
     //mp is_none
     /// Return true if the buffer is not initialized
     pub fn is_none(&self) -> bool {
         self.gl.is_none()
     }
 
+    //ap kind
+    /// Get the kind of the texture
+    pub fn kind(&self) -> TextureKind {
+        self.kind
+    }
+
+    //ap target
+    /// Get the WebGL target that the texture is bound to
+    pub fn target(&self) -> u32 {
+        gl_target(self.kind)
+    }
+
     //mp of_texture
-    /// Create a texture
+    /// Create a texture of a kind, optionally generating mipmaps
+    ///
+    /// 3D textures clamp to their edges; 2D and array textures repeat
     pub fn of_texture(
         texture: &mod3d_base::Texture<Model3DWebGL>,
+        kind: TextureKind,
+        mipmaps: bool,
         render_context: &WebGl2RenderingContext,
-    ) -> Self {
+    ) -> Result<Self, String> {
         let (width, height, depth) = *texture.dims();
-        assert!(depth == 0);
-        assert!(height != 0);
+        kind.check_depth(depth != 0)?;
+        if height == 0 {
+            return Err("Texture data must have a non-zero height".into());
+        }
         let data_type = texture.data_type();
-        let data_format = {
-            match data_type.0 {
-                1 => WebGl2RenderingContext::RED,
-                2 => WebGl2RenderingContext::RG,
-                3 => WebGl2RenderingContext::RGB,
-                4 => WebGl2RenderingContext::RGBA,
-                _ => WebGl2RenderingContext::RGBA,
+        let (data_format, data_type) = gl_data_format(data_type.0 as usize, data_type.1);
+        let target = gl_target(kind);
+        let wrap = {
+            if kind == TextureKind::Texture3D {
+                WebGl2RenderingContext::CLAMP_TO_EDGE
+            } else {
+                WebGl2RenderingContext::REPEAT
             }
         };
-        let data_type = {
-            match data_type.1 {
-                mod3d_base::BufferElementType::Int8 => WebGl2RenderingContext::UNSIGNED_BYTE,
-                mod3d_base::BufferElementType::Int16 => WebGl2RenderingContext::UNSIGNED_SHORT,
-                mod3d_base::BufferElementType::Float16 => WebGl2RenderingContext::SHORT,
-                mod3d_base::BufferElementType::Float32 => WebGl2RenderingContext::FLOAT,
-                _ => WebGl2RenderingContext::UNSIGNED_BYTE,
+        let min_filter = {
+            if mipmaps {
+                WebGl2RenderingContext::LINEAR_MIPMAP_LINEAR
+            } else {
+                WebGl2RenderingContext::LINEAR
             }
         };
-        let gl = render_context.create_texture().unwrap();
-        render_context.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&gl));
-        render_context.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
-            WebGl2RenderingContext::TEXTURE_2D,
-            0,
-            WebGl2RenderingContext::RGB as i32,
-            width as i32,
-            height as i32,
-            0,
-            data_format,
-            data_type,
-            Some(texture.data()),
-        );
+        let gl = render_context
+            .create_texture()
+            .ok_or_else(|| String::from("Unable to create texture"))?;
+        render_context.bind_texture(target, Some(&gl));
+        let result = {
+            if kind.has_depth() {
+                render_context.tex_image_3d_with_opt_u8_array(
+                    target,
+                    0,
+                    WebGl2RenderingContext::RGB as i32,
+                    width as i32,
+                    height as i32,
+                    depth as i32,
+                    0,
+                    data_format,
+                    data_type,
+                    Some(texture.data()),
+                )
+            } else {
+                render_context
+                    .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                        target,
+                        0,
+                        WebGl2RenderingContext::RGB as i32,
+                        width as i32,
+                        height as i32,
+                        0,
+                        data_format,
+                        data_type,
+                        Some(texture.data()),
+                    )
+            }
+        };
+        if result.is_err() {
+            render_context.bind_texture(target, None);
+            render_context.delete_texture(Some(&gl));
+            return Err(format!("Failed to set the image of a {kind:?}"));
+        }
+        if mipmaps {
+            render_context.generate_mipmap(target);
+        }
 
+        render_context.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_WRAP_S, wrap as i32);
+        render_context.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_WRAP_T, wrap as i32);
+        if kind == TextureKind::Texture3D {
+            render_context.tex_parameteri(
+                target,
+                WebGl2RenderingContext::TEXTURE_WRAP_R,
+                wrap as i32,
+            );
+        }
         render_context.tex_parameteri(
-            WebGl2RenderingContext::TEXTURE_2D,
-            WebGl2RenderingContext::TEXTURE_WRAP_S,
-            WebGl2RenderingContext::REPEAT as i32,
+            target,
+            WebGl2RenderingContext::TEXTURE_MIN_FILTER,
+            min_filter as i32,
         );
         render_context.tex_parameteri(
-            WebGl2RenderingContext::TEXTURE_2D,
-            WebGl2RenderingContext::TEXTURE_WRAP_T,
-            WebGl2RenderingContext::REPEAT as i32,
+            target,
+            WebGl2RenderingContext::TEXTURE_MAG_FILTER,
+            WebGl2RenderingContext::LINEAR as i32,
         );
+        render_context.bind_texture(target, None);
+
+        Ok(Self {
+            gl: Rc::new(Some(gl)),
+            size: (width, height),
+            kind,
+            tracked: None,
+        })
+    }
+
+    //mp of_cube_faces
+    /// Create a cube map from the data of six square faces of a size
+    /// (in the order +X, -X, +Y, -Y, +Z, -Z), optionally generating
+    /// mipmaps
+    ///
+    /// The internal format has the channels of the data
+    pub fn of_cube_faces(
+        size: usize,
+        channels: usize,
+        ele_type: mod3d_base::BufferElementType,
+        faces: [&[u8]; 6],
+        mipmaps: bool,
+        render_context: &WebGl2RenderingContext,
+    ) -> Result<Self, String> {
+        let face_byte_size =
+            size * size * channels * crate::buffer::ele_byte_size(ele_type) as usize;
+        if size == 0 || faces.iter().any(|f| f.len() < face_byte_size) {
+            return Err(format!(
+                "Cube map faces must each have {face_byte_size} bytes of data"
+            ));
+        }
+        let internal_format = gl_internal_format(channels, ele_type)?;
+        let (data_format, data_type) = gl_data_format(channels, ele_type);
+        let target = WebGl2RenderingContext::TEXTURE_CUBE_MAP;
+        let min_filter = {
+            if mipmaps {
+                WebGl2RenderingContext::LINEAR_MIPMAP_LINEAR
+            } else {
+                WebGl2RenderingContext::LINEAR
+            }
+        };
+        let gl = render_context
+            .create_texture()
+            .ok_or_else(|| String::from("Unable to create texture"))?;
+        render_context.bind_texture(target, Some(&gl));
+        render_context.pixel_storei(WebGl2RenderingContext::UNPACK_ALIGNMENT, 1);
+        let mut result = Ok(());
+        for (i, face) in faces.iter().enumerate() {
+            result = result.and(
+                render_context
+                    .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                        WebGl2RenderingContext::TEXTURE_CUBE_MAP_POSITIVE_X + i as u32,
+                        0,
+                        internal_format as i32,
+                        size as i32,
+                        size as i32,
+                        0,
+                        data_format,
+                        data_type,
+                        Some(&face[..face_byte_size]),
+                    ),
+            );
+        }
+        render_context.pixel_storei(WebGl2RenderingContext::UNPACK_ALIGNMENT, 4);
+        if result.is_err() {
+            render_context.bind_texture(target, None);
+            render_context.delete_texture(Some(&gl));
+            return Err("Failed to set the faces of a cube map".into());
+        }
+        if mipmaps {
+            render_context.generate_mipmap(target);
+        }
+        for wrap in [
+            WebGl2RenderingContext::TEXTURE_WRAP_S,
+            WebGl2RenderingContext::TEXTURE_WRAP_T,
+            WebGl2RenderingContext::TEXTURE_WRAP_R,
+        ] {
+            render_context.tex_parameteri(
+                target,
+                wrap,
+                WebGl2RenderingContext::CLAMP_TO_EDGE as i32,
+            );
+        }
         render_context.tex_parameteri(
-            WebGl2RenderingContext::TEXTURE_2D,
+            target,
             WebGl2RenderingContext::TEXTURE_MIN_FILTER,
-            WebGl2RenderingContext::LINEAR as i32,
+            min_filter as i32,
         );
         render_context.tex_parameteri(
-            WebGl2RenderingContext::TEXTURE_2D,
+            target,
             WebGl2RenderingContext::TEXTURE_MAG_FILTER,
             WebGl2RenderingContext::LINEAR as i32,
         );
-        render_context.bind_texture(WebGl2RenderingContext::TEXTURE_2D, None);
+        render_context.bind_texture(target, None);
 
-        Self {
+        Ok(Self {
             gl: Rc::new(Some(gl)),
+            size: (size, size),
+            kind: TextureKind::CubeMap,
+            tracked: None,
+        })
+    }
+
+    //mp of_target
+    /// Create a 2D texture or cube map, of square faces of a size, with
+    /// a number of mipmap levels of uninitialized storage in a format,
+    /// to be rendered to
+    ///
+    /// [PixelFormat::RgbaF32] is stored as 16-bit floats, which are
+    /// filterable; rendering to them requires EXT_color_buffer_float
+    pub fn of_target(
+        kind: TextureKind,
+        size: usize,
+        format: PixelFormat,
+        levels: usize,
+        render_context: &WebGl2RenderingContext,
+    ) -> Result<Self, String> {
+        if kind != TextureKind::Texture2D && kind != TextureKind::CubeMap {
+            return Err(format!("Cannot render to a {kind:?}"));
+        }
+        if size == 0 || levels == 0 || (size >> (levels - 1)) == 0 {
+            return Err(format!(
+                "Cannot create a render target of size {size} with {levels} levels"
+            ));
         }
+        if format == PixelFormat::RgbaF32 {
+            let _ = render_context.get_extension("EXT_color_buffer_float");
+        }
+        let internal_format = match format {
+            PixelFormat::Rgba8 => WebGl2RenderingContext::RGBA8,
+            PixelFormat::RgbaF32 => WebGl2RenderingContext::RGBA16F,
+        };
+        let target = gl_target(kind);
+        let min_filter = {
+            if levels > 1 {
+                WebGl2RenderingContext::LINEAR_MIPMAP_LINEAR
+            } else {
+                WebGl2RenderingContext::LINEAR
+            }
+        };
+        let gl = render_context
+            .create_texture()
+            .ok_or_else(|| String::from("Unable to create texture"))?;
+        render_context.bind_texture(target, Some(&gl));
+        render_context.tex_storage_2d(
+            target,
+            levels as i32,
+            internal_format,
+            size as i32,
+            size as i32,
+        );
+        for wrap in [
+            WebGl2RenderingContext::TEXTURE_WRAP_S,
+            WebGl2RenderingContext::TEXTURE_WRAP_T,
+            WebGl2RenderingContext::TEXTURE_WRAP_R,
+        ] {
+            render_context.tex_parameteri(
+                target,
+                wrap,
+                WebGl2RenderingContext::CLAMP_TO_EDGE as i32,
+            );
+        }
+        render_context.tex_parameteri(
+            target,
+            WebGl2RenderingContext::TEXTURE_MIN_FILTER,
+            min_filter as i32,
+        );
+        render_context.tex_parameteri(
+            target,
+            WebGl2RenderingContext::TEXTURE_MAG_FILTER,
+            WebGl2RenderingContext::LINEAR as i32,
+        );
+        render_context.bind_texture(target, None);
+
+        Ok(Self {
+            gl: Rc::new(Some(gl)),
+            size: (size, size),
+            kind,
+            tracked: None,
+        })
     }
 
     //zz All done
@@ -3,6 +3,7 @@ use super::Model3DWebGL;
 use web_sys::{WebGl2RenderingContext, WebGlVertexArrayObject};
 
 use crate::webgl_log::log_gl_vao;
+use crate::TrackedResource;
 
 //a Vao
 //tp Vao
@@ -18,6 +19,12 @@ use crate::webgl_log::log_gl_vao;
 pub struct Vao {
     gl_vao: Box<WebGlVertexArrayObject>,
     index_type: u32,
+    /// Byte offset of the indices in the element array buffer
+    index_byte_offset: usize,
+    /// True if the VAO has an element array buffer
+    indexed: bool,
+    /// The record of the VAO in the resource statistics
+    tracked: Option<TrackedResource>,
 }
 
 //ip Vao
@@ -33,6 +40,40 @@ impl Vao {
         self.index_type
     }
 
+    //ap index_byte_offset
+    /// Get the byte offset of the indices in the element array buffer
+    pub fn index_byte_offset(&self) -> usize {
+        self.index_byte_offset
+    }
+
+    //ap indexed
+    /// Return true if the VAO has an element array buffer
+    pub fn indexed(&self) -> bool {
+        self.indexed
+    }
+
+    //mp set_tracked
+    /// Set the record of the VAO in the resource statistics
+    pub fn set_tracked(&mut self, tracked: TrackedResource) {
+        self.tracked = Some(tracked);
+    }
+
+    //fp create
+    /// This creates a VAO with no indices, leaving the VAO bound
+    pub fn create(render_context: &Model3DWebGL) -> Result<Self, ()> {
+        let gl_vao = render_context.create_vertex_array().ok_or(())?.into();
+        let vao = Self {
+            gl_vao,
+            index_type: WebGl2RenderingContext::UNSIGNED_INT,
+            index_byte_offset: 0,
+            indexed: false,
+            tracked: None,
+        };
+        render_context.bind_vertex_array(Some(&vao.gl_vao));
+        log_gl_vao(render_context, Some(&vao.gl_vao), "Vao::create");
+        Ok(vao)
+    }
+
     //fp create_from_indices
     /// This creates a VAO, and attaches the indices, leaving the VAO bound
     pub fn create_from_indices(
@@ -47,7 +88,14 @@ impl Vao {
                 _ => WebGl2RenderingContext::UNSIGNED_INT,
             }
         };
-        let vao = Self { gl_vao, index_type };
+        let index_byte_offset = indices.byte_offset as usize;
+        let vao = Self {
+            gl_vao,
+            index_type,
+            index_byte_offset,
+            indexed: true,
+            tracked: None,
+        };
         render_context.bind_vertex_array(Some(&vao.gl_vao));
         indices
             .gl_buffer()